@@ -1,20 +0,0 @@
-// AI interface module for communicating with Python AI brain
-// Handles natural language processing and intelligent decision making
-
-use anyhow::Result;
-
-pub struct AiInterface {
-    // Python process interface
-}
-
-impl AiInterface {
-    pub async fn new() -> Result<Self> {
-        Ok(Self {})
-    }
-
-    pub async fn process_natural_language(&self, _input: &str) -> Result<String> {
-        // Interface with Python AI brain
-        // For now, return placeholder
-        Ok("AI processing placeholder".to_string())
-    }
-}
@@ -0,0 +1,187 @@
+// In-process GGUF models through llama.cpp
+// A `.gguf` file at `AiConfig::model_path` becomes the AI backend when no other
+// one is configured, so planning, schemas and extraction all run on the CPU
+// with no external service. The model is loaded the first time it's asked.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::OnceCell;
+use tracing::info;
+
+use super::backend::{AiBackend, ChatMessage, CompletionRequest};
+
+/// Whether a model path points at a GGUF file rather than an ONNX model directory
+pub fn is_gguf(path: &str) -> bool {
+    Path::new(path).extension().map(|e| e.eq_ignore_ascii_case("gguf")).unwrap_or(false)
+}
+
+pub struct GgufBackend {
+    path: PathBuf,
+    context_length: u32,
+    model: OnceCell<Arc<LlamaModel>>,
+}
+
+impl GgufBackend {
+    pub fn new(path: &str, context_length: u32) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            context_length,
+            model: OnceCell::new(),
+        }
+    }
+
+    /// The model, loaded on first use. A failed load is retried by the next request.
+    async fn model(&self) -> Result<Arc<LlamaModel>> {
+        self.model
+            .get_or_try_init(|| async {
+                let path = self.path.clone();
+                let model = tokio::task::spawn_blocking(move || {
+                    LlamaModel::load_from_file(llama()?, &path, &LlamaModelParams::default())
+                        .with_context(|| format!("Failed to load GGUF model {}", path.display()))
+                })
+                .await
+                .map_err(|e| anyhow!("Loading the GGUF model panicked: {}", e))??;
+                info!("Loaded GGUF model from {}", self.path.display());
+                Ok(Arc::new(model))
+            })
+            .await
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl AiBackend for GgufBackend {
+    fn name(&self) -> &str {
+        "gguf"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String> {
+        let model = self.model().await?;
+        let context_length = self.context_length;
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || generate(&model, context_length, &request))
+            .await
+            .map_err(|e| anyhow!("GGUF generation panicked: {}", e))?
+    }
+}
+
+/// llama.cpp's global state, which may only be initialised once per process
+fn llama() -> Result<&'static LlamaBackend> {
+    static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+    static INIT: Mutex<()> = Mutex::new(());
+
+    let _guard = INIT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+    let backend = LlamaBackend::init().context("Failed to initialise llama.cpp")?;
+    Ok(BACKEND.get_or_init(|| backend))
+}
+
+/// Run one completion in a fresh context: evaluate the prompt, then sample until
+/// the model ends its turn or `max_tokens` is reached
+fn generate(model: &LlamaModel, context_length: u32, request: &CompletionRequest) -> Result<String> {
+    let prompt = render_prompt(model, &request.messages)?;
+    let tokens = model.str_to_token(&prompt, AddBos::Always)?;
+    if tokens.is_empty() {
+        return Ok(String::new());
+    }
+    let needed = tokens.len() + request.max_tokens as usize;
+    if needed > context_length as usize {
+        return Err(anyhow!(
+            "Prompt of {} tokens plus {} response tokens doesn't fit the {}-token context",
+            tokens.len(),
+            request.max_tokens,
+            context_length
+        ));
+    }
+
+    let params = LlamaContextParams::default()
+        .with_n_ctx(NonZeroU32::new(context_length))
+        .with_n_batch(context_length);
+    let mut ctx = model.new_context(llama()?, params).context("Failed to create a llama.cpp context")?;
+
+    let mut batch = LlamaBatch::new(tokens.len(), 1);
+    let last = tokens.len() as i32 - 1;
+    for (position, token) in (0_i32..).zip(tokens) {
+        // Only the last prompt token needs logits to sample from
+        batch.add(token, position, &[0], position == last)?;
+    }
+    ctx.decode(&mut batch)?;
+
+    let mut sampler = if request.temperature > 0.0 {
+        LlamaSampler::chain_simple([LlamaSampler::temp(request.temperature), LlamaSampler::dist(rand::random())])
+    } else {
+        LlamaSampler::greedy()
+    };
+
+    let mut position = batch.n_tokens();
+    let mut output: Vec<u8> = Vec::new();
+    for _ in 0..request.max_tokens {
+        let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+        sampler.accept(token);
+        if model.is_eog_token(token) {
+            break;
+        }
+        output.extend(model.token_to_bytes(token, Special::Tokenize)?);
+
+        batch.clear();
+        batch.add(token, position, &[0], true)?;
+        position += 1;
+        ctx.decode(&mut batch)?;
+    }
+
+    // Tokens can split a character, so decode once at the end
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Format a conversation with the model's own chat template, or as a plain
+/// transcript for models that don't ship one
+fn render_prompt(model: &LlamaModel, messages: &[ChatMessage]) -> Result<String> {
+    let template = match model.chat_template(None) {
+        Ok(template) => template,
+        Err(_) => return Ok(transcript(messages)),
+    };
+    let chat = messages
+        .iter()
+        .map(|m| LlamaChatMessage::new(m.role.as_str().to_string(), m.content.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(model.apply_chat_template(&template, &chat, true)?)
+}
+
+fn transcript(messages: &[ChatMessage]) -> String {
+    let mut prompt: String = messages
+        .iter()
+        .map(|m| format!("{}: {}\n\n", m.role.as_str(), m.content))
+        .collect();
+    prompt.push_str("assistant: ");
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_gguf_paths() {
+        assert!(is_gguf("models/qwen2.5-0.5b-instruct-q4_k_m.gguf"));
+        assert!(is_gguf("MODEL.GGUF"));
+        assert!(!is_gguf("models/intent"));
+        assert!(!is_gguf("models/intent/intent.onnx"));
+    }
+
+    #[test]
+    fn transcript_ends_with_the_assistant_turn() {
+        let prompt = transcript(&[ChatMessage::system("Be brief."), ChatMessage::user("Hi")]);
+        assert_eq!(prompt, "system: Be brief.\n\nuser: Hi\n\nassistant: ");
+    }
+}
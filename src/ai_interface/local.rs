@@ -0,0 +1,203 @@
+// In-process CPU models loaded from `AiConfig::model_path`
+// Runs an intent classifier and an optional slot tagger (BIO token classifier)
+// exported to ONNX, so natural language tasks work with no external service.
+// GGUF models at the same setting run through `gguf::GgufBackend` instead.
+//
+// Expected layout of the model directory:
+//   intent.onnx     - sequence classifier: (input_ids, attention_mask) -> logits [1, intents]
+//   slots.onnx      - optional token classifier: (input_ids, attention_mask) -> logits [1, seq, tags]
+//   tokenizer.json  - HuggingFace tokenizer matching both models
+//   labels.json     - { "intents": [...], "slots": ["O", "B-location", ...], "max_length": 128 }
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokenizers::Tokenizer;
+use tracing::info;
+use tract_onnx::prelude::*;
+
+type OnnxModel = TypedRunnableModel<TypedModel>;
+
+#[derive(Debug, Deserialize)]
+struct Labels {
+    intents: Vec<String>,
+    #[serde(default)]
+    slots: Vec<String>,
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_max_length() -> usize {
+    128
+}
+
+/// A span of the input tagged with a slot name, e.g. ("location", "dhaka, BD")
+#[derive(Debug, Clone)]
+pub struct SlotSpan {
+    pub slot: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalPrediction {
+    pub intent: String,
+    pub intent_confidence: f32,
+    pub slots: Vec<SlotSpan>,
+}
+
+pub struct LocalModel {
+    tokenizer: Tokenizer,
+    intent_model: OnnxModel,
+    slot_model: Option<OnnxModel>,
+    labels: Labels,
+}
+
+impl LocalModel {
+    pub fn load<P: AsRef<Path>>(model_path: P) -> Result<Self> {
+        let path = model_path.as_ref();
+
+        // Accept either the model directory or a path to intent.onnx inside it
+        let dir: PathBuf = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+
+        let labels: Labels = serde_json::from_str(
+            &std::fs::read_to_string(dir.join("labels.json")).context("Failed to read labels.json")?,
+        )?;
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| anyhow!("Failed to load tokenizer.json: {}", e))?;
+
+        let intent_path = if path.is_dir() { dir.join("intent.onnx") } else { path.to_path_buf() };
+        let intent_model = load_onnx(&intent_path, labels.max_length)?;
+
+        let slot_path = dir.join("slots.onnx");
+        let slot_model = if slot_path.exists() && !labels.slots.is_empty() {
+            Some(load_onnx(&slot_path, labels.max_length)?)
+        } else {
+            None
+        };
+
+        info!(
+            "Loaded local model from {} ({} intents, slot tagger: {})",
+            dir.display(),
+            labels.intents.len(),
+            if slot_model.is_some() { "yes" } else { "no" }
+        );
+
+        Ok(Self { tokenizer, intent_model, slot_model, labels })
+    }
+
+    pub fn predict(&self, input: &str) -> Result<LocalPrediction> {
+        let encoding = self.tokenizer
+            .encode(input, true)
+            .map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+
+        let max_length = self.labels.max_length;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        let mut mask: Vec<i64> = encoding.get_attention_mask().iter().map(|&m| m as i64).collect();
+        ids.resize(max_length, 0);
+        mask.resize(max_length, 0);
+
+        let input_ids: Tensor = tract_ndarray::Array2::from_shape_vec((1, max_length), ids)?.into();
+        let attention_mask: Tensor = tract_ndarray::Array2::from_shape_vec((1, max_length), mask)?.into();
+
+        let outputs = self.intent_model.run(tvec!(input_ids.clone().into(), attention_mask.clone().into()))?;
+        let logits: Vec<f32> = outputs[0].to_array_view::<f32>()?.iter().copied().collect();
+        let probs = softmax(&logits);
+        let (best, confidence) = probs
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .ok_or_else(|| anyhow!("Intent model returned no logits"))?;
+        let intent = self.labels.intents
+            .get(best)
+            .cloned()
+            .ok_or_else(|| anyhow!("Intent index {} has no label", best))?;
+
+        let slots = match &self.slot_model {
+            Some(model) => {
+                let outputs = model.run(tvec!(input_ids.into(), attention_mask.into()))?;
+                let logits = outputs[0].to_array_view::<f32>()?;
+                let tags = logits.shape().last().copied().unwrap_or(0);
+                let token_count = encoding.get_ids().len().min(max_length);
+                let flat: Vec<f32> = logits.iter().copied().collect();
+
+                let predicted: Vec<&str> = (0..token_count)
+                    .map(|t| {
+                        let row = &flat[t * tags..(t + 1) * tags];
+                        let best = row
+                            .iter()
+                            .enumerate()
+                            .max_by(|a, b| a.1.total_cmp(b.1))
+                            .map(|(i, _)| i)
+                            .unwrap_or(0);
+                        self.labels.slots.get(best).map(String::as_str).unwrap_or("O")
+                    })
+                    .collect();
+
+                decode_bio(input, &predicted, encoding.get_offsets())
+            }
+            None => Vec::new(),
+        };
+
+        Ok(LocalPrediction { intent, intent_confidence: confidence, slots })
+    }
+}
+
+fn load_onnx(path: &Path, max_length: usize) -> Result<OnnxModel> {
+    let model = tract_onnx::onnx()
+        .model_for_path(path)
+        .with_context(|| format!("Failed to load ONNX model {}", path.display()))?
+        .with_input_fact(0, i64::fact([1, max_length]).into())?
+        .with_input_fact(1, i64::fact([1, max_length]).into())?
+        .into_optimized()?
+        .into_runnable()?;
+    Ok(model)
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.iter().map(|e| e / sum).collect()
+}
+
+/// Merge B-/I- tagged tokens into text spans using the tokenizer's character offsets
+fn decode_bio(input: &str, tags: &[&str], offsets: &[(usize, usize)]) -> Vec<SlotSpan> {
+    let mut spans = Vec::new();
+    let mut current: Option<(String, usize, usize)> = None;
+
+    for (tag, &(start, end)) in tags.iter().zip(offsets) {
+        // Special tokens such as [CLS] have empty offsets
+        if start == end {
+            continue;
+        }
+
+        if let Some(slot) = tag.strip_prefix("B-") {
+            if let Some((name, s, e)) = current.take() {
+                spans.push(SlotSpan { slot: name, text: input[s..e].to_string() });
+            }
+            current = Some((slot.to_string(), start, end));
+        } else if let Some(slot) = tag.strip_prefix("I-") {
+            match current.as_mut() {
+                Some((name, _, e)) if name == slot => *e = end,
+                _ => {
+                    if let Some((name, s, e)) = current.take() {
+                        spans.push(SlotSpan { slot: name, text: input[s..e].to_string() });
+                    }
+                    current = Some((slot.to_string(), start, end));
+                }
+            }
+        } else if let Some((name, s, e)) = current.take() {
+            spans.push(SlotSpan { slot: name, text: input[s..e].to_string() });
+        }
+    }
+    if let Some((name, s, e)) = current {
+        spans.push(SlotSpan { slot: name, text: input[s..e].to_string() });
+    }
+
+    spans
+}
@@ -0,0 +1,61 @@
+// Scripted backend for tests and offline demos
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::backend::{AiBackend, CompletionRequest};
+
+pub struct MockBackend {
+    responses: Mutex<VecDeque<String>>,
+    fallback: Option<String>,
+    requests: Mutex<Vec<CompletionRequest>>,
+}
+
+impl MockBackend {
+    /// Replies with each queued response in order, then fails
+    pub fn new(responses: Vec<String>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            fallback: None,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Replies with the same response to every request
+    pub fn always(response: impl Into<String>) -> Self {
+        Self {
+            responses: Mutex::new(VecDeque::new()),
+            fallback: Some(response.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn push_response(&self, response: impl Into<String>) {
+        self.responses.lock().unwrap().push_back(response.into());
+    }
+
+    /// Requests received so far, for assertions
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl AiBackend for MockBackend {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String> {
+        self.requests.lock().unwrap().push(request.clone());
+
+        if let Some(response) = self.responses.lock().unwrap().pop_front() {
+            return Ok(response);
+        }
+        self.fallback
+            .clone()
+            .ok_or_else(|| anyhow!("Mock backend has no more responses"))
+    }
+}
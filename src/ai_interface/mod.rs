@@ -0,0 +1,414 @@
+// AI interface module for communicating with the AI brain
+// Handles natural language processing and intelligent decision making.
+// The model itself sits behind the `AiBackend` trait so it can be an HTTP
+// endpoint, a Python subprocess, an in-process GGUF model, or a mock. A local
+// ONNX model from `AiConfig::model_path` handles planning in-process (loaded the
+// first time a plan is needed), and without either we fall back to the rule-based parser.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+pub mod backend;
+pub mod gguf;
+pub mod local;
+pub mod mock;
+pub mod openai;
+pub mod subprocess;
+
+pub use backend::{AiBackend, ChatMessage, CompletionRequest, Role};
+pub use gguf::GgufBackend;
+pub use local::LocalModel;
+pub use mock::MockBackend;
+pub use openai::OpenAiBackend;
+pub use subprocess::SubprocessBackend;
+
+use crate::data::schema::{FieldSpec, FieldType, OutputSchema};
+use crate::engine::config::{AiBackendConfig, AiConfig};
+use crate::engine::parser::{parse_number, EntityKind, Location, RuleParser, TaskPlan};
+
+const ASSISTANT_PROMPT: &str = "You are Flash AI, an assistant that helps users plan web scraping tasks. \
+Answer briefly and ask for any missing details such as what to collect, where, how many and which fields.";
+
+const PLAN_PROMPT: &str = "Convert the user's web scraping request into a JSON object with these keys: \
+\"entity\" (what to collect, e.g. \"universities\"), \"count\" (integer or null), \
+\"location\" (string or null), \"fields\" (array of snake_case field names), \
+\"fields_explicit\" (true if the user listed the fields), \"qualifiers\" (array of extra filters), \
+\"confidence\" (0.0-1.0). Reply with the JSON object only.";
+
+const SCHEMA_PROMPT: &str = "Design the output columns for a web scraping task. Reply with a JSON object \
+{\"fields\": [{\"name\": snake_case string, \"type\": one of string|url|email|phone|number|date|country|region, \"required\": bool}]}. \
+Keep the fields the user asked for, in their order, and only mark a field required if a record is useless without it.";
+
+pub struct AiInterface {
+    backend: Option<Arc<dyn AiBackend>>,
+    model_path: Option<String>,
+    // Loaded on first use; None once loading has failed
+    local: OnceCell<Option<Arc<LocalModel>>>,
+    parser: RuleParser,
+    max_context_length: u32,
+    response_tokens: u32,
+}
+
+impl AiInterface {
+    pub async fn new(config: &AiConfig) -> Result<Self> {
+        // A GGUF model at model_path is a full LLM, so it answers as the backend
+        let gguf = config.model_path.as_deref().filter(|path| gguf::is_gguf(path));
+        let backend: Option<Arc<dyn AiBackend>> = match &config.backend {
+            AiBackendConfig::RuleBased => gguf.map(|path| {
+                Arc::new(GgufBackend::new(path, config.max_context_length)) as Arc<dyn AiBackend>
+            }),
+            AiBackendConfig::OpenAi { base_url, model, api_key_env, timeout_seconds } => {
+                let api_key = api_key_env
+                    .as_ref()
+                    .and_then(|var| std::env::var(var).ok());
+                Some(Arc::new(OpenAiBackend::new(base_url, model, api_key, *timeout_seconds)?))
+            }
+            AiBackendConfig::Subprocess { command, args, timeout_seconds } => {
+                Some(Arc::new(SubprocessBackend::spawn(command, args, *timeout_seconds)?))
+            }
+        };
+
+        if let (Some(path), false) = (gguf, matches!(config.backend, AiBackendConfig::RuleBased)) {
+            warn!("Ignoring GGUF model {} because [ai.backend] is configured", path);
+        }
+
+        let mut interface = Self::from_parts(backend, config);
+        interface.model_path = config.model_path.clone().filter(|path| !gguf::is_gguf(path));
+        Ok(interface)
+    }
+
+    /// Build an interface around an existing backend, e.g. a `MockBackend` in tests
+    pub fn with_backend(backend: Arc<dyn AiBackend>, config: &AiConfig) -> Self {
+        Self::from_parts(Some(backend), config)
+    }
+
+    fn from_parts(backend: Option<Arc<dyn AiBackend>>, config: &AiConfig) -> Self {
+        Self {
+            backend,
+            model_path: None,
+            local: OnceCell::new(),
+            parser: RuleParser::new(),
+            max_context_length: config.max_context_length,
+            response_tokens: config.response_tokens,
+        }
+    }
+
+    pub fn backend_name(&self) -> &str {
+        match (&self.backend, &self.model_path) {
+            (Some(backend), _) => backend.name(),
+            (None, Some(_)) => "local",
+            (None, None) => "rule-based",
+        }
+    }
+
+    pub fn has_model(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Tokens available for the prompt once the response allowance is reserved
+    pub fn prompt_budget(&self) -> usize {
+        let max_context = self.max_context_length as usize;
+        max_context.saturating_sub(response_allowance(max_context, self.response_tokens))
+    }
+
+    /// Send a conversation to the backend, trimmed to fit `max_context_length`
+    pub async fn complete(&self, messages: Vec<ChatMessage>, json_mode: bool) -> Result<String> {
+        let backend = self.backend
+            .as_ref()
+            .ok_or_else(|| anyhow!("No AI backend configured"))?;
+
+        let (messages, max_tokens) = fit_to_context(messages, self.max_context_length, self.response_tokens);
+        let request = CompletionRequest {
+            messages,
+            max_tokens,
+            temperature: if json_mode { 0.0 } else { 0.3 },
+            json_mode,
+        };
+
+        debug!("Sending {} messages to {} backend", request.messages.len(), backend.name());
+        backend.complete(&request).await
+    }
+
+    pub async fn process_natural_language(&self, input: &str) -> Result<String> {
+        if !self.has_model() {
+            return Ok(self.parser.parse(input).summary());
+        }
+
+        self.complete(vec![ChatMessage::system(ASSISTANT_PROMPT), ChatMessage::user(input)], false).await
+    }
+
+    /// Reply to an ongoing conversation, keeping as much history as fits the context
+    pub async fn chat(&self, history: &[ChatMessage]) -> Result<String> {
+        let mut messages = vec![ChatMessage::system(ASSISTANT_PROMPT)];
+        messages.extend(history.iter().cloned());
+        self.complete(messages, false).await
+    }
+
+    /// Turn a request into a task plan, using the local model or the backend when
+    /// available and the rule-based parser otherwise or when the model's answer is unusable
+    pub async fn plan(&self, input: &str) -> TaskPlan {
+        let rule_plan = self.parser.parse(input);
+
+        if self.model_path.is_some() {
+            let Some(model) = self.local_model().await else {
+                return rule_plan;
+            };
+            let text = input.to_string();
+            match tokio::task::spawn_blocking(move || model.predict(&text)).await {
+                Ok(Ok(prediction)) => return merge_local_prediction(prediction, rule_plan),
+                Ok(Err(e)) => warn!("Local model failed ({}), using rule-based parser", e),
+                Err(e) => warn!("Local model task panicked ({}), using rule-based parser", e),
+            }
+            return rule_plan;
+        }
+
+        if !self.has_model() {
+            return rule_plan;
+        }
+
+        let messages = vec![ChatMessage::system(PLAN_PROMPT), ChatMessage::user(input)];
+        match self.complete(messages, true).await {
+            Ok(response) => match extract_json(&response).and_then(|v| serde_json::from_value::<ModelPlan>(v).ok()) {
+                Some(model_plan) => model_plan.merge_into(rule_plan),
+                None => {
+                    warn!("AI backend returned an unparseable plan, using rule-based parser");
+                    rule_plan
+                }
+            },
+            Err(e) => {
+                warn!("AI backend failed ({}), using rule-based parser", e);
+                rule_plan
+            }
+        }
+    }
+}
+
+impl AiInterface {
+    /// The local model, loaded the first time it's needed so commands that
+    /// never plan don't pay for it, or fail when it's missing
+    async fn local_model(&self) -> Option<Arc<LocalModel>> {
+        let path = self.model_path.clone()?;
+        self.local
+            .get_or_init(|| async move {
+                match tokio::task::spawn_blocking(move || LocalModel::load(path)).await {
+                    Ok(Ok(model)) => Some(Arc::new(model)),
+                    Ok(Err(e)) => {
+                        warn!("Failed to load the local model ({:#}), using rule-based parser", e);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Loading the local model panicked ({}), using rule-based parser", e);
+                        None
+                    }
+                }
+            })
+            .await
+            .clone()
+    }
+
+    /// Propose a typed output schema for a plan. Explicitly requested fields are
+    /// always kept; the model only types them (and may add fields when the user
+    /// didn't list any). Falls back to name-based inference.
+    pub async fn propose_schema(&self, plan: &TaskPlan) -> OutputSchema {
+        let inferred = OutputSchema::infer(&plan.fields);
+        if !self.has_model() {
+            return inferred;
+        }
+
+        let request = format!(
+            "Task: {}\nRequested fields: {}{}",
+            plan.query,
+            plan.fields.join(", "),
+            if plan.fields_explicit { " (listed by the user)" } else { " (suggested defaults)" }
+        );
+        let messages = vec![ChatMessage::system(SCHEMA_PROMPT), ChatMessage::user(request)];
+
+        let response = match self.complete(messages, true).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("AI backend failed to propose a schema ({}), inferring from field names", e);
+                return inferred;
+            }
+        };
+
+        let proposed: Vec<FieldSpec> = extract_json(&response)
+            .and_then(|v| v.get("fields").cloned())
+            .and_then(|v| v.as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|field| {
+                let name = field.get("name")?.as_str()?.trim().to_lowercase().replace(' ', "_");
+                if name.is_empty() {
+                    return None;
+                }
+                let field_type = field
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .and_then(FieldType::from_name)
+                    .unwrap_or_else(|| FieldType::infer(&name));
+                let required = field.get("required").and_then(|r| r.as_bool()).unwrap_or(false);
+                Some(FieldSpec { name, field_type, required })
+            })
+            .collect();
+
+        if proposed.is_empty() {
+            warn!("AI backend returned an unusable schema, inferring from field names");
+            return inferred;
+        }
+        if !plan.fields_explicit {
+            return OutputSchema { fields: proposed };
+        }
+
+        // Keep exactly the user's columns, taking types from the model where it gave one
+        let fields = inferred
+            .fields
+            .into_iter()
+            .map(|spec| proposed.iter().find(|p| p.name == spec.name).cloned().unwrap_or(spec))
+            .collect();
+        OutputSchema { fields }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelPlan {
+    entity: Option<String>,
+    count: Option<u32>,
+    location: Option<String>,
+    #[serde(default)]
+    fields: Vec<String>,
+    #[serde(default)]
+    fields_explicit: bool,
+    #[serde(default)]
+    qualifiers: Vec<String>,
+    confidence: Option<f32>,
+}
+
+impl ModelPlan {
+    fn merge_into(self, mut plan: TaskPlan) -> TaskPlan {
+        if let Some(entity) = self.entity.filter(|e| !e.trim().is_empty()) {
+            plan.entity_kind = EntityKind::classify(&entity);
+            plan.entity = Some(entity.to_lowercase());
+        }
+        if self.count.is_some() {
+            plan.count = self.count;
+        }
+        if let Some(raw) = self.location.filter(|l| !l.trim().is_empty()) {
+            plan.location = Some(Location::from_raw(&raw));
+        }
+        if !self.fields.is_empty() {
+            plan.fields = self.fields;
+            plan.fields_explicit = self.fields_explicit;
+        }
+        if !self.qualifiers.is_empty() {
+            plan.qualifiers = self.qualifiers;
+        }
+        if let Some(confidence) = self.confidence {
+            plan.confidence = confidence.clamp(0.0, 1.0);
+        }
+        plan
+    }
+}
+
+/// Overlay the local model's intent and slots on the rule-based plan. The
+/// intent probability becomes the plan confidence so low-certainty predictions
+/// get routed to clarifying questions.
+fn merge_local_prediction(prediction: local::LocalPrediction, mut plan: TaskPlan) -> TaskPlan {
+    info!("Local model intent: {} ({:.2})", prediction.intent, prediction.intent_confidence);
+
+    let intent_kind = EntityKind::classify(&prediction.intent.replace('_', " "));
+    if intent_kind != EntityKind::Other {
+        plan.entity_kind = intent_kind;
+    }
+
+    let mut fields = Vec::new();
+    for span in prediction.slots {
+        match span.slot.as_str() {
+            "entity" => plan.entity = Some(span.text.to_lowercase()),
+            "count" => {
+                if let Some(count) = span.text.split_whitespace().find_map(parse_number) {
+                    plan.count = Some(count);
+                }
+            }
+            "location" => plan.location = Some(Location::from_raw(&span.text)),
+            "field" => fields.push(span.text.trim().to_lowercase().replace(' ', "_")),
+            _ => {}
+        }
+    }
+    if !fields.is_empty() {
+        plan.fields = fields;
+        plan.fields_explicit = true;
+    }
+
+    plan.confidence = prediction.intent_confidence;
+    plan
+}
+
+/// Rough token estimate (~4 characters per token) used for context budgeting
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count() / 4 + 1
+}
+
+/// Pull the first JSON object or array out of a model response, tolerating
+/// surrounding prose and markdown code fences
+pub fn extract_json(text: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str::<Value>(text.trim()) {
+        return Some(value);
+    }
+
+    for (open, close) in [('{', '}'), ('[', ']')] {
+        if let (Some(start), Some(end)) = (text.find(open), text.rfind(close)) {
+            if start < end {
+                if let Ok(value) = serde_json::from_str::<Value>(&text[start..=end]) {
+                    return Some(value);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Tokens reserved for the response, never more than half the context
+fn response_allowance(max_context: usize, response_tokens: u32) -> usize {
+    (response_tokens as usize).min(max_context / 2).max(1)
+}
+
+/// Drop the oldest non-system messages (and truncate the newest one if needed)
+/// so the prompt plus the reserved response fits in the context window
+fn fit_to_context(mut messages: Vec<ChatMessage>, max_context_length: u32, response_tokens: u32) -> (Vec<ChatMessage>, u32) {
+    let max_context = max_context_length as usize;
+    let response_tokens = response_allowance(max_context, response_tokens);
+    let budget = max_context.saturating_sub(response_tokens);
+
+    let total = |messages: &[ChatMessage]| messages.iter().map(|m| estimate_tokens(&m.content)).sum::<usize>();
+
+    while total(&messages) > budget {
+        let removable = messages
+            .iter()
+            .enumerate()
+            .take(messages.len().saturating_sub(1))
+            .find(|(_, m)| m.role != Role::System)
+            .map(|(i, _)| i);
+        match removable {
+            Some(index) => {
+                messages.remove(index);
+            }
+            None => break,
+        }
+    }
+
+    let used = total(&messages);
+    if used > budget {
+        if let Some(last) = messages.last_mut() {
+            let over = used - budget;
+            let keep_tokens = estimate_tokens(&last.content).saturating_sub(over + 1);
+            last.content = last.content.chars().take(keep_tokens * 4).collect();
+        }
+    }
+
+    let remaining = max_context.saturating_sub(total(&messages)).max(1);
+    (messages, remaining.min(response_tokens) as u32)
+}
@@ -0,0 +1,80 @@
+// OpenAI-compatible chat completions backend
+// Works with hosted APIs as well as local servers such as llama.cpp or Ollama
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::backend::{AiBackend, CompletionRequest};
+
+pub struct OpenAiBackend {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiBackend {
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>, timeout_seconds: u64) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(timeout_seconds))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+        })
+    }
+}
+
+#[async_trait]
+impl AiBackend for OpenAiBackend {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": request.messages,
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature,
+        });
+        if request.json_mode {
+            body["response_format"] = json!({ "type": "json_object" });
+        }
+
+        let mut http_request = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+        if let Some(key) = &self.api_key {
+            http_request = http_request.bearer_auth(key);
+        }
+
+        let response = http_request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach AI endpoint {}", self.base_url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            // OpenAI-style errors carry a message; proxies and local servers may send plain text
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|payload| payload["error"]["message"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(anyhow!("AI endpoint returned {}: {}", status, message.trim()));
+        }
+        let payload: Value = response.json().await.context("AI endpoint returned invalid JSON")?;
+
+        payload["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("AI endpoint response had no message content"))
+    }
+}
@@ -0,0 +1,114 @@
+// JSON-RPC bridge to an external AI process (e.g. the Python brain)
+// Requests and responses are exchanged as one JSON object per line over stdin/stdout
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use super::backend::{AiBackend, CompletionRequest};
+
+struct BridgeIo {
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+pub struct SubprocessBackend {
+    // Kept so the child is killed when the backend is dropped
+    _child: Child,
+    io: Mutex<BridgeIo>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl SubprocessBackend {
+    pub fn spawn(command: &str, args: &[String], timeout_seconds: u64) -> Result<Self> {
+        info!("Starting AI bridge process: {} {}", command, args.join(" "));
+
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start AI bridge process '{}'", command))?;
+
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("AI bridge has no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("AI bridge has no stdout"))?;
+
+        Ok(Self {
+            _child: child,
+            io: Mutex::new(BridgeIo { stdin, stdout: BufReader::new(stdout) }),
+            next_id: AtomicU64::new(1),
+            timeout: Duration::from_secs(timeout_seconds),
+        })
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        // Hold the lock for the whole round trip so responses can't interleave
+        let mut io = self.io.lock().await;
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        io.stdin.write_all(line.as_bytes()).await?;
+        io.stdin.flush().await?;
+
+        // A late answer is skipped by the next call, since its id won't match
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let mut response_line = String::new();
+            let read = tokio::time::timeout_at(deadline, io.stdout.read_line(&mut response_line))
+                .await
+                .map_err(|_| anyhow!("AI bridge did not answer '{}' within {}s", method, self.timeout.as_secs()))??;
+            if read == 0 {
+                return Err(anyhow!("AI bridge process exited"));
+            }
+
+            let response: Value = match serde_json::from_str(response_line.trim()) {
+                Ok(value) => value,
+                // Ignore stray output such as print() debugging from the bridge
+                Err(_) => continue,
+            };
+            if response["id"].as_u64() != Some(id) {
+                continue;
+            }
+
+            if let Some(error) = response.get("error") {
+                let message = error["message"].as_str().unwrap_or("unknown error");
+                return Err(anyhow!("AI bridge error: {}", message));
+            }
+            return Ok(response["result"].clone());
+        }
+    }
+}
+
+#[async_trait]
+impl AiBackend for SubprocessBackend {
+    fn name(&self) -> &str {
+        "subprocess"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String> {
+        let result = self.call("complete", serde_json::to_value(request)?).await?;
+        match result {
+            Value::String(text) => Ok(text),
+            Value::Object(ref obj) if obj.contains_key("content") => {
+                Ok(obj["content"].as_str().unwrap_or_default().to_string())
+            }
+            other => Ok(other.to_string()),
+        }
+    }
+}
@@ -1,20 +0,0 @@
-// Browser interface module for communicating with browser automation
-// Handles web scraping, JavaScript execution, and DOM manipulation
-
-use anyhow::Result;
-
-pub struct BrowserInterface {
-    // Browser automation interface
-}
-
-impl BrowserInterface {
-    pub async fn new() -> Result<Self> {
-        Ok(Self {})
-    }
-
-    pub async fn scrape_page(&self, _url: &str) -> Result<String> {
-        // Interface with browser automation
-        // For now, return placeholder
-        Ok("Browser scraping placeholder".to_string())
-    }
-}
@@ -0,0 +1,297 @@
+// Chromium driven over the DevTools protocol
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chromiumoxide::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide::cdp::browser_protocol::network::{CookieParam, TimeSinceEpoch};
+use chromiumoxide::cdp::browser_protocol::storage::{GetCookiesParams, SetCookiesParams};
+use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotFormat;
+use chromiumoxide::cdp::browser_protocol::target::{CreateBrowserContextParams, CreateTargetParams};
+use chromiumoxide::page::ScreenshotParams;
+use chromiumoxide::{Browser, BrowserConfig as CdpConfig, Page};
+use futures::StreamExt;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use crate::engine::config::BrowserConfig;
+use crate::networking::Cookie;
+use super::driver::{BrowserDriver, BrowserTab};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Counts resource requests the page has started; stable means the network is idle
+const RESOURCE_COUNT_JS: &str = "performance.getEntriesByType('resource').length";
+
+// Sets a <select>'s value and fires the events frameworks listen for; returns
+// false when no option has that value
+const SELECT_JS: &str = "((selector, value) => { const el = document.querySelector(selector); \
+if (!el) return null; if (![...el.options].some(o => o.value === value)) return false; \
+el.value = value; el.dispatchEvent(new Event('input', { bubbles: true })); \
+el.dispatchEvent(new Event('change', { bubbles: true })); return true; })";
+
+const SCROLL_JS: &str = "(() => { const before = document.documentElement.scrollHeight; \
+window.scrollTo(0, before); return before; })()";
+
+pub struct ChromiumDriver {
+    browser: Browser,
+    handler: JoinHandle<()>,
+    navigation_timeout: Duration,
+}
+
+impl ChromiumDriver {
+    pub async fn launch(config: &BrowserConfig) -> Result<Self> {
+        let mut builder = CdpConfig::builder()
+            .request_timeout(Duration::from_secs(config.navigation_timeout_seconds))
+            .window_size(1366, 900);
+        if !config.headless {
+            builder = builder.with_head();
+        }
+        if let Some(executable) = &config.executable {
+            builder = builder.chrome_executable(executable);
+        }
+        for arg in &config.extra_args {
+            builder = builder.arg(arg);
+        }
+        let cdp_config = builder.build().map_err(|e| anyhow!("Invalid browser config: {}", e))?;
+
+        let (browser, mut handler) = Browser::launch(cdp_config)
+            .await
+            .context("Failed to launch Chromium")?;
+
+        // The handler drives the CDP connection and must be polled for the browser to work
+        let handler = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+
+        info!("Launched Chromium (headless: {})", config.headless);
+        Ok(Self {
+            browser,
+            handler,
+            navigation_timeout: Duration::from_secs(config.navigation_timeout_seconds),
+        })
+    }
+
+    async fn open_target(&self, params: CreateTargetParams, url: &str) -> Result<Box<dyn BrowserTab>> {
+        let page = tokio::time::timeout(self.navigation_timeout, self.browser.new_page(params))
+            .await
+            .map_err(|_| anyhow!("Timed out opening {}", url))??;
+        page.wait_for_navigation().await?;
+        Ok(Box::new(ChromiumTab { page }))
+    }
+}
+
+impl Drop for ChromiumDriver {
+    fn drop(&mut self) {
+        self.handler.abort();
+    }
+}
+
+#[async_trait]
+impl BrowserDriver for ChromiumDriver {
+    fn name(&self) -> &str {
+        "chromium"
+    }
+
+    async fn open(&self, url: &str) -> Result<Box<dyn BrowserTab>> {
+        self.open_target(CreateTargetParams::new(url), url).await
+    }
+
+    async fn new_context(&self) -> Result<String> {
+        let id = self.browser
+            .create_browser_context(CreateBrowserContextParams::default())
+            .await
+            .context("Failed to create browser context")?;
+        Ok(id.inner().clone())
+    }
+
+    async fn open_in_context(&self, context: &str, url: &str) -> Result<Box<dyn BrowserTab>> {
+        let params = CreateTargetParams::builder()
+            .url(url)
+            .browser_context_id(BrowserContextId::new(context))
+            .build()
+            .map_err(|e| anyhow!("Invalid target for {}: {}", url, e))?;
+        self.open_target(params, url).await
+    }
+
+    async fn close_context(&self, context: &str) -> Result<()> {
+        self.browser.dispose_browser_context(BrowserContextId::new(context)).await?;
+        Ok(())
+    }
+
+    async fn set_cookies(&self, context: &str, cookies: &[Cookie]) -> Result<()> {
+        let params = cookies
+            .iter()
+            .map(|c| {
+                let mut builder = CookieParam::builder()
+                    .name(c.name.clone())
+                    .value(c.value.clone())
+                    // A leading dot makes Chromium send the cookie to subdomains too
+                    .domain(if c.include_subdomains { format!(".{}", c.domain) } else { c.domain.clone() })
+                    .path(c.path.clone())
+                    .secure(c.secure)
+                    .http_only(c.http_only);
+                if let Some(expires) = c.expires {
+                    builder = builder.expires(TimeSinceEpoch::new(expires as f64));
+                }
+                builder.build().map_err(|e| anyhow!("Invalid cookie {}: {}", c.name, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut request = SetCookiesParams::new(params);
+        request.browser_context_id = Some(BrowserContextId::new(context));
+        self.browser.execute(request).await?;
+        Ok(())
+    }
+
+    async fn cookies(&self, context: &str) -> Result<Vec<Cookie>> {
+        let request = GetCookiesParams {
+            browser_context_id: Some(BrowserContextId::new(context)),
+        };
+        let response = self.browser.execute(request).await?;
+        Ok(response
+            .result
+            .cookies
+            .into_iter()
+            .map(|c| Cookie {
+                include_subdomains: c.domain.starts_with('.'),
+                domain: c.domain.trim_start_matches('.').to_string(),
+                path: c.path,
+                name: c.name,
+                value: c.value,
+                expires: if c.session { None } else { Some(c.expires as i64) },
+                secure: c.secure,
+                http_only: c.http_only,
+            })
+            .collect())
+    }
+
+    async fn is_alive(&self) -> bool {
+        // The handler task ends when the CDP connection drops
+        !self.handler.is_finished() && self.browser.version().await.is_ok()
+    }
+}
+
+pub struct ChromiumTab {
+    page: Page,
+}
+
+#[async_trait]
+impl BrowserTab for ChromiumTab {
+    async fn goto(&mut self, url: &str) -> Result<()> {
+        self.page.goto(url).await?;
+        self.page.wait_for_navigation().await?;
+        Ok(())
+    }
+
+    async fn current_url(&self) -> Result<String> {
+        Ok(self.page.url().await?.unwrap_or_default())
+    }
+
+    async fn wait_for_selector(&self, selector: &str, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            if self.page.find_element(selector).await.is_ok() {
+                return Ok(());
+            }
+            if started.elapsed() > timeout {
+                bail!("Timed out waiting for '{}'", selector);
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn wait_for_network_idle(&self, idle: Duration, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        let mut last_count = -1i64;
+        let mut stable_since = Instant::now();
+
+        loop {
+            let ready = self.evaluate("document.readyState").await?;
+            let count = self.evaluate(RESOURCE_COUNT_JS).await?.as_i64().unwrap_or(0);
+            if count != last_count || ready.as_str() != Some("complete") {
+                last_count = count;
+                stable_since = Instant::now();
+            } else if stable_since.elapsed() >= idle {
+                debug!("Network idle after {:?} ({} resources)", started.elapsed(), count);
+                return Ok(());
+            }
+
+            if started.elapsed() > timeout {
+                // Long-polling pages never go fully idle; carry on with what we have
+                debug!("Network still busy after {:?}, continuing", timeout);
+                return Ok(());
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn evaluate(&self, script: &str) -> Result<Value> {
+        let result = self.page.evaluate(script).await?;
+        Ok(result.into_value::<Value>().unwrap_or(Value::Null))
+    }
+
+    async fn click(&self, selector: &str) -> Result<()> {
+        let element = self.page
+            .find_element(selector)
+            .await
+            .with_context(|| format!("No element matches '{}'", selector))?;
+        element.scroll_into_view().await?;
+        element.click().await?;
+        Ok(())
+    }
+
+    async fn type_text(&self, selector: &str, text: &str) -> Result<()> {
+        let element = self.page
+            .find_element(selector)
+            .await
+            .with_context(|| format!("No element matches '{}'", selector))?;
+        element.scroll_into_view().await?;
+        // Clear the field first so the text replaces any default value
+        element
+            .call_js_fn("function() { this.value = ''; }", false)
+            .await?;
+        element.click().await?;
+        element.type_str(text).await?;
+        Ok(())
+    }
+
+    async fn select_option(&self, selector: &str, value: &str) -> Result<()> {
+        let script = format!("{}({}, {})", SELECT_JS, Value::from(selector), Value::from(value));
+        match self.evaluate(&script).await? {
+            Value::Bool(true) => Ok(()),
+            Value::Bool(false) => bail!("'{}' has no option with value {:?}", selector, value),
+            _ => bail!("No element matches '{}'", selector),
+        }
+    }
+
+    async fn scroll_to_bottom(&self) -> Result<bool> {
+        let before = self.evaluate(SCROLL_JS).await?.as_i64().unwrap_or(0);
+        sleep(Duration::from_millis(500)).await;
+        let after = self.evaluate("document.documentElement.scrollHeight").await?.as_i64().unwrap_or(0);
+        Ok(after > before)
+    }
+
+    async fn content(&self) -> Result<String> {
+        Ok(self.page.content().await?)
+    }
+
+    async fn screenshot(&self) -> Result<Vec<u8>> {
+        let params = ScreenshotParams::builder()
+            .format(CaptureScreenshotFormat::Png)
+            .full_page(true)
+            .build();
+        Ok(self.page.screenshot(params).await?)
+    }
+
+    async fn close(self: Box<Self>) -> Result<()> {
+        self.page.close().await?;
+        Ok(())
+    }
+}
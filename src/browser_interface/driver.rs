@@ -0,0 +1,69 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::networking::Cookie;
+
+/// Something that can open browser tabs: real Chromium or a fixture-serving fake
+#[async_trait]
+pub trait BrowserDriver: Send + Sync {
+    /// Short name used in logs and status output
+    fn name(&self) -> &str;
+
+    /// Open a new tab and navigate it to `url`
+    async fn open(&self, url: &str) -> Result<Box<dyn BrowserTab>>;
+
+    /// Create an isolated context (its own cookies and storage), returning its id
+    async fn new_context(&self) -> Result<String>;
+
+    /// Open a new tab inside a context from `new_context`
+    async fn open_in_context(&self, context: &str, url: &str) -> Result<Box<dyn BrowserTab>>;
+
+    /// Close a context and every tab in it
+    async fn close_context(&self, context: &str) -> Result<()>;
+
+    /// Add cookies to a context, e.g. ones an HTTP request or an earlier run received
+    async fn set_cookies(&self, context: &str, cookies: &[Cookie]) -> Result<()>;
+
+    /// Every cookie the context holds
+    async fn cookies(&self, context: &str) -> Result<Vec<Cookie>>;
+
+    /// Whether the browser process is still there and answering
+    async fn is_alive(&self) -> bool;
+}
+
+/// A single tab with a loaded page
+#[async_trait]
+pub trait BrowserTab: Send + Sync {
+    async fn goto(&mut self, url: &str) -> Result<()>;
+
+    async fn current_url(&self) -> Result<String>;
+
+    /// Wait until an element matching `selector` exists
+    async fn wait_for_selector(&self, selector: &str, timeout: Duration) -> Result<()>;
+
+    /// Wait until no new network requests have started for `idle`
+    async fn wait_for_network_idle(&self, idle: Duration, timeout: Duration) -> Result<()>;
+
+    async fn evaluate(&self, script: &str) -> Result<Value>;
+
+    async fn click(&self, selector: &str) -> Result<()>;
+
+    /// Replace the value of the input matching `selector` by typing `text`
+    async fn type_text(&self, selector: &str, text: &str) -> Result<()>;
+
+    /// Choose the option with `value` in the `<select>` matching `selector`
+    async fn select_option(&self, selector: &str, value: &str) -> Result<()>;
+
+    /// Scroll to the bottom of the page, returning false once the page stops growing
+    async fn scroll_to_bottom(&self) -> Result<bool>;
+
+    /// The rendered DOM as HTML
+    async fn content(&self) -> Result<String>;
+
+    /// Full-page PNG screenshot
+    async fn screenshot(&self) -> Result<Vec<u8>>;
+
+    async fn close(self: Box<Self>) -> Result<()>;
+}
@@ -0,0 +1,306 @@
+// Fixture-serving browser for tests
+// Pages are plain HTML registered per URL; clicks can swap in a follow-up
+// fixture to simulate "load more" buttons and other interactions.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::networking::Cookie;
+use super::driver::{BrowserDriver, BrowserTab};
+use super::pool::BrowserLauncher;
+
+// A 1x1 transparent PNG stands in for screenshots
+const PLACEHOLDER_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+#[derive(Default)]
+struct FakeState {
+    pages: HashMap<String, String>,
+    // (url, selector) -> html shown after clicking
+    clicks: HashMap<(String, String), String>,
+    // (url, script) -> value returned by evaluate
+    scripts: HashMap<(String, String), Value>,
+    actions: Vec<String>,
+    contexts: Vec<String>,
+    cookies: HashMap<String, Vec<Cookie>>,
+    next_context: usize,
+    crashed: bool,
+    launches: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct FakeBrowser {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeBrowser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_page(self, url: &str, html: &str) -> Self {
+        self.state.lock().unwrap().pages.insert(url.to_string(), html.to_string());
+        self
+    }
+
+    pub fn with_fixture<P: AsRef<Path>>(self, url: &str, path: P) -> Result<Self> {
+        let html = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read fixture {}", path.as_ref().display()))?;
+        Ok(self.with_page(url, &html))
+    }
+
+    /// Replace the page content when `selector` is clicked on `url`
+    pub fn on_click(self, url: &str, selector: &str, html_after: &str) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .clicks
+            .insert((url.to_string(), selector.to_string()), html_after.to_string());
+        self
+    }
+
+    pub fn on_evaluate(self, url: &str, script: &str, result: Value) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .scripts
+            .insert((url.to_string(), script.to_string()), result);
+        self
+    }
+
+    /// Everything the tabs were asked to do, e.g. "goto https://..." or "click a.more"
+    pub fn actions(&self) -> Vec<String> {
+        self.state.lock().unwrap().actions.clone()
+    }
+
+    /// Make the browser stop responding, as if Chromium had crashed
+    pub fn crash(&self) {
+        self.state.lock().unwrap().crashed = true;
+    }
+
+    /// How many times a pool has (re)started this browser
+    pub fn launches(&self) -> usize {
+        self.state.lock().unwrap().launches
+    }
+
+    /// Contexts that are currently open
+    pub fn contexts(&self) -> Vec<String> {
+        self.state.lock().unwrap().contexts.clone()
+    }
+
+    fn page(&self, url: &str) -> Result<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .pages
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow!("No fixture for {}", url))
+    }
+
+    fn log(&self, action: String) {
+        self.state.lock().unwrap().actions.push(action);
+    }
+}
+
+#[async_trait]
+impl BrowserDriver for FakeBrowser {
+    fn name(&self) -> &str {
+        "fake"
+    }
+
+    async fn open(&self, url: &str) -> Result<Box<dyn BrowserTab>> {
+        if self.state.lock().unwrap().crashed {
+            bail!("Browser is not responding");
+        }
+        self.log(format!("goto {}", url));
+        let html = self.page(url)?;
+        Ok(Box::new(FakeTab {
+            browser: self.clone(),
+            url: url.to_string(),
+            html: Mutex::new(html),
+        }))
+    }
+
+    async fn new_context(&self) -> Result<String> {
+        let mut state = self.state.lock().unwrap();
+        state.next_context += 1;
+        let id = format!("context-{}", state.next_context);
+        state.contexts.push(id.clone());
+        Ok(id)
+    }
+
+    async fn open_in_context(&self, context: &str, url: &str) -> Result<Box<dyn BrowserTab>> {
+        if !self.state.lock().unwrap().contexts.iter().any(|c| c == context) {
+            bail!("Unknown browser context {}", context);
+        }
+        self.open(url).await
+    }
+
+    async fn close_context(&self, context: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.contexts.retain(|c| c != context);
+        state.cookies.remove(context);
+        Ok(())
+    }
+
+    async fn set_cookies(&self, context: &str, cookies: &[Cookie]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let jar = state.cookies.entry(context.to_string()).or_default();
+        for cookie in cookies {
+            jar.retain(|c| !(c.domain == cookie.domain && c.path == cookie.path && c.name == cookie.name));
+            jar.push(cookie.clone());
+        }
+        Ok(())
+    }
+
+    async fn cookies(&self, context: &str) -> Result<Vec<Cookie>> {
+        Ok(self.state.lock().unwrap().cookies.get(context).cloned().unwrap_or_default())
+    }
+
+    async fn is_alive(&self) -> bool {
+        !self.state.lock().unwrap().crashed
+    }
+}
+
+#[async_trait]
+impl BrowserLauncher for FakeBrowser {
+    async fn launch(&self) -> Result<Arc<dyn BrowserDriver>> {
+        let mut state = self.state.lock().unwrap();
+        // A restarted browser comes back healthy with no contexts
+        state.crashed = false;
+        state.contexts.clear();
+        state.cookies.clear();
+        state.launches += 1;
+        drop(state);
+        Ok(Arc::new(self.clone()))
+    }
+}
+
+pub struct FakeTab {
+    browser: FakeBrowser,
+    url: String,
+    html: Mutex<String>,
+}
+
+impl FakeTab {
+    fn has_element(&self, selector: &str) -> Result<bool> {
+        let parsed = Selector::parse(selector).map_err(|e| anyhow!("Invalid selector '{}': {:?}", selector, e))?;
+        let document = Html::parse_document(&self.html.lock().unwrap());
+        let found = document.select(&parsed).next().is_some();
+        Ok(found)
+    }
+}
+
+#[async_trait]
+impl BrowserTab for FakeTab {
+    async fn goto(&mut self, url: &str) -> Result<()> {
+        if self.browser.state.lock().unwrap().crashed {
+            bail!("Browser is not responding");
+        }
+        self.browser.log(format!("goto {}", url));
+        *self.html.lock().unwrap() = self.browser.page(url)?;
+        self.url = url.to_string();
+        Ok(())
+    }
+
+    async fn current_url(&self) -> Result<String> {
+        Ok(self.url.clone())
+    }
+
+    async fn wait_for_selector(&self, selector: &str, _timeout: Duration) -> Result<()> {
+        self.browser.log(format!("wait_for {}", selector));
+        if self.has_element(selector)? {
+            Ok(())
+        } else {
+            bail!("Timed out waiting for '{}'", selector)
+        }
+    }
+
+    async fn wait_for_network_idle(&self, _idle: Duration, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    async fn evaluate(&self, script: &str) -> Result<Value> {
+        self.browser.log(format!("evaluate {}", script));
+        let state = self.browser.state.lock().unwrap();
+        Ok(state
+            .scripts
+            .get(&(self.url.clone(), script.to_string()))
+            .cloned()
+            .unwrap_or(Value::Null))
+    }
+
+    async fn click(&self, selector: &str) -> Result<()> {
+        self.browser.log(format!("click {}", selector));
+        if !self.has_element(selector)? {
+            bail!("No element matches '{}'", selector);
+        }
+        let next = self.browser
+            .state
+            .lock()
+            .unwrap()
+            .clicks
+            .get(&(self.url.clone(), selector.to_string()))
+            .cloned();
+        if let Some(html) = next {
+            *self.html.lock().unwrap() = html;
+        }
+        Ok(())
+    }
+
+    async fn type_text(&self, selector: &str, text: &str) -> Result<()> {
+        self.browser.log(format!("type {} {}", selector, text));
+        if !self.has_element(selector)? {
+            bail!("No element matches '{}'", selector);
+        }
+        Ok(())
+    }
+
+    async fn select_option(&self, selector: &str, value: &str) -> Result<()> {
+        self.browser.log(format!("select {} {}", selector, value));
+        let parsed = Selector::parse(selector).map_err(|e| anyhow!("Invalid selector '{}': {:?}", selector, e))?;
+        let document = Html::parse_document(&self.html.lock().unwrap());
+        let element = document
+            .select(&parsed)
+            .next()
+            .ok_or_else(|| anyhow!("No element matches '{}'", selector))?;
+        let option = Selector::parse("option").expect("static selector");
+        let found = element
+            .select(&option)
+            .any(|o| o.value().attr("value").unwrap_or_default() == value);
+        if !found {
+            bail!("'{}' has no option with value {:?}", selector, value);
+        }
+        Ok(())
+    }
+
+    async fn scroll_to_bottom(&self) -> Result<bool> {
+        self.browser.log("scroll".to_string());
+        Ok(false)
+    }
+
+    async fn content(&self) -> Result<String> {
+        Ok(self.html.lock().unwrap().clone())
+    }
+
+    async fn screenshot(&self) -> Result<Vec<u8>> {
+        self.browser.log("screenshot".to_string());
+        Ok(PLACEHOLDER_PNG.to_vec())
+    }
+
+    async fn close(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+// Browser interface module for communicating with browser automation
+// Handles web scraping, JavaScript execution, and DOM manipulation.
+// The browser sits behind the `BrowserDriver` trait: Chromium over the DevTools
+// protocol in production, `FakeBrowser` with fixture HTML in tests. Browsers
+// come from a shared `BrowserPool`; each task renders through its own session.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+pub mod chromium;
+pub mod driver;
+#[cfg(test)]
+pub mod fake;
+pub mod pool;
+pub mod steps;
+
+pub use chromium::ChromiumDriver;
+pub use driver::{BrowserDriver, BrowserTab};
+#[cfg(test)]
+pub use fake::FakeBrowser;
+pub use pool::{BrowserLauncher, BrowserPool, BrowserSession, ChromiumLauncher, PooledTab};
+pub use steps::BrowserStep;
+
+use crate::data::{AuthMethod, AuthProfile};
+use crate::engine::config::BrowserConfig;
+use crate::networking::{CookieJar, WarcWriter};
+
+/// What to wait for after the page loads
+#[derive(Debug, Clone)]
+pub enum WaitCondition {
+    None,
+    NetworkIdle,
+    Selector(String),
+}
+
+/// How to render a page before taking its DOM
+#[derive(Debug, Clone)]
+pub struct ScrapeOptions {
+    pub wait: WaitCondition,
+    /// Keep scrolling to the bottom (up to this many times) to trigger lazy loading
+    pub max_scrolls: u32,
+    /// "Load more" button to keep clicking until it disappears
+    pub load_more_selector: Option<String>,
+    pub max_load_more_clicks: u32,
+    /// Script to run once the page is ready; its result is returned with the page
+    pub script: Option<String>,
+    /// Capture a PNG screenshot along with the DOM
+    pub screenshot: bool,
+}
+
+impl Default for ScrapeOptions {
+    fn default() -> Self {
+        Self {
+            wait: WaitCondition::NetworkIdle,
+            max_scrolls: 0,
+            load_more_selector: None,
+            max_load_more_clicks: 10,
+            script: None,
+            screenshot: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedPage {
+    /// Final URL after redirects
+    pub url: String,
+    pub html: String,
+    pub script_result: Option<Value>,
+    pub screenshot: Option<Vec<u8>>,
+}
+
+pub struct BrowserInterface {
+    config: BrowserConfig,
+    // Browsers are only launched the first time a page needs rendering
+    pool: Arc<BrowserPool>,
+    // Cookie context and idle tabs of the task this interface belongs to
+    session: Arc<BrowserSession>,
+    // Where rendered DOMs are archived, when WARC output is on
+    archive: Option<Arc<WarcWriter>>,
+}
+
+impl BrowserInterface {
+    /// `max_tabs` caps concurrent tabs across every task sharing the pool
+    pub async fn new(config: &BrowserConfig, max_tabs: usize) -> Result<Self> {
+        let launcher: Arc<dyn BrowserLauncher> = Arc::new(ChromiumLauncher::new(config));
+        Ok(Self::with_launcher(launcher, config, max_tabs))
+    }
+
+    /// Use a specific launcher, e.g. `FakeBrowser` in tests
+    pub fn with_launcher(launcher: Arc<dyn BrowserLauncher>, config: &BrowserConfig, max_tabs: usize) -> Self {
+        let pool = Arc::new(BrowserPool::new(launcher, config, max_tabs));
+        Self {
+            config: config.clone(),
+            session: Arc::new(BrowserSession::new(pool.clone(), "shared")),
+            pool,
+            archive: None,
+        }
+    }
+
+    /// Write every DOM rendered through this interface (and the per-task ones
+    /// made from it) to WARC files
+    pub fn with_archive(mut self, archive: Arc<WarcWriter>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// An interface sharing this one's browsers but with its own browser
+    /// context and tabs, whose cookies live in `cookies`; call `finish` when
+    /// the task is done
+    pub fn for_task(&self, task_id: &str, cookies: Arc<CookieJar>) -> Self {
+        Self {
+            config: self.config.clone(),
+            pool: self.pool.clone(),
+            session: Arc::new(BrowserSession::new(self.pool.clone(), task_id).with_cookies(cookies)),
+            archive: self.archive.clone(),
+        }
+    }
+
+    /// Close this task's tabs and contexts, copying their cookies to the task's jar
+    pub async fn finish(&self) -> Result<()> {
+        self.session.close().await
+    }
+
+    pub fn pool(&self) -> &Arc<BrowserPool> {
+        &self.pool
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.config.navigation_timeout_seconds)
+    }
+
+    fn network_idle(&self) -> Duration {
+        Duration::from_millis(self.config.network_idle_ms)
+    }
+
+    /// Check out a tab on `url` for callers that need to drive the page
+    /// themselves; hand it back with `release_tab`
+    pub async fn open_tab(&self, url: &str) -> Result<PooledTab> {
+        self.session.acquire(url).await
+    }
+
+    pub async fn release_tab(&self, tab: PooledTab) {
+        self.session.release(tab).await
+    }
+
+    pub async fn scrape_page(&self, url: &str) -> Result<String> {
+        Ok(self.render(url, &ScrapeOptions::default()).await?.html)
+    }
+
+    /// Full-page PNG screenshot of `url` once the network settles
+    pub async fn screenshot(&self, url: &str) -> Result<Vec<u8>> {
+        let options = ScrapeOptions { screenshot: true, ..ScrapeOptions::default() };
+        self.render(url, &options)
+            .await?
+            .screenshot
+            .ok_or_else(|| anyhow::anyhow!("No screenshot captured for {}", url))
+    }
+
+    /// Load a page, wait for it to settle, expand lazy content and return the rendered DOM
+    pub async fn render(&self, url: &str, options: &ScrapeOptions) -> Result<RenderedPage> {
+        info!("Rendering {} in browser", url);
+        let tab = self.open_tab(url).await?;
+
+        let result = self.render_in_tab(tab.tab(), options).await;
+        self.finish_with(tab, result.is_ok()).await;
+        let page = result?;
+        self.archive(&page).await?;
+        Ok(page)
+    }
+
+    async fn archive(&self, page: &RenderedPage) -> Result<()> {
+        if let Some(archive) = &self.archive {
+            archive.record_rendered(&page.url, &page.html).await?;
+        }
+        Ok(())
+    }
+
+    // Tabs that hit an error may be stuck mid-load, so they aren't reused
+    async fn finish_with(&self, tab: PooledTab, ok: bool) {
+        if ok {
+            self.session.release(tab).await;
+        } else {
+            self.session.discard(tab).await;
+        }
+    }
+
+    async fn render_in_tab(&self, tab: &dyn BrowserTab, options: &ScrapeOptions) -> Result<RenderedPage> {
+        self.wait(tab, &options.wait).await?;
+
+        for scroll in 0..options.max_scrolls {
+            if !tab.scroll_to_bottom().await? {
+                debug!("Page stopped growing after {} scroll(s)", scroll + 1);
+                break;
+            }
+            tab.wait_for_network_idle(self.network_idle(), self.timeout()).await?;
+        }
+
+        if let Some(selector) = &options.load_more_selector {
+            for click in 0..options.max_load_more_clicks {
+                if tab.click(selector).await.is_err() {
+                    debug!("'{}' gone after {} click(s)", selector, click);
+                    break;
+                }
+                tab.wait_for_network_idle(self.network_idle(), self.timeout()).await?;
+            }
+        }
+
+        let script_result = match &options.script {
+            Some(script) => Some(tab.evaluate(script).await?),
+            None => None,
+        };
+
+        let screenshot = if options.screenshot {
+            Some(tab.screenshot().await?)
+        } else {
+            None
+        };
+
+        Ok(RenderedPage {
+            url: tab.current_url().await?,
+            html: tab.content().await?,
+            script_result,
+            screenshot,
+        })
+    }
+
+    /// Open `url` and run scripted steps, returning the DOM captured at each
+    /// `extract` step (or the final page when there are none). A failing step
+    /// is reported with its number and action.
+    pub async fn run_steps(&self, url: &str, steps: &[BrowserStep]) -> Result<Vec<RenderedPage>> {
+        info!("Running {} browser step(s) from {}", steps.len(), url);
+        let mut tab = self.open_tab(url).await?;
+
+        let mut pages = Vec::new();
+        let mut result = Ok(());
+        for (index, step) in steps.iter().enumerate() {
+            debug!("Step {}: {}", index + 1, step);
+            result = self
+                .run_step(tab.tab_mut(), step, &mut pages)
+                .await
+                .with_context(|| format!("Step {} ({}) failed", index + 1, step));
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() && !steps.iter().any(|s| matches!(s, BrowserStep::Extract)) {
+            result = self.snapshot(tab.tab()).await.map(|page| pages.push(page));
+        }
+
+        self.finish_with(tab, result.is_ok()).await;
+        result?;
+        for page in &pages {
+            self.archive(page).await?;
+        }
+        Ok(pages)
+    }
+
+    /// Log in through a form so the session cookies land in this task's jar
+    pub async fn login(&self, profile: &AuthProfile) -> Result<()> {
+        let AuthMethod::Form {
+            login_url,
+            username,
+            password,
+            username_selector,
+            password_selector,
+            submit_selector,
+            success_selector,
+        } = &profile.method
+        else {
+            return Ok(());
+        };
+
+        info!("Logging in to {} through the browser", profile.domain);
+        let mut steps = vec![
+            BrowserStep::WaitFor { selector: username_selector.clone(), timeout_seconds: None },
+            BrowserStep::Type { selector: username_selector.clone(), text: username.clone(), secret: false },
+            BrowserStep::Type { selector: password_selector.clone(), text: password.expose().to_string(), secret: true },
+            BrowserStep::Click { selector: submit_selector.clone() },
+        ];
+        if let Some(selector) = success_selector {
+            steps.push(BrowserStep::WaitFor { selector: selector.clone(), timeout_seconds: None });
+        }
+
+        self.run_steps(login_url, &steps)
+            .await
+            .with_context(|| format!("Login to {} failed", profile.domain))?;
+        Ok(())
+    }
+
+    async fn run_step(&self, tab: &mut dyn BrowserTab, step: &BrowserStep, pages: &mut Vec<RenderedPage>) -> Result<()> {
+        match step {
+            BrowserStep::Navigate { url } => {
+                tab.goto(url).await?;
+                tab.wait_for_network_idle(self.network_idle(), self.timeout()).await
+            }
+            BrowserStep::Type { selector, text, .. } => tab.type_text(selector, text).await,
+            BrowserStep::Select { selector, value } => {
+                tab.select_option(selector, value).await?;
+                tab.wait_for_network_idle(self.network_idle(), self.timeout()).await
+            }
+            BrowserStep::Click { selector } => {
+                tab.click(selector).await?;
+                tab.wait_for_network_idle(self.network_idle(), self.timeout()).await
+            }
+            BrowserStep::WaitFor { selector, timeout_seconds } => {
+                let timeout = timeout_seconds.map(Duration::from_secs).unwrap_or_else(|| self.timeout());
+                tab.wait_for_selector(selector, timeout).await
+            }
+            BrowserStep::Scroll { times } => {
+                for _ in 0..*times {
+                    if !tab.scroll_to_bottom().await? {
+                        break;
+                    }
+                    tab.wait_for_network_idle(self.network_idle(), self.timeout()).await?;
+                }
+                Ok(())
+            }
+            BrowserStep::Extract => {
+                pages.push(self.snapshot(tab).await?);
+                Ok(())
+            }
+        }
+    }
+
+    async fn snapshot(&self, tab: &dyn BrowserTab) -> Result<RenderedPage> {
+        Ok(RenderedPage {
+            url: tab.current_url().await?,
+            html: tab.content().await?,
+            script_result: None,
+            screenshot: None,
+        })
+    }
+
+    async fn wait(&self, tab: &dyn BrowserTab, wait: &WaitCondition) -> Result<()> {
+        match wait {
+            WaitCondition::None => Ok(()),
+            WaitCondition::NetworkIdle => tab.wait_for_network_idle(self.network_idle(), self.timeout()).await,
+            WaitCondition::Selector(selector) => tab.wait_for_selector(selector, self.timeout()).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "https://example.com/list";
+    const SEARCH: &str = "https://example.com/search";
+
+    fn interface(browser: &FakeBrowser) -> BrowserInterface {
+        BrowserInterface::with_launcher(Arc::new(browser.clone()), &BrowserConfig::default(), 4)
+    }
+
+    #[tokio::test]
+    async fn render_clicks_load_more_until_it_disappears() {
+        let browser = FakeBrowser::new()
+            .with_page(LIST, "<ul><li>one</li></ul><a class=\"more\">More</a>")
+            .on_click(LIST, "a.more", "<ul><li>one</li><li>two</li></ul>");
+        let options = ScrapeOptions { load_more_selector: Some("a.more".to_string()), ..ScrapeOptions::default() };
+
+        let page = interface(&browser).render(LIST, &options).await.unwrap();
+
+        assert_eq!(page.url, LIST);
+        assert!(page.html.contains("<li>two</li>"));
+        let clicks = browser.actions().iter().filter(|a| *a == "click a.more").count();
+        assert_eq!(clicks, 2);
+    }
+
+    #[tokio::test]
+    async fn render_returns_script_results_and_screenshots() {
+        let browser = FakeBrowser::new()
+            .with_page(LIST, "<p>hello</p>")
+            .on_evaluate(LIST, "document.title", Value::String("Title".to_string()));
+        let options = ScrapeOptions {
+            script: Some("document.title".to_string()),
+            screenshot: true,
+            ..ScrapeOptions::default()
+        };
+
+        let page = interface(&browser).render(LIST, &options).await.unwrap();
+
+        assert_eq!(page.script_result, Some(Value::String("Title".to_string())));
+        assert!(page.screenshot.map(|png| png.starts_with(&[0x89, 0x50, 0x4e, 0x47])).unwrap_or(false));
+    }
+
+    #[tokio::test]
+    async fn fixtures_load_from_files() {
+        let path = std::env::temp_dir().join(format!("flash-fixture-{}.html", std::process::id()));
+        std::fs::write(&path, "<p>from a file</p>").unwrap();
+        let browser = FakeBrowser::new().with_fixture(LIST, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let html = interface(&browser).scrape_page(LIST).await.unwrap();
+        assert!(html.contains("from a file"));
+    }
+
+    #[tokio::test]
+    async fn render_fails_for_unknown_pages() {
+        let browser = FakeBrowser::new();
+        assert!(interface(&browser).render(LIST, &ScrapeOptions::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn run_steps_captures_a_page_at_each_extract_step() {
+        let browser = FakeBrowser::new()
+            .with_page(SEARCH, "<form><input name=\"q\"><select id=\"country\"><option value=\"bd\">BD</option></select><button>Go</button></form>")
+            .on_click(SEARCH, "button", "<ul class=\"results\"><li>Dhaka University</li></ul>")
+            .with_page(LIST, "<ul class=\"results\"><li>page two</li></ul>");
+        let steps = vec![
+            BrowserStep::Type { selector: "input[name=q]".to_string(), text: "universities".to_string(), secret: false },
+            BrowserStep::Select { selector: "#country".to_string(), value: "bd".to_string() },
+            BrowserStep::Click { selector: "button".to_string() },
+            BrowserStep::WaitFor { selector: "ul.results".to_string(), timeout_seconds: Some(1) },
+            BrowserStep::Extract,
+            BrowserStep::Navigate { url: LIST.to_string() },
+            BrowserStep::Extract,
+        ];
+
+        let pages = interface(&browser).run_steps(SEARCH, &steps).await.unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].html.contains("Dhaka University"));
+        assert_eq!(pages[1].url, LIST);
+        assert!(browser.actions().contains(&"type input[name=q] universities".to_string()));
+    }
+
+    #[tokio::test]
+    async fn run_steps_without_extract_returns_the_final_page() {
+        let browser = FakeBrowser::new().with_page(SEARCH, "<p>start</p>").with_page(LIST, "<p>end</p>");
+        let steps = vec![BrowserStep::Navigate { url: LIST.to_string() }];
+
+        let pages = interface(&browser).run_steps(SEARCH, &steps).await.unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].html.contains("end"));
+    }
+
+    #[tokio::test]
+    async fn failing_steps_are_reported_by_number() {
+        let browser = FakeBrowser::new().with_page(SEARCH, "<form></form>");
+        let steps = vec![
+            BrowserStep::WaitFor { selector: "form".to_string(), timeout_seconds: Some(1) },
+            BrowserStep::Click { selector: "button.missing".to_string() },
+        ];
+
+        let error = interface(&browser).run_steps(SEARCH, &steps).await.unwrap_err();
+
+        assert!(format!("{:#}", error).contains("Step 2"));
+    }
+
+    #[tokio::test]
+    async fn tasks_render_in_their_own_contexts() {
+        let browser = FakeBrowser::new().with_page(LIST, "<p>hi</p>");
+        let shared = interface(&browser);
+        let first = shared.for_task("first", Arc::new(CookieJar::new("first")));
+        let second = shared.for_task("second", Arc::new(CookieJar::new("second")));
+
+        first.render(LIST, &ScrapeOptions::default()).await.unwrap();
+        second.render(LIST, &ScrapeOptions::default()).await.unwrap();
+        assert_eq!(browser.contexts().len(), 2);
+
+        first.finish().await.unwrap();
+        second.finish().await.unwrap();
+        assert!(browser.contexts().is_empty());
+    }
+}
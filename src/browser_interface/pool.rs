@@ -0,0 +1,565 @@
+// Browser pool
+// A fixed number of browser processes shared by every task. Each task gets a
+// `BrowserSession` with its own browser context per process, so cookies never
+// leak between tasks, and keeps its tabs around for reuse until they have
+// loaded `max_navigations_per_tab` pages. Idle tabs still count against the
+// pool's capacity; when it's used up, another task's idle tab is closed to
+// make room. Crashed browsers are relaunched the next time they're needed.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, warn};
+
+use crate::engine::config::BrowserConfig;
+use crate::networking::CookieJar;
+use super::chromium::ChromiumDriver;
+use super::driver::{BrowserDriver, BrowserTab};
+
+/// Starts browser processes for the pool
+#[async_trait]
+pub trait BrowserLauncher: Send + Sync {
+    async fn launch(&self) -> Result<Arc<dyn BrowserDriver>>;
+}
+
+pub struct ChromiumLauncher {
+    config: BrowserConfig,
+}
+
+impl ChromiumLauncher {
+    pub fn new(config: &BrowserConfig) -> Self {
+        Self { config: config.clone() }
+    }
+}
+
+#[async_trait]
+impl BrowserLauncher for ChromiumLauncher {
+    async fn launch(&self) -> Result<Arc<dyn BrowserDriver>> {
+        Ok(Arc::new(ChromiumDriver::launch(&self.config).await?))
+    }
+}
+
+struct Instance {
+    driver: Arc<dyn BrowserDriver>,
+    // Bumped on every relaunch so sessions can tell their contexts are gone
+    generation: u64,
+    open_tabs: usize,
+}
+
+pub struct BrowserPool {
+    launcher: Arc<dyn BrowserLauncher>,
+    instances: Mutex<Vec<Option<Instance>>>,
+    // Held while (re)launching a browser, so slow launches don't block the others
+    launching: Vec<Mutex<()>>,
+    permits: Arc<Semaphore>,
+    // Every session's idle tabs, oldest first; each keeps its tab's permit
+    idle: Mutex<Vec<IdleTab>>,
+    // Signalled when a tab goes idle, so a task waiting for room can close it
+    idled: Notify,
+    sessions: AtomicU64,
+    capacity: usize,
+    tabs_per_browser: usize,
+    max_navigations: u32,
+    generations: AtomicU64,
+}
+
+impl BrowserPool {
+    /// `max_tabs` caps how many tabs may be open at once across the whole pool
+    pub fn new(launcher: Arc<dyn BrowserLauncher>, config: &BrowserConfig, max_tabs: usize) -> Self {
+        let pool_size = config.pool_size.max(1);
+        let tabs_per_browser = config.tabs_per_browser.max(1);
+        let capacity = (pool_size * tabs_per_browser).min(max_tabs.max(1));
+
+        Self {
+            launcher,
+            instances: Mutex::new((0..pool_size).map(|_| None).collect()),
+            launching: (0..pool_size).map(|_| Mutex::new(())).collect(),
+            permits: Arc::new(Semaphore::new(capacity)),
+            idle: Mutex::new(Vec::new()),
+            idled: Notify::new(),
+            sessions: AtomicU64::new(0),
+            capacity,
+            tabs_per_browser,
+            max_navigations: config.max_navigations_per_tab.max(1),
+            generations: AtomicU64::new(0),
+        }
+    }
+
+    /// Tabs that can be open at the same time
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Browsers currently running
+    pub async fn running(&self) -> usize {
+        self.instances.lock().await.iter().filter(|i| i.is_some()).count()
+    }
+
+    /// Tabs open across every browser, idle ones included
+    pub async fn open_tabs(&self) -> usize {
+        self.instances.lock().await.iter().flatten().map(|i| i.open_tabs).sum()
+    }
+
+    /// Pick the least busy browser for a new tab, launching or relaunching it as needed
+    async fn checkout(&self) -> Result<(usize, u64, Arc<dyn BrowserDriver>)> {
+        let (index, current) = {
+            let instances = self.instances.lock().await;
+            let index = instances
+                .iter()
+                .enumerate()
+                .filter_map(|(i, instance)| instance.as_ref().map(|inst| (i, inst.open_tabs)))
+                .filter(|(_, open)| *open < self.tabs_per_browser)
+                .min_by_key(|(_, open)| *open)
+                .map(|(i, _)| i)
+                // Every running browser is full: start another if there's room
+                .or_else(|| instances.iter().position(|i| i.is_none()))
+                .or_else(|| {
+                    instances
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, i)| i.as_ref().map(|i| i.open_tabs).unwrap_or(0))
+                        .map(|(i, _)| i)
+                })
+                .ok_or_else(|| anyhow!("Browser pool is empty"))?;
+            (index, instances[index].as_ref().map(|i| (i.generation, i.driver.clone())))
+        };
+
+        let healthy = match &current {
+            Some((_, driver)) => driver.is_alive().await,
+            None => false,
+        };
+        if !healthy {
+            if current.is_some() {
+                warn!("Browser {} stopped responding, restarting it", index);
+            }
+            self.relaunch(index, current.map(|(generation, _)| generation)).await?;
+        }
+
+        let mut instances = self.instances.lock().await;
+        let instance = instances[index]
+            .as_mut()
+            .ok_or_else(|| anyhow!("Browser {} is not running", index))?;
+        instance.open_tabs += 1;
+        Ok((index, instance.generation, instance.driver.clone()))
+    }
+
+    async fn launch(&self) -> Result<Instance> {
+        let driver = self.launcher.launch().await?;
+        let generation = self.generations.fetch_add(1, Ordering::SeqCst) + 1;
+        info!("Started {} browser (generation {})", driver.name(), generation);
+        Ok(Instance { driver, generation, open_tabs: 0 })
+    }
+
+    /// Launch browser `index` in place of `stale` (None when it isn't running),
+    /// unless another task got there first
+    async fn relaunch(&self, index: usize, stale: Option<u64>) -> Result<()> {
+        let _launching = self.launching[index].lock().await;
+        if self.current_generation(index).await != stale {
+            return Ok(());
+        }
+        let instance = self.launch().await?;
+        self.instances.lock().await[index] = Some(instance);
+        Ok(())
+    }
+
+    /// A tab on browser `index` was closed
+    async fn checkin(&self, index: usize, generation: u64) {
+        let mut instances = self.instances.lock().await;
+        if let Some(instance) = instances[index].as_mut().filter(|i| i.generation == generation) {
+            instance.open_tabs = instance.open_tabs.saturating_sub(1);
+        }
+    }
+
+    /// Relaunch browser `index` unless someone already replaced that generation
+    async fn restart(&self, index: usize, generation: u64) -> Result<()> {
+        if self.current_generation(index).await == Some(generation) {
+            warn!("Browser {} crashed, restarting it", index);
+        }
+        self.relaunch(index, Some(generation)).await
+    }
+
+    /// Room for one more open tab: a free permit, or one taken from the
+    /// oldest idle tab, which is closed. Waits while every tab is busy.
+    async fn permit(&self) -> Result<OwnedSemaphorePermit> {
+        loop {
+            if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+                return Ok(permit);
+            }
+            let oldest = {
+                let mut idle = self.idle.lock().await;
+                (!idle.is_empty()).then(|| idle.remove(0))
+            };
+            if let Some(idle) = oldest {
+                debug!("Closing an idle tab of session {} to make room", idle.session);
+                if let Err(e) = idle.tab.close().await {
+                    debug!("Failed to close tab: {}", e);
+                }
+                self.checkin(idle.index, idle.generation).await;
+                return Ok(idle.permit);
+            }
+            tokio::select! {
+                permit = self.permits.clone().acquire_owned() => return Ok(permit?),
+                _ = self.idled.notified() => continue,
+            }
+        }
+    }
+
+    /// The most recently used idle tab of `session`, if it has one
+    async fn take_idle(&self, session: u64) -> Option<IdleTab> {
+        let mut idle = self.idle.lock().await;
+        let position = idle.iter().rposition(|t| t.session == session)?;
+        Some(idle.remove(position))
+    }
+
+    async fn park(&self, tab: IdleTab) {
+        self.idle.lock().await.push(tab);
+        self.idled.notify_one();
+    }
+
+    /// Take every idle tab of `session` out of the pool
+    async fn drain_idle(&self, session: u64) -> Vec<IdleTab> {
+        let mut idle = self.idle.lock().await;
+        let (mine, others): (Vec<IdleTab>, Vec<IdleTab>) = idle.drain(..).partition(|t| t.session == session);
+        *idle = others;
+        mine
+    }
+
+    async fn current_generation(&self, index: usize) -> Option<u64> {
+        self.instances.lock().await[index].as_ref().map(|i| i.generation)
+    }
+
+    /// The driver for browser `index`, unless it has been relaunched since `generation`
+    async fn driver(&self, index: usize, generation: u64) -> Option<Arc<dyn BrowserDriver>> {
+        self.instances.lock().await[index]
+            .as_ref()
+            .filter(|i| i.generation == generation)
+            .map(|i| i.driver.clone())
+    }
+}
+
+/// A tab checked out of the pool; hand it back with `BrowserSession::release`
+pub struct PooledTab {
+    tab: Box<dyn BrowserTab>,
+    index: usize,
+    generation: u64,
+    navigations: u32,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledTab {
+    pub fn tab(&self) -> &dyn BrowserTab {
+        self.tab.as_ref()
+    }
+
+    pub fn tab_mut(&mut self) -> &mut dyn BrowserTab {
+        self.tab.as_mut()
+    }
+}
+
+struct IdleTab {
+    session: u64,
+    tab: Box<dyn BrowserTab>,
+    index: usize,
+    generation: u64,
+    navigations: u32,
+    permit: OwnedSemaphorePermit,
+}
+
+/// One task's view of the pool: its own cookie contexts and reusable tabs
+pub struct BrowserSession {
+    pool: Arc<BrowserPool>,
+    id: u64,
+    label: String,
+    // (browser index, generation) -> context id
+    contexts: Mutex<HashMap<(usize, u64), String>>,
+    // Shared with the task's HttpClient so both see the same session
+    cookies: Option<Arc<CookieJar>>,
+}
+
+impl BrowserSession {
+    pub fn new(pool: Arc<BrowserPool>, label: &str) -> Self {
+        Self {
+            id: pool.sessions.fetch_add(1, Ordering::SeqCst),
+            pool,
+            label: label.to_string(),
+            contexts: Mutex::new(HashMap::new()),
+            cookies: None,
+        }
+    }
+
+    /// Seed tabs with cookies from `jar` and copy back whatever the pages set
+    pub fn with_cookies(mut self, jar: Arc<CookieJar>) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /// A tab showing `url`, reusing one of this session's idle tabs when possible
+    pub async fn acquire(&self, url: &str) -> Result<PooledTab> {
+        while let Some(idle) = self.pool.take_idle(self.id).await {
+            if self.pool.current_generation(idle.index).await != Some(idle.generation) {
+                // Its browser was restarted; the tab went with it, and its permit goes now
+                continue;
+            }
+            self.push_cookies(idle.index, idle.generation).await;
+            let mut pooled = PooledTab {
+                tab: idle.tab,
+                index: idle.index,
+                generation: idle.generation,
+                navigations: idle.navigations,
+                _permit: idle.permit,
+            };
+            match pooled.tab.goto(url).await {
+                Ok(()) => {
+                    pooled.navigations += 1;
+                    return Ok(pooled);
+                }
+                Err(e) => {
+                    debug!("Reused tab failed to load {} ({}), opening a new one", url, e);
+                    let (index, generation) = (pooled.index, pooled.generation);
+                    let permit = self.close_tab(pooled).await;
+                    self.recover(index, generation).await?;
+                    return self.open_new(url, permit).await;
+                }
+            }
+        }
+
+        let permit = self.pool.permit().await?;
+        self.open_new(url, permit).await
+    }
+
+    async fn open_new(&self, url: &str, permit: OwnedSemaphorePermit) -> Result<PooledTab> {
+        let (index, generation, driver) = self.pool.checkout().await?;
+        match self.open_in(&driver, index, generation, url).await {
+            Ok(tab) => Ok(PooledTab { tab, index, generation, navigations: 1, _permit: permit }),
+            Err(e) if !driver.is_alive().await => {
+                // The browser died under us: restart it and try once more
+                warn!("Browser {} crashed while opening {}: {}", index, url, e);
+                self.pool.checkin(index, generation).await;
+                self.pool.restart(index, generation).await?;
+                let (index, generation, driver) = self.pool.checkout().await?;
+                match self.open_in(&driver, index, generation, url).await {
+                    Ok(tab) => Ok(PooledTab { tab, index, generation, navigations: 1, _permit: permit }),
+                    Err(e) => {
+                        self.pool.checkin(index, generation).await;
+                        Err(e)
+                    }
+                }
+            }
+            Err(e) => {
+                self.pool.checkin(index, generation).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn open_in(&self, driver: &Arc<dyn BrowserDriver>, index: usize, generation: u64, url: &str) -> Result<Box<dyn BrowserTab>> {
+        let context = {
+            let mut contexts = self.contexts.lock().await;
+            match contexts.get(&(index, generation)) {
+                Some(context) => context.clone(),
+                None => {
+                    let context = driver.new_context().await?;
+                    debug!("Session {} got context {} on browser {}", self.label, context, index);
+                    contexts.insert((index, generation), context.clone());
+                    context
+                }
+            }
+        };
+        self.push_cookies(index, generation).await;
+        driver.open_in_context(&context, url).await
+    }
+
+    // Cookie sync failures only cost us a session, so they're logged rather than fatal
+    async fn push_cookies(&self, index: usize, generation: u64) {
+        let (Some(jar), Some(driver), Some(context)) = (
+            &self.cookies,
+            self.pool.driver(index, generation).await,
+            self.contexts.lock().await.get(&(index, generation)).cloned(),
+        ) else {
+            return;
+        };
+        if let Err(e) = driver.set_cookies(&context, &jar.list(None)).await {
+            debug!("Failed to pass cookies to the browser: {}", e);
+        }
+    }
+
+    async fn pull_cookies(&self, index: usize, generation: u64) {
+        let (Some(jar), Some(driver), Some(context)) = (
+            &self.cookies,
+            self.pool.driver(index, generation).await,
+            self.contexts.lock().await.get(&(index, generation)).cloned(),
+        ) else {
+            return;
+        };
+        match driver.cookies(&context).await {
+            Ok(cookies) => jar.extend(cookies),
+            Err(e) => debug!("Failed to read cookies from the browser: {}", e),
+        }
+    }
+
+    /// Hand a tab back for reuse, closing it once it has done enough navigations
+    pub async fn release(&self, tab: PooledTab) {
+        self.pull_cookies(tab.index, tab.generation).await;
+        if tab.navigations >= self.pool.max_navigations {
+            debug!("Recycling tab after {} navigations", tab.navigations);
+            self.close_tab(tab).await;
+            return;
+        }
+        self.pool
+            .park(IdleTab {
+                session: self.id,
+                tab: tab.tab,
+                index: tab.index,
+                generation: tab.generation,
+                navigations: tab.navigations,
+                permit: tab._permit,
+            })
+            .await;
+    }
+
+    /// Close a tab that shouldn't be reused, e.g. after an error left it in a bad state
+    pub async fn discard(&self, tab: PooledTab) {
+        self.close_tab(tab).await;
+    }
+
+    async fn close_tab(&self, tab: PooledTab) -> OwnedSemaphorePermit {
+        let PooledTab { tab, index, generation, _permit: permit, .. } = tab;
+        self.pull_cookies(index, generation).await;
+        if let Err(e) = tab.close().await {
+            debug!("Failed to close tab: {}", e);
+        }
+        self.pool.checkin(index, generation).await;
+        permit
+    }
+
+    async fn recover(&self, index: usize, generation: u64) -> Result<()> {
+        let alive = match self.pool.driver(index, generation).await {
+            Some(driver) => driver.is_alive().await,
+            None => true,
+        };
+        if !alive {
+            self.pool.restart(index, generation).await?;
+        }
+        Ok(())
+    }
+
+    /// Close this session's tabs and contexts; cookies survive only in the shared jar
+    pub async fn close(&self) -> Result<()> {
+        for idle in self.pool.drain_idle(self.id).await {
+            if let Err(e) = idle.tab.close().await {
+                debug!("Failed to close tab: {}", e);
+            }
+            self.pool.checkin(idle.index, idle.generation).await;
+        }
+
+        let contexts: Vec<((usize, u64), String)> = self.contexts.lock().await.drain().collect();
+        for ((index, generation), context) in contexts {
+            if let Some(driver) = self.pool.driver(index, generation).await {
+                if let (Some(jar), Ok(cookies)) = (&self.cookies, driver.cookies(&context).await) {
+                    jar.extend(cookies);
+                }
+                driver.close_context(&context).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser_interface::FakeBrowser;
+
+    const PAGE: &str = "https://example.com/";
+
+    fn pool(browser: &FakeBrowser, config: &BrowserConfig, max_tabs: usize) -> Arc<BrowserPool> {
+        Arc::new(BrowserPool::new(Arc::new(browser.clone()), config, max_tabs))
+    }
+
+    #[test]
+    fn capacity_is_capped_by_max_tabs() {
+        let config = BrowserConfig { pool_size: 2, tabs_per_browser: 4, ..BrowserConfig::default() };
+        let browser = FakeBrowser::new();
+        assert_eq!(pool(&browser, &config, 3).capacity(), 3);
+        assert_eq!(pool(&browser, &config, 100).capacity(), 8);
+    }
+
+    #[tokio::test]
+    async fn browsers_launch_on_first_use() {
+        let browser = FakeBrowser::new().with_page(PAGE, "<p>hi</p>");
+        let pool = pool(&browser, &BrowserConfig::default(), 4);
+        assert_eq!(pool.running().await, 0);
+
+        let session = BrowserSession::new(pool.clone(), "task");
+        let tab = session.acquire(PAGE).await.unwrap();
+        session.release(tab).await;
+
+        assert_eq!(pool.running().await, 1);
+        assert_eq!(browser.launches(), 1);
+    }
+
+    #[tokio::test]
+    async fn idle_tabs_are_reused() {
+        let browser = FakeBrowser::new().with_page(PAGE, "<p>hi</p>");
+        let session = BrowserSession::new(pool(&browser, &BrowserConfig::default(), 4), "task");
+
+        let tab = session.acquire(PAGE).await.unwrap();
+        session.release(tab).await;
+        let tab = session.acquire(PAGE).await.unwrap();
+        assert_eq!(tab.navigations, 2);
+        session.release(tab).await;
+        session.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_tabs_of_other_sessions_make_room() {
+        let browser = FakeBrowser::new().with_page(PAGE, "<p>hi</p>");
+        let pool = pool(&browser, &BrowserConfig::default(), 1);
+        let first = BrowserSession::new(pool.clone(), "first");
+        let tab = first.acquire(PAGE).await.unwrap();
+        first.release(tab).await;
+
+        let second = BrowserSession::new(pool.clone(), "second");
+        let tab = second.acquire(PAGE).await.unwrap();
+        assert_eq!(pool.open_tabs().await, 1);
+
+        second.release(tab).await;
+        first.close().await.unwrap();
+        second.close().await.unwrap();
+        assert_eq!(pool.open_tabs().await, 0);
+    }
+
+    #[tokio::test]
+    async fn crashed_browser_is_restarted() {
+        let browser = FakeBrowser::new().with_page(PAGE, "<p>hi</p>");
+        let session = BrowserSession::new(pool(&browser, &BrowserConfig::default(), 4), "task");
+        let tab = session.acquire(PAGE).await.unwrap();
+        session.release(tab).await;
+
+        browser.crash();
+        let tab = session.acquire(PAGE).await.unwrap();
+
+        assert_eq!(tab.tab().content().await.unwrap(), "<p>hi</p>");
+        assert_eq!(browser.launches(), 2);
+        session.release(tab).await;
+    }
+
+    #[tokio::test]
+    async fn crash_while_opening_a_new_tab_restarts_once() {
+        let browser = FakeBrowser::new().with_page(PAGE, "<p>hi</p>");
+        let pool = pool(&browser, &BrowserConfig::default(), 4);
+        let session = BrowserSession::new(pool.clone(), "task");
+        let tab = session.acquire(PAGE).await.unwrap();
+        session.discard(tab).await;
+
+        browser.crash();
+        let other = BrowserSession::new(pool, "other");
+        let tab = other.acquire(PAGE).await.unwrap();
+
+        assert_eq!(browser.launches(), 2);
+        other.release(tab).await;
+    }
+}
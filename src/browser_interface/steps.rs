@@ -0,0 +1,87 @@
+// Scripted browser interaction
+// A recipe can list steps to run in the browser before extraction, e.g. fill
+// a search form, pick a country and click through result pages:
+//
+//   [[steps]]
+//   action = "type"
+//   selector = "input[name=q]"
+//   text = "engineering"
+//
+//   [[steps]]
+//   action = "click"
+//   selector = "button[type=submit]"
+//
+//   [[steps]]
+//   action = "extract"
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BrowserStep {
+    Navigate {
+        url: String,
+    },
+    /// Type into an input, replacing what was there
+    Type {
+        selector: String,
+        text: String,
+        /// Keep the text out of logs and error messages (passwords)
+        #[serde(default)]
+        secret: bool,
+    },
+    /// Choose an option of a `<select>` by its value
+    Select {
+        selector: String,
+        value: String,
+    },
+    Click {
+        selector: String,
+    },
+    WaitFor {
+        selector: String,
+        /// Defaults to the browser's navigation timeout
+        #[serde(default)]
+        timeout_seconds: Option<u64>,
+    },
+    /// Scroll to the bottom, up to `times` times or until the page stops growing
+    Scroll {
+        #[serde(default = "default_scrolls")]
+        times: u32,
+    },
+    /// Take the current DOM for extraction
+    Extract,
+}
+
+fn default_scrolls() -> u32 {
+    1
+}
+
+impl BrowserStep {
+    /// CSS selector the step acts on, if any
+    pub fn selector(&self) -> Option<&str> {
+        match self {
+            Self::Type { selector, .. }
+            | Self::Select { selector, .. }
+            | Self::Click { selector }
+            | Self::WaitFor { selector, .. } => Some(selector),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BrowserStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Navigate { url } => write!(f, "navigate to {}", url),
+            Self::Type { selector, secret: true, .. } => write!(f, "type ******** into '{}'", selector),
+            Self::Type { selector, text, .. } => write!(f, "type {:?} into '{}'", text, selector),
+            Self::Select { selector, value } => write!(f, "select {:?} in '{}'", value, selector),
+            Self::Click { selector } => write!(f, "click '{}'", selector),
+            Self::WaitFor { selector, .. } => write!(f, "wait for '{}'", selector),
+            Self::Scroll { times } => write!(f, "scroll {} time(s)", times),
+            Self::Extract => write!(f, "extract"),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// URL canonicalisation
+// Different spellings of the same page ("HTTP://Example.com:80/a?b=2&a=1#top",
+// "http://example.com/a?a=1&b=2&utm_source=x") must map to one frontier entry.
+
+use url::Url;
+
+/// Canonical form of an http(s) URL: no fragment or tracking parameters, with
+/// the remaining query parameters sorted. The `url` crate already lowercases the
+/// host and drops default ports. Returns None for other schemes.
+pub fn canonicalize(url: &Url, ignore_params: &[String]) -> Option<Url> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+    url.host_str()?;
+
+    let mut canonical = url.clone();
+    canonical.set_fragment(None);
+
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_ignored(name, ignore_params))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+    if params.is_empty() {
+        canonical.set_query(None);
+    } else {
+        canonical.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    Some(canonical)
+}
+
+fn is_ignored(name: &str, ignore_params: &[String]) -> bool {
+    let name = name.to_ascii_lowercase();
+    ignore_params.iter().any(|pattern| {
+        let pattern = pattern.to_ascii_lowercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        }
+    })
+}
@@ -0,0 +1,234 @@
+// Crawl frontier
+// The queue of URLs a crawl still has to visit, kept in Storage so a crawl
+// can be resumed after it stops. URLs are canonicalised and scoped before
+// they're queued, and each one is only ever queued once per crawl. URLs a
+// site's robots.txt disallows are never fetched, and fetches can be paced.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use url::Url;
+
+use super::canonical::canonicalize;
+use super::robots::Robots;
+use crate::data::Storage;
+use crate::engine::config::CrawlConfig;
+
+/// Which URLs a crawl may visit
+#[derive(Debug, Clone)]
+pub struct CrawlScope {
+    seed_hosts: Vec<String>,
+    same_domain: bool,
+    allowed_domains: Vec<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    ignore_params: Vec<String>,
+    pub max_depth: u32,
+    pub max_pages: usize,
+}
+
+impl CrawlScope {
+    pub fn new(config: &CrawlConfig, seeds: &[Url]) -> Result<Self> {
+        let compile = |patterns: &[String]| -> Result<Vec<Regex>> {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).map_err(|e| anyhow!("Invalid crawl pattern '{}': {}", p, e)))
+                .collect()
+        };
+
+        Ok(Self {
+            seed_hosts: seeds.iter().filter_map(|u| u.host_str()).map(bare_host).collect(),
+            same_domain: config.same_domain,
+            allowed_domains: config.allowed_domains.iter().map(|d| bare_host(d)).collect(),
+            include: compile(&config.include)?,
+            exclude: compile(&config.exclude)?,
+            ignore_params: config.ignore_params.clone(),
+            max_depth: config.max_depth,
+            max_pages: config.max_pages,
+        })
+    }
+
+    pub fn canonicalize(&self, url: &Url) -> Option<Url> {
+        canonicalize(url, &self.ignore_params)
+    }
+
+    /// True when the URL's host is in scope and it passes the include/exclude patterns
+    pub fn allows(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => bare_host(host),
+            None => return false,
+        };
+        let on_seed_host = self.seed_hosts.iter().any(|d| within(&host, d));
+        let on_allowed_host = self.allowed_domains.iter().any(|d| within(&host, d));
+        let host_ok = if self.same_domain || !self.allowed_domains.is_empty() {
+            (self.same_domain && on_seed_host) || on_allowed_host
+        } else {
+            true
+        };
+
+        let url = url.as_str();
+        host_ok
+            && (self.include.is_empty() || self.include.iter().any(|r| r.is_match(url)))
+            && !self.exclude.iter().any(|r| r.is_match(url))
+    }
+}
+
+fn bare_host(host: &str) -> String {
+    host.trim_start_matches("www.").to_ascii_lowercase()
+}
+
+fn within(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// A URL taken off the frontier
+#[derive(Debug, Clone)]
+pub struct FrontierEntry {
+    pub url: String,
+    /// Links followed from a seed to get here
+    pub depth: u32,
+    pub parent_url: Option<String>,
+}
+
+pub struct Frontier<'a> {
+    storage: &'a Storage,
+    task_id: String,
+    scope: CrawlScope,
+    visited: usize,
+    robots: Option<Robots<'a>>,
+    /// Least time between two URLs being handed out
+    interval: Option<Duration>,
+    last_claim: Option<Instant>,
+}
+
+impl<'a> Frontier<'a> {
+    /// Frontier of the crawl `task_id`, picking up where an earlier run left off
+    pub async fn open(storage: &'a Storage, task_id: &str, scope: CrawlScope) -> Result<Frontier<'a>> {
+        storage.requeue_frontier(task_id).await?;
+        let counts = storage.frontier_counts(task_id).await?;
+        let visited = ["done", "failed"].iter().filter_map(|s| counts.get(*s)).sum::<u64>() as usize;
+        Ok(Self {
+            storage,
+            task_id: task_id.to_string(),
+            scope,
+            visited,
+            robots: None,
+            interval: None,
+            last_claim: None,
+        })
+    }
+
+    /// Skip the URLs each site's robots.txt disallows
+    pub fn with_robots(mut self, robots: Robots<'a>) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    /// Hand out at most `per_minute` URLs a minute; 0 means no limit
+    pub fn with_rate_limit(mut self, per_minute: u32) -> Self {
+        self.interval = if per_minute > 0 { Some(Duration::from_secs(60) / per_minute) } else { None };
+        self
+    }
+
+    pub fn scope(&self) -> &CrawlScope {
+        &self.scope
+    }
+
+    /// Queue the seed URLs at depth 0. Seeds skip the include/exclude patterns.
+    pub async fn seed(&self, seeds: &[Url]) -> Result<u64> {
+        let entries: Vec<_> = seeds
+            .iter()
+            .filter_map(|url| self.scope.canonicalize(url))
+            .map(|url| (url.to_string(), 0, None))
+            .collect();
+        self.storage.enqueue_frontier(&self.task_id, &entries).await
+    }
+
+    /// Next URL to fetch, or None once the frontier is empty or the page budget is spent.
+    /// URLs robots.txt disallows are marked "blocked" and passed over.
+    pub async fn next(&mut self) -> Result<Option<FrontierEntry>> {
+        loop {
+            if self.visited >= self.scope.max_pages {
+                return Ok(None);
+            }
+            let (url, depth, parent_url) = match self.storage.claim_frontier_url(&self.task_id).await? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+            let allowed = match Url::parse(&url) {
+                Ok(parsed) => self.robots_allow(&parsed).await,
+                Err(_) => true,
+            };
+            if !allowed {
+                debug!("robots.txt disallows {}", url);
+                self.storage
+                    .finish_frontier_url(&self.task_id, &url, "blocked", Some("Disallowed by robots.txt"))
+                    .await?;
+                continue;
+            }
+            self.pace().await;
+            self.visited += 1;
+            return Ok(Some(FrontierEntry { url, depth, parent_url }));
+        }
+    }
+
+    async fn pace(&mut self) {
+        if let (Some(interval), Some(last)) = (self.interval, self.last_claim) {
+            let wait = interval.saturating_sub(last.elapsed());
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        self.last_claim = Some(Instant::now());
+    }
+
+    async fn robots_allow(&self, url: &Url) -> bool {
+        match &self.robots {
+            Some(robots) => robots.allows(url).await,
+            None => true,
+        }
+    }
+
+    /// Queue the in-scope links found on `entry`'s page. Returns how many were new.
+    pub async fn discover(&self, entry: &FrontierEntry, links: &[Url]) -> Result<u64> {
+        if entry.depth >= self.scope.max_depth {
+            return Ok(0);
+        }
+        let mut entries = Vec::new();
+        for url in links.iter().filter_map(|url| self.scope.canonicalize(url)) {
+            if self.scope.allows(&url) && self.robots_allow(&url).await {
+                entries.push((url.to_string(), entry.depth + 1, Some(entry.url.clone())));
+            }
+        }
+        self.storage.enqueue_frontier(&self.task_id, &entries).await
+    }
+
+    /// Queue the next page of a listing at the same depth, so result pages
+    /// don't use up the link depth budget
+    pub async fn queue_next_page(&self, entry: &FrontierEntry, next: &Url) -> Result<u64> {
+        let next = match self.scope.canonicalize(next) {
+            Some(next) if self.scope.allows(&next) => next,
+            _ => return Ok(0),
+        };
+        if !self.robots_allow(&next).await {
+            return Ok(0);
+        }
+        let entries = [(next.to_string(), entry.depth, Some(entry.url.clone()))];
+        self.storage.enqueue_frontier(&self.task_id, &entries).await
+    }
+
+    pub async fn complete(&self, entry: &FrontierEntry) -> Result<()> {
+        self.storage.finish_frontier_url(&self.task_id, &entry.url, "done", None).await
+    }
+
+    pub async fn fail(&self, entry: &FrontierEntry, error: &str) -> Result<()> {
+        self.storage.finish_frontier_url(&self.task_id, &entry.url, "failed", Some(error)).await
+    }
+
+    /// URLs per status ("pending", "done", "failed", "blocked")
+    pub async fn counts(&self) -> Result<HashMap<String, u64>> {
+        self.storage.frontier_counts(&self.task_id).await
+    }
+}
@@ -0,0 +1,45 @@
+// Link discovery
+// Absolute http(s) links of a fetched page, resolved against its <base href>
+// when it has one.
+
+use scraper::{Html, Selector};
+use std::collections::HashSet;
+use url::Url;
+
+/// Links on the page in document order, without duplicates
+pub fn extract_links(html: &str, page_url: &Url) -> Vec<Url> {
+    let document = Html::parse_document(html);
+    let base_selector = Selector::parse("base[href]").expect("static selector");
+    let link_selector = Selector::parse("a[href], area[href]").expect("static selector");
+
+    let base = document
+        .select(&base_selector)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .and_then(|href| page_url.join(href).ok())
+        .unwrap_or_else(|| page_url.clone());
+
+    let mut seen = HashSet::new();
+    let mut links: Vec<Url> = Vec::new();
+    for element in document.select(&link_selector) {
+        let href = match element.value().attr("href") {
+            Some(href) => href.trim(),
+            None => continue,
+        };
+        if href.is_empty() || href.starts_with('#') {
+            continue;
+        }
+        let mut link = match base.join(href) {
+            Ok(link) => link,
+            Err(_) => continue,
+        };
+        if link.scheme() != "http" && link.scheme() != "https" {
+            continue;
+        }
+        link.set_fragment(None);
+        if seen.insert(link.clone()) {
+            links.push(link);
+        }
+    }
+    links
+}
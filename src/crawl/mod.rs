@@ -0,0 +1,17 @@
+// Crawling module for Flash AI
+// Follows links from seed URLs through a persistent, deduplicated frontier
+// and walks paginated listings, keeping to robots.txt; sitemaps can seed it
+
+pub mod canonical;
+pub mod frontier;
+pub mod links;
+pub mod pagination;
+pub mod robots;
+pub mod sitemap;
+
+pub use canonical::canonicalize;
+pub use frontier::{CrawlScope, Frontier, FrontierEntry};
+pub use links::extract_links;
+pub use pagination::{next_page_url, Paginator};
+pub use robots::{Robots, RobotsTxt};
+pub use sitemap::{SitemapEntry, SitemapReader};
@@ -0,0 +1,202 @@
+// Pagination
+// Finds the next page of a result listing. In order of trust:
+//   1. the recipe's own `list.next_page` selector
+//   2. rel="next" links
+//   3. links labelled "Next", "›", "»", ... (text, aria-label, title or class)
+//   4. a numbered link for the page after the current one
+//   5. bumping a `?page=`, `/page/N` or `?offset=` style URL
+// Listings that load more results on scroll are handled by the caller through
+// the browser once no next link turns up.
+
+use scraper::{ElementRef, Html, Selector};
+use serde_json::Value;
+use std::collections::HashSet;
+use url::Url;
+
+use crate::engine::config::PaginationConfig;
+
+const NEXT_LABELS: &[&str] = &["next", "next page", "next »", "next ›", "next >", "›", "»", "→", ">", ">>", "more results", "older"];
+
+const PAGE_PARAMS: &[&str] = &["page", "p", "pg", "pagenum", "page_number"];
+
+const OFFSET_PARAMS: &[&str] = &["offset", "start", "skip", "from"];
+
+/// URL of the page after `page_url`, if the listing has one. `items_on_page`
+/// is the number of records found on it, used to step offset-style URLs.
+pub fn next_page_url(html: &str, page_url: &Url, next_selector: Option<&str>, items_on_page: usize) -> Option<Url> {
+    let document = Html::parse_document(html);
+
+    if let Some(selector) = next_selector {
+        // The recipe knows best; don't second-guess it when its link is missing
+        return Selector::parse(selector)
+            .ok()
+            .and_then(|s| document.select(&s).find_map(|e| link_target(e, page_url)));
+    }
+
+    let rel_next = Selector::parse("link[rel~=next][href], a[rel~=next][href]").expect("static selector");
+    if let Some(url) = document.select(&rel_next).find_map(|e| link_target(e, page_url)) {
+        return Some(url);
+    }
+
+    let anchors = Selector::parse("a[href]").expect("static selector");
+    if let Some(url) = document
+        .select(&anchors)
+        .filter(|a| is_next_label(a))
+        .find_map(|a| link_target(a, page_url))
+    {
+        return Some(url);
+    }
+
+    let wanted = (current_page(page_url).unwrap_or(1) + 1).to_string();
+    if let Some(url) = document
+        .select(&anchors)
+        .filter(|a| a.text().collect::<String>().trim() == wanted)
+        .find_map(|a| link_target(a, page_url))
+    {
+        return Some(url);
+    }
+
+    bump_url(page_url, items_on_page)
+}
+
+fn link_target(element: ElementRef, page_url: &Url) -> Option<Url> {
+    let href = element.value().attr("href")?.trim();
+    if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+        return None;
+    }
+    let mut url = page_url.join(href).ok()?;
+    url.set_fragment(None);
+    // A "next" link back to the same page would loop forever
+    if url == without_fragment(page_url) || url.host_str() != page_url.host_str() {
+        return None;
+    }
+    Some(url)
+}
+
+fn without_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+fn is_next_label(anchor: &ElementRef) -> bool {
+    let text = anchor.text().collect::<String>().trim().to_lowercase();
+    if NEXT_LABELS.contains(&text.as_str()) {
+        return true;
+    }
+    let attr = |name: &str| anchor.value().attr(name).unwrap_or("").to_lowercase();
+    let labelled = attr("aria-label");
+    let title = attr("title");
+    if labelled.starts_with("next") || title.starts_with("next") {
+        return true;
+    }
+    anchor
+        .value()
+        .classes()
+        .any(|c| matches!(c.to_lowercase().as_str(), "next" | "next-page" | "pagination-next" | "pager-next" | "nextpostslink"))
+}
+
+/// Page number in a `?page=N` or `/page/N` style URL
+fn current_page(url: &Url) -> Option<u32> {
+    for (name, value) in url.query_pairs() {
+        if PAGE_PARAMS.contains(&name.to_lowercase().as_str()) {
+            if let Ok(page) = value.parse() {
+                return Some(page);
+            }
+        }
+    }
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    segments
+        .windows(2)
+        .rev()
+        .find(|pair| pair[0] == "page")
+        .and_then(|pair| pair[1].parse().ok())
+}
+
+// Guess the next page from the URL alone. Only used once a page has yielded
+// records, and the caller stops as soon as a page brings nothing new.
+fn bump_url(url: &Url, items_on_page: usize) -> Option<Url> {
+    if items_on_page == 0 {
+        return None;
+    }
+
+    let pairs: Vec<(String, String)> = url.query_pairs().map(|(k, v)| (k.into_owned(), v.into_owned())).collect();
+    let bump = |params: &[&str], step: u64| -> Option<Url> {
+        let index = pairs.iter().position(|(k, v)| params.contains(&k.to_lowercase().as_str()) && v.parse::<u64>().is_ok())?;
+        let mut next = url.clone();
+        let mut bumped = pairs.clone();
+        bumped[index].1 = (bumped[index].1.parse::<u64>().ok()? + step).to_string();
+        next.query_pairs_mut().clear().extend_pairs(bumped);
+        Some(next)
+    };
+    if let Some(next) = bump(PAGE_PARAMS, 1).or_else(|| bump(OFFSET_PARAMS, items_on_page as u64)) {
+        return Some(next);
+    }
+
+    let mut segments: Vec<String> = url.path_segments()?.map(str::to_string).collect();
+    let index = segments.iter().rposition(|s| s == "page")?;
+    let page: u32 = segments.get(index + 1)?.parse().ok()?;
+    segments[index + 1] = (page + 1).to_string();
+    let mut next = url.clone();
+    next.set_path(&segments.join("/"));
+    Some(next)
+}
+
+/// Walks one listing's pages: keeps track of pages already read and records
+/// already seen, and decides when to stop
+pub struct Paginator {
+    config: PaginationConfig,
+    next_selector: Option<String>,
+    visited: HashSet<String>,
+    seen: HashSet<String>,
+    pages: usize,
+    scrolled: bool,
+}
+
+impl Paginator {
+    pub fn new(config: &PaginationConfig, next_selector: Option<&str>) -> Self {
+        Self {
+            config: config.clone(),
+            next_selector: next_selector.map(str::to_string),
+            visited: HashSet::new(),
+            seen: HashSet::new(),
+            pages: 0,
+            scrolled: false,
+        }
+    }
+
+    /// Count a page as read and keep only the records not seen on earlier pages
+    pub fn fresh<T>(&mut self, page_url: &str, records: Vec<(Value, T)>) -> Vec<(Value, T)> {
+        self.pages += 1;
+        self.visited.insert(page_url.to_string());
+        records
+            .into_iter()
+            .filter(|(record, _)| self.seen.insert(record.to_string()))
+            .collect()
+    }
+
+    /// The page to read after this one. None once pagination is off, the page
+    /// budget is spent, the page brought no new records or there is no next link.
+    pub fn next(&self, html: &str, page_url: &str, items_on_page: usize, new_on_page: usize) -> Option<Url> {
+        if !self.config.enabled || self.pages >= self.config.max_pages || new_on_page == 0 {
+            return None;
+        }
+        let page_url = Url::parse(page_url).ok()?;
+        next_page_url(html, &page_url, self.next_selector.as_deref(), items_on_page)
+            .filter(|next| !self.visited.contains(next.as_str()))
+    }
+
+    /// Whether to try scrolling the listing in the browser for more results:
+    /// only for single-page listings that had records, and at most once
+    pub fn should_scroll(&mut self, new_on_page: usize) -> bool {
+        if !self.config.enabled || !self.config.infinite_scroll || self.scrolled || self.pages > 1 || new_on_page == 0 {
+            return false;
+        }
+        self.scrolled = true;
+        true
+    }
+
+    pub fn max_scrolls(&self) -> u32 {
+        self.config.max_scrolls
+    }
+}
@@ -0,0 +1,201 @@
+// robots.txt rules
+// A crawl skips the pages a site's robots.txt disallows. We don't announce a
+// crawler name of our own, so the rules for every robot (`User-agent: *`)
+// are the ones that apply. Each site's file is fetched once per crawl.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use url::Url;
+
+use crate::networking::HttpClient;
+
+/// The Allow/Disallow rules of a robots.txt that apply to us
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    /// (allow, path pattern)
+    rules: Vec<(bool, String)>,
+}
+
+impl RobotsTxt {
+    pub fn parse(robots: &str) -> Self {
+        let mut rules = Vec::new();
+        // Whether the group being read applies to every robot
+        let mut applies = false;
+        // Consecutive User-agent lines share one group
+        let mut in_agents = false;
+
+        for line in robots.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        applies = false;
+                        in_agents = true;
+                    }
+                    applies |= value == "*";
+                }
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    // An empty Disallow allows everything, so it adds no rule
+                    if applies && !value.is_empty() {
+                        rules.push((key == "allow", value.to_string()));
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        Self { rules }
+    }
+
+    /// Whether the URL may be fetched. The longest matching rule decides, and
+    /// Allow wins a tie.
+    pub fn allows(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, &path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Match a robots.txt path pattern, where `*` is any run of characters and a
+/// trailing `$` anchors the end
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let mut rest = match path.strip_prefix(parts.next().unwrap_or("")) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return !anchored || rest.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// robots.txt rules per site, fetched the first time a site is asked about.
+/// A site without a readable robots.txt allows everything.
+pub struct Robots<'a> {
+    http: &'a HttpClient,
+    sites: Mutex<HashMap<String, Arc<RobotsTxt>>>,
+}
+
+impl<'a> Robots<'a> {
+    pub fn new(http: &'a HttpClient) -> Self {
+        Self {
+            http,
+            sites: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn allows(&self, url: &Url) -> bool {
+        let site = url.origin().ascii_serialization();
+        let cached = self.sites.lock().unwrap_or_else(|e| e.into_inner()).get(&site).cloned();
+        let rules = match cached {
+            Some(rules) => rules,
+            None => {
+                let rules = Arc::new(self.fetch(url).await);
+                self.sites
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(site, rules.clone());
+                rules
+            }
+        };
+        rules.allows(url)
+    }
+
+    async fn fetch(&self, url: &Url) -> RobotsTxt {
+        let robots_url = match url.join("/robots.txt") {
+            Ok(robots_url) => robots_url,
+            Err(_) => return RobotsTxt::default(),
+        };
+        let response = match self.http.get(robots_url.as_str()).await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                debug!("No robots.txt at {} ({})", robots_url, response.status());
+                return RobotsTxt::default();
+            }
+            Err(e) => {
+                debug!("No robots.txt at {}: {}", robots_url, e);
+                return RobotsTxt::default();
+            }
+        };
+        match response.text().await {
+            Ok(robots) => RobotsTxt::parse(&robots),
+            Err(e) => {
+                debug!("Failed to read {}: {}", robots_url, e);
+                RobotsTxt::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(robots: &RobotsTxt, url: &str) -> bool {
+        robots.allows(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn applies_the_rules_for_every_robot() {
+        let robots = RobotsTxt::parse(
+            "User-agent: Googlebot\n\
+             Disallow: /\n\
+             \n\
+             User-agent: Bingbot\n\
+             User-agent: *\n\
+             Disallow: /private/ # staff only\n\
+             Disallow: /search\n",
+        );
+        assert!(allows(&robots, "https://example.com/listings/1"));
+        assert!(!allows(&robots, "https://example.com/private/report"));
+        assert!(!allows(&robots, "https://example.com/search?q=flats"));
+    }
+
+    #[test]
+    fn the_longest_rule_wins() {
+        let robots = RobotsTxt::parse(
+            "User-agent: *\n\
+             Disallow: /shop/\n\
+             Allow: /shop/catalogue\n\
+             Disallow: /*.pdf$\n\
+             Disallow:\n",
+        );
+        assert!(!allows(&robots, "https://example.com/shop/cart"));
+        assert!(allows(&robots, "https://example.com/shop/catalogue/page-2"));
+        assert!(!allows(&robots, "https://example.com/docs/terms.pdf"));
+        assert!(allows(&robots, "https://example.com/docs/terms.pdf.html"));
+    }
+
+    #[test]
+    fn nothing_is_disallowed_without_rules() {
+        assert!(allows(&RobotsTxt::default(), "https://example.com/anything"));
+        assert!(allows(&RobotsTxt::parse("User-agent: *\nDisallow:\n"), "https://example.com/"));
+    }
+}
@@ -0,0 +1,198 @@
+// Sitemaps
+// Enumerates a site's pages from the sitemaps listed in its robots.txt (or
+// /sitemap.xml when it lists none). Sitemap indexes are followed and gzipped
+// sitemaps are unpacked. Entries can be narrowed to those modified since a
+// date; entries without a lastmod are kept since we can't tell.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use libxml::parser::Parser;
+use libxml::tree::Node;
+use libxml::xpath::Context as XPathContext;
+use std::collections::{HashSet, VecDeque};
+use std::io::Read;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::networking::HttpClient;
+
+#[derive(Debug, Clone)]
+pub struct SitemapEntry {
+    pub url: Url,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+impl SitemapEntry {
+    /// True unless the entry has a lastmod older than `since`
+    pub fn modified_since(&self, since: Option<DateTime<Utc>>) -> bool {
+        match (self.lastmod, since) {
+            (Some(lastmod), Some(since)) => lastmod >= since,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SitemapDocument {
+    /// A `<sitemapindex>` pointing at more sitemaps
+    Index(Vec<SitemapEntry>),
+    /// A `<urlset>` of pages
+    Urls(Vec<SitemapEntry>),
+}
+
+/// Parse a sitemap or sitemap index
+pub fn parse_sitemap(xml: &str) -> Result<SitemapDocument> {
+    let document = Parser::default()
+        .parse_string(xml)
+        .map_err(|e| anyhow!("Failed to parse sitemap: {:?}", e))?;
+    let context = XPathContext::new(&document).map_err(|_| anyhow!("Failed to create XPath context"))?;
+    let root = document.get_root_element().ok_or_else(|| anyhow!("Sitemap is empty"))?;
+
+    // Sitemaps declare a default namespace, so match on local names
+    let (item, index) = match root.get_name().as_str() {
+        "sitemapindex" => ("sitemap", true),
+        "urlset" => ("url", false),
+        other => return Err(anyhow!("Not a sitemap: root element is <{}>", other)),
+    };
+    let items = context
+        .findnodes(&format!("/*/*[local-name()='{}']", item), None)
+        .map_err(|_| anyhow!("Failed to read sitemap entries"))?;
+
+    let entries = items
+        .iter()
+        .filter_map(|node| {
+            let url = Url::parse(child_text(&context, node, "loc")?.trim()).ok()?;
+            let lastmod = child_text(&context, node, "lastmod").and_then(|d| parse_lastmod(&d));
+            Some(SitemapEntry { url, lastmod })
+        })
+        .collect();
+
+    Ok(if index { SitemapDocument::Index(entries) } else { SitemapDocument::Urls(entries) })
+}
+
+fn child_text(context: &XPathContext, node: &Node, name: &str) -> Option<String> {
+    context
+        .findnodes(&format!("*[local-name()='{}']", name), Some(node))
+        .ok()?
+        .first()
+        .map(|n| n.get_content())
+}
+
+/// W3C datetime as used by sitemaps: a full timestamp or just a date
+fn parse_lastmod(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Sitemap URLs declared in a robots.txt
+pub fn robots_sitemaps(robots: &str, base: &Url) -> Vec<Url> {
+    robots
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if !key.trim().eq_ignore_ascii_case("sitemap") {
+                return None;
+            }
+            base.join(value.trim()).ok()
+        })
+        .collect()
+}
+
+pub struct SitemapReader<'a> {
+    http: &'a HttpClient,
+    /// Sitemap files to read at most, indexes included
+    max_sitemaps: usize,
+}
+
+impl<'a> SitemapReader<'a> {
+    pub fn new(http: &'a HttpClient, max_sitemaps: usize) -> Self {
+        Self { http, max_sitemaps }
+    }
+
+    /// Sitemaps of the site `url` belongs to
+    pub async fn discover(&self, url: &Url) -> Result<Vec<Url>> {
+        let robots_url = url.join("/robots.txt")?;
+        let sitemaps = match self.fetch(&robots_url).await {
+            Ok(robots) => robots_sitemaps(&String::from_utf8_lossy(&robots), url),
+            Err(e) => {
+                debug!("No robots.txt at {}: {}", robots_url, e);
+                Vec::new()
+            }
+        };
+        if sitemaps.is_empty() {
+            return Ok(vec![url.join("/sitemap.xml")?]);
+        }
+        Ok(sitemaps)
+    }
+
+    /// Page entries of the given sitemaps, following indexes. Child sitemaps an
+    /// index marks as unchanged since `since` are skipped.
+    pub async fn read(&self, sitemaps: Vec<Url>, since: Option<DateTime<Utc>>) -> Result<Vec<SitemapEntry>> {
+        let mut queue: VecDeque<Url> = sitemaps.into();
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+
+        while let Some(sitemap) = queue.pop_front() {
+            if !seen.insert(sitemap.clone()) {
+                continue;
+            }
+            if seen.len() > self.max_sitemaps {
+                warn!("Stopped after {} sitemaps; {} left unread", self.max_sitemaps, queue.len() + 1);
+                break;
+            }
+
+            let body = match self.fetch(&sitemap).await {
+                Ok(body) => body,
+                Err(e) => {
+                    warn!("Failed to fetch sitemap {}: {}", sitemap, e);
+                    continue;
+                }
+            };
+            let xml = match decode(&body) {
+                Ok(xml) => xml,
+                Err(e) => {
+                    warn!("Skipping sitemap {}: {}", sitemap, e);
+                    continue;
+                }
+            };
+            match parse_sitemap(&xml) {
+                Ok(SitemapDocument::Index(children)) => {
+                    debug!("Sitemap index {} lists {} sitemap(s)", sitemap, children.len());
+                    queue.extend(children.into_iter().filter(|c| c.modified_since(since)).map(|c| c.url));
+                }
+                Ok(SitemapDocument::Urls(urls)) => {
+                    debug!("Sitemap {} lists {} URL(s)", sitemap, urls.len());
+                    entries.extend(urls.into_iter().filter(|u| u.modified_since(since)));
+                }
+                Err(e) => warn!("Skipping sitemap {}: {}", sitemap, e),
+            }
+        }
+
+        info!("Read {} URL(s) from {} sitemap(s)", entries.len(), seen.len().min(self.max_sitemaps));
+        Ok(entries)
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<Vec<u8>> {
+        let response = self.http.get(url.as_str()).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+// .xml.gz files come back as gzip bytes rather than a gzip Content-Encoding
+fn decode(body: &[u8]) -> Result<String> {
+    if body.starts_with(&[0x1f, 0x8b]) {
+        let mut xml = String::new();
+        GzDecoder::new(body)
+            .read_to_string(&mut xml)
+            .map_err(|e| anyhow!("Failed to unpack gzipped sitemap: {}", e))?;
+        return Ok(xml);
+    }
+    Ok(String::from_utf8_lossy(body).into_owned())
+}
@@ -3,14 +3,23 @@ use std::collections::HashMap;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use crate::Result;
+use super::schema::OutputSchema;
 
 pub struct DataExporter {
     output_dir: String,
+    schema: Option<OutputSchema>,
 }
 
 impl DataExporter {
     pub fn new(output_dir: String) -> Self {
-        Self { output_dir }
+        Self { output_dir, schema: None }
+    }
+
+    /// Export columns in the schema's order, so the layout doesn't depend on
+    /// which fields the first record happened to have
+    pub fn with_schema(mut self, schema: OutputSchema) -> Self {
+        self.schema = Some(schema);
+        self
     }
 
     pub async fn export_to_csv(&self, data: &[(String, Value)], filename: &str) -> Result<String> {
@@ -20,8 +29,11 @@ impl DataExporter {
             return Ok(String::new());
         }
 
-        // Extract headers from first record
-        let headers = self.extract_headers(&data[0].1);
+        // Use the schema's columns when we have one, otherwise the first record's keys
+        let headers = match &self.schema {
+            Some(schema) => self.schema_headers(schema),
+            None => self.extract_headers(&data[0].1),
+        };
         csv_content.push_str(&headers.join(","));
         csv_content.push('\n');
 
@@ -53,11 +65,15 @@ impl DataExporter {
         
         for (url, record) in data {
             let mut entry = HashMap::new();
-            entry.insert("url".to_string(), Value::String(url.clone()));
+            entry.insert(self.source_key().to_string(), Value::String(url.clone()));
             
+            let record = match &self.schema {
+                Some(schema) => schema.conform(record),
+                None => record.clone(),
+            };
             if let Value::Object(obj) = record {
                 for (key, value) in obj {
-                    entry.insert(key.clone(), value.clone());
+                    entry.insert(key, value);
                 }
             }
             
@@ -79,11 +95,17 @@ impl DataExporter {
         
         for (url, record) in data {
             xml_content.push_str("  <record>\n");
-            xml_content.push_str(&format!("    <url>{}</url>\n", self.escape_xml(url)));
+            let source_key = self.source_key();
+            xml_content.push_str(&format!("    <{}>{}</{}>\n", source_key, self.escape_xml(url), source_key));
             
-            if let Value::Object(obj) = record {
+            let record = match &self.schema {
+                Some(schema) => schema.conform(record),
+                None => record.clone(),
+            };
+            if let Value::Object(obj) = &record {
                 for (key, value) in obj {
                     let value_str = match value {
+                        Value::Null => String::new(),
                         Value::String(s) => s.clone(),
                         Value::Number(n) => n.to_string(),
                         Value::Bool(b) => b.to_string(),
@@ -125,6 +147,21 @@ impl DataExporter {
         headers
     }
 
+    /// Key for the page a record came from. Renamed when the schema has its own
+    /// "url" field (e.g. the entity's website) so the two don't collide.
+    fn source_key(&self) -> &'static str {
+        match &self.schema {
+            Some(schema) if schema.field("url").is_some() => "source_url",
+            _ => "url",
+        }
+    }
+
+    fn schema_headers(&self, schema: &OutputSchema) -> Vec<String> {
+        let mut headers = vec![self.source_key().to_string()];
+        headers.extend(schema.column_names());
+        headers
+    }
+
     fn extract_value(&self, record: &Value, key: &str) -> String {
         if let Value::Object(obj) = record {
             if let Some(value) = obj.get(key) {
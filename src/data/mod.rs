@@ -3,6 +3,14 @@
 
 pub mod storage;
 pub mod export;
+pub mod schema;
+pub mod snapshot;
+pub mod vault;
+pub mod versions;
 
 pub use storage::Storage;
 pub use export::DataExporter;
+pub use schema::{FieldType, OutputSchema};
+pub use snapshot::{PageSnapshot, SnapshotStore};
+pub use vault::{AuthMethod, AuthProfile, Secret, Vault};
+pub use versions::{ChangeReport, PageChange, PageVersion};
@@ -0,0 +1,224 @@
+// Typed output schema for a task
+// Decides the column set once per task so extraction, validation and export
+// all agree on field names, types and order.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Url,
+    Email,
+    Phone,
+    Number,
+    Date,
+    /// ISO 3166-1 country
+    Country,
+    /// State, province or other ISO 3166-2 subdivision
+    Region,
+}
+
+impl FieldType {
+    /// Guess a type from a field name such as "website" or "phone_number".
+    /// Only whole words count, so "tel" types "tel_no" as a phone but not "hotel_name".
+    pub fn infer(name: &str) -> Self {
+        let name = name.to_lowercase();
+        let words: Vec<&str> = name.split(['_', '-', ' ']).filter(|w| !w.is_empty()).collect();
+        // Plurals ("emails", "links") type the same as the singular
+        let has = |keys: &[&str]| {
+            words
+                .iter()
+                .any(|w| keys.iter().any(|k| w == k || w.strip_suffix('s') == Some(k)))
+        };
+
+        if has(&["email", "mail"]) {
+            FieldType::Email
+        } else if has(&["phone", "tel", "mobile", "fax"]) {
+            FieldType::Phone
+        } else if has(&["url", "website", "link", "linkedin", "facebook", "twitter", "instagram", "homepage"]) {
+            FieldType::Url
+        } else if has(&["country"]) {
+            FieldType::Country
+        } else if has(&["region", "state", "province"]) {
+            FieldType::Region
+        } else if has(&["date", "founded", "established", "posted", "deadline"]) {
+            FieldType::Date
+        } else if has(&["count", "price", "rating", "salary", "ranking", "rank", "students", "employees", "year", "score"]) {
+            FieldType::Number
+        } else {
+            FieldType::String
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "string" | "text" => Some(FieldType::String),
+            "url" | "link" => Some(FieldType::Url),
+            "email" => Some(FieldType::Email),
+            "phone" => Some(FieldType::Phone),
+            "number" | "integer" | "float" => Some(FieldType::Number),
+            "date" | "datetime" => Some(FieldType::Date),
+            "country" => Some(FieldType::Country),
+            "region" | "state" | "province" => Some(FieldType::Region),
+            _ => None,
+        }
+    }
+
+    /// Check that a value looks like this type. Empty strings are left to the
+    /// required-field check.
+    pub fn accepts(&self, value: &Value) -> bool {
+        let text = match value {
+            Value::Null => return true,
+            Value::Number(_) => return matches!(self, FieldType::Number | FieldType::String | FieldType::Phone),
+            Value::String(s) => s.trim(),
+            Value::Bool(_) => return matches!(self, FieldType::String),
+            Value::Array(items) => return items.iter().all(|item| self.accepts(item)),
+            Value::Object(_) => return false,
+        };
+        if text.is_empty() {
+            return true;
+        }
+
+        match self {
+            FieldType::String | FieldType::Region => true,
+            FieldType::Country => crate::pipeline::codes::country_code(text).is_some(),
+            FieldType::Url => url::Url::parse(text).map(|u| u.scheme() == "http" || u.scheme() == "https").unwrap_or(false),
+            FieldType::Email => {
+                let mut parts = text.splitn(2, '@');
+                let local = parts.next().unwrap_or_default();
+                let domain = parts.next().unwrap_or_default();
+                !local.is_empty() && domain.contains('.') && !text.contains(char::is_whitespace)
+            }
+            FieldType::Phone => {
+                let digits = text.chars().filter(|c| c.is_ascii_digit()).count();
+                (7..=15).contains(&digits)
+                    && text.chars().all(|c| c.is_ascii_digit() || " +-().".contains(c) || c == 'x')
+            }
+            FieldType::Number => text
+                .trim_start_matches(|c: char| "$€£¥".contains(c))
+                .replace(',', "")
+                .parse::<f64>()
+                .is_ok(),
+            FieldType::Date => {
+                ["%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y", "%B %d, %Y", "%d %B %Y", "%Y"]
+                    .iter()
+                    .any(|fmt| NaiveDate::parse_from_str(text, fmt).is_ok())
+                    || chrono::DateTime::parse_from_rfc3339(text).is_ok()
+                    || NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d").is_ok()
+                    || (text.len() == 4 && text.parse::<u16>().is_ok())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    pub required: bool,
+}
+
+/// A problem found when checking a record against the schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldIssue {
+    pub field: String,
+    pub problem: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputSchema {
+    pub fields: Vec<FieldSpec>,
+}
+
+impl OutputSchema {
+    /// Build a schema from field names, typing them by name. The identifying
+    /// field (name/title) is required, everything else optional.
+    pub fn infer(field_names: &[String]) -> Self {
+        let identifier = field_names
+            .iter()
+            .find(|f| matches!(f.as_str(), "name" | "title"))
+            .or_else(|| field_names.first())
+            .cloned();
+
+        let fields = field_names
+            .iter()
+            .map(|name| FieldSpec {
+                name: name.clone(),
+                field_type: FieldType::infer(name),
+                required: Some(name) == identifier.as_ref(),
+            })
+            .collect();
+
+        Self { fields }
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.fields.iter().map(|f| f.name.clone()).collect()
+    }
+
+    /// Short hash of the field names (in any order), so things derived for one
+    /// schema, such as recipes, aren't reused for another
+    pub fn fingerprint(&self) -> String {
+        let mut names = self.column_names();
+        names.sort();
+        format!("{:x}", Sha256::digest(names.join("\n").as_bytes()))[..12].to_string()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldSpec> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn required_fields(&self) -> impl Iterator<Item = &FieldSpec> {
+        self.fields.iter().filter(|f| f.required)
+    }
+
+    /// Check a record for missing required fields and values of the wrong type
+    pub fn validate(&self, record: &Value) -> Vec<FieldIssue> {
+        let mut issues = Vec::new();
+        let obj = match record.as_object() {
+            Some(obj) => obj,
+            None => {
+                issues.push(FieldIssue { field: String::new(), problem: "record is not an object".to_string() });
+                return issues;
+            }
+        };
+
+        for spec in &self.fields {
+            let value = obj.get(&spec.name).unwrap_or(&Value::Null);
+            let empty = match value {
+                Value::Null => true,
+                Value::String(s) => s.trim().is_empty(),
+                Value::Array(a) => a.is_empty(),
+                _ => false,
+            };
+
+            if empty {
+                if spec.required {
+                    issues.push(FieldIssue { field: spec.name.clone(), problem: "required field is missing".to_string() });
+                }
+            } else if !spec.field_type.accepts(value) {
+                issues.push(FieldIssue {
+                    field: spec.name.clone(),
+                    problem: format!("expected {:?}, got {}", spec.field_type, value),
+                });
+            }
+        }
+
+        issues
+    }
+
+    /// Project a record onto the schema: schema fields in order, missing ones as null
+    pub fn conform(&self, record: &Value) -> Value {
+        let mut out = serde_json::Map::new();
+        for spec in &self.fields {
+            let value = record.get(&spec.name).cloned().unwrap_or(Value::Null);
+            out.insert(spec.name.clone(), value);
+        }
+        Value::Object(out)
+    }
+}
@@ -0,0 +1,106 @@
+// Page snapshots for auditing scraped records
+// Raw HTML (and PNG screenshots of browser-rendered pages) are written under
+// `<directory>/<YYYY-MM-DD>/`, so retention is a matter of dropping whole days.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs;
+use tracing::{debug, info};
+use crate::Result;
+use crate::engine::config::SnapshotConfig;
+
+// Keeps file names readable without running into path length limits
+const MAX_SLUG_LEN: usize = 80;
+
+/// Files captured for one page at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageSnapshot {
+    pub source_url: String,
+    pub captured_at: DateTime<Utc>,
+    pub html_path: Option<String>,
+    pub screenshot_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    config: SnapshotConfig,
+}
+
+impl SnapshotStore {
+    pub fn new(config: &SnapshotConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    pub fn screenshots_enabled(&self) -> bool {
+        self.config.screenshots
+    }
+
+    /// Write whatever was captured for `source_url` and return where it went
+    pub async fn capture(&self, source_url: &str, html: Option<&str>, screenshot: Option<&[u8]>) -> Result<PageSnapshot> {
+        let captured_at = Utc::now();
+        let day_dir = Path::new(&self.config.directory).join(captured_at.format("%Y-%m-%d").to_string());
+        fs::create_dir_all(&day_dir).await?;
+        let stem = format!("{}-{}", slug(source_url), captured_at.format("%H%M%S%3f"));
+
+        let mut snapshot = PageSnapshot {
+            source_url: source_url.to_string(),
+            captured_at,
+            html_path: None,
+            screenshot_path: None,
+        };
+        if let Some(html) = html {
+            let path = day_dir.join(format!("{}.html", stem));
+            fs::write(&path, html).await?;
+            snapshot.html_path = Some(path.to_string_lossy().to_string());
+        }
+        if let Some(png) = screenshot {
+            let path = day_dir.join(format!("{}.png", stem));
+            fs::write(&path, png).await?;
+            snapshot.screenshot_path = Some(path.to_string_lossy().to_string());
+        }
+
+        debug!("Captured snapshot of {}", source_url);
+        Ok(snapshot)
+    }
+
+    /// Delete day directories past the retention period, returning how many were removed
+    pub async fn prune(&self) -> Result<usize> {
+        if self.config.retention_days == 0 || !Path::new(&self.config.directory).exists() {
+            return Ok(0);
+        }
+        let cutoff = Utc::now().date_naive() - chrono::Duration::days(self.config.retention_days as i64);
+
+        let mut removed = 0;
+        let mut entries = fs::read_dir(&self.config.directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Anything that isn't one of our day directories is left alone
+            let Ok(day) = NaiveDate::parse_from_str(&name, "%Y-%m-%d") else {
+                continue;
+            };
+            if day < cutoff && entry.file_type().await?.is_dir() {
+                fs::remove_dir_all(entry.path()).await?;
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            info!("Removed {} day(s) of snapshots older than {} days", removed, self.config.retention_days);
+        }
+        Ok(removed)
+    }
+}
+
+/// File-name-safe version of a URL, e.g. "example.com_universities_page-2"
+fn slug(url: &str) -> String {
+    let trimmed = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let mut slug: String = trimmed
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    slug.truncate(MAX_SLUG_LEN);
+    slug.trim_matches('_').to_string()
+}
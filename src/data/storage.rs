@@ -3,6 +3,12 @@ use serde_json::Value;
 use std::collections::HashMap;
 use tokio::fs;
 use crate::Result;
+use super::schema::OutputSchema;
+use super::snapshot::PageSnapshot;
+use super::versions::PageVersion;
+use crate::engine::ScrapingResult;
+use crate::networking::Cookie;
+use crate::pipeline::Entity;
 
 pub struct Storage {
     pool: SqlitePool,
@@ -19,11 +25,18 @@ impl Storage {
                 url TEXT NOT NULL,
                 data TEXT NOT NULL,
                 timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-                task_id TEXT
+                task_id TEXT,
+                quality_score REAL,
+                confidence REAL,
+                flags TEXT
             )
         "#)
         .execute(&pool)
         .await?;
+        // Databases from before records were scored
+        add_column(&pool, "scraped_data", "quality_score", "REAL").await?;
+        add_column(&pool, "scraped_data", "confidence", "REAL").await?;
+        add_column(&pool, "scraped_data", "flags", "TEXT").await?;
 
         sqlx::query(r#"
             CREATE TABLE IF NOT EXISTS tasks (
@@ -38,23 +51,150 @@ impl Storage {
         .execute(&pool)
         .await?;
 
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS task_schemas (
+                task_id TEXT PRIMARY KEY,
+                query TEXT NOT NULL,
+                schema TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS chat_sessions (
+                id TEXT PRIMARY KEY,
+                title TEXT,
+                state TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS chat_messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS chat_plans (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                plan TEXT NOT NULL,
+                task_id TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS page_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT,
+                source_url TEXT NOT NULL,
+                captured_at TEXT NOT NULL,
+                html_path TEXT,
+                screenshot_path TEXT
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS cookies (
+                scope TEXT NOT NULL,
+                domain TEXT NOT NULL,
+                path TEXT NOT NULL,
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                include_subdomains INTEGER NOT NULL,
+                expires INTEGER,
+                secure INTEGER NOT NULL,
+                http_only INTEGER NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (scope, domain, path, name)
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS crawl_frontier (
+                task_id TEXT NOT NULL,
+                url TEXT NOT NULL,
+                depth INTEGER NOT NULL,
+                parent_url TEXT,
+                status TEXT NOT NULL DEFAULT 'pending',
+                error TEXT,
+                discovered_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                fetched_at DATETIME,
+                PRIMARY KEY (task_id, url)
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS page_versions (
+                scope TEXT NOT NULL,
+                url TEXT NOT NULL,
+                etag TEXT,
+                last_modified TEXT,
+                content_hash TEXT NOT NULL,
+                last_task_id TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (scope, url)
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS entities (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                sources TEXT NOT NULL,
+                provenance TEXT NOT NULL,
+                quality_score REAL NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#)
+        .execute(&pool)
+        .await?;
+
         Ok(Self { pool })
     }
 
-    pub async fn store_scraped_data(&self, url: &str, data: &Value, task_id: Option<&str>) -> Result<i64> {
-        let data_json = serde_json::to_string(data)?;
+    /// Save a scraped record with its quality score and flagged values
+    pub async fn store_scraped_data(&self, result: &ScrapingResult) -> Result<i64> {
+        let data_json = serde_json::to_string(&result.data)?;
         
-        let result = sqlx::query(r#"
-            INSERT INTO scraped_data (url, data, task_id) 
-            VALUES (?, ?, ?)
+        let inserted = sqlx::query(r#"
+            INSERT INTO scraped_data (url, data, task_id, quality_score, confidence, flags) 
+            VALUES (?, ?, ?, ?, ?, ?)
         "#)
-        .bind(url)
+        .bind(&result.source_url)
         .bind(data_json)
-        .bind(task_id)
+        .bind(&result.task_id)
+        .bind(result.quality_score)
+        .bind(result.confidence)
+        .bind(serde_json::to_string(&result.flags)?)
         .execute(&self.pool)
         .await?;
 
-        Ok(result.last_insert_rowid())
+        Ok(inserted.last_insert_rowid())
     }
 
     pub async fn get_scraped_data(&self, limit: Option<i64>) -> Result<Vec<(String, Value)>> {
@@ -80,6 +220,42 @@ impl Storage {
         Ok(results)
     }
 
+    /// Records scraped by a task, in the order they were stored
+    /// A task's records as (url, data, confidence); records stored before
+    /// scoring have no confidence
+    pub async fn get_task_records(&self, task_id: &str) -> Result<Vec<(String, Value, Option<f32>)>> {
+        let rows = sqlx::query(r#"
+            SELECT url, data, confidence FROM scraped_data WHERE task_id = ? ORDER BY id
+        "#)
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let data_str: String = row.get("data");
+                Ok((row.get("url"), serde_json::from_str(&data_str)?, row.get("confidence")))
+            })
+            .collect()
+    }
+
+    /// Save a merged entity along with where each of its fields came from
+    pub async fn store_entity(&self, task_id: &str, entity: &Entity) -> Result<i64> {
+        let result = sqlx::query(r#"
+            INSERT INTO entities (task_id, data, sources, provenance, quality_score)
+            VALUES (?, ?, ?, ?, ?)
+        "#)
+        .bind(task_id)
+        .bind(serde_json::to_string(&entity.data)?)
+        .bind(serde_json::to_string(&entity.sources)?)
+        .bind(serde_json::to_string(&entity.provenance)?)
+        .bind(entity.quality_score)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
     pub async fn store_task(&self, task_id: &str, query: &str, status: &str) -> Result<()> {
         sqlx::query(r#"
             INSERT OR REPLACE INTO tasks (id, query, status) 
@@ -134,6 +310,59 @@ impl Storage {
         }
     }
 
+    pub async fn store_task_schema(&self, task_id: &str, query: &str, schema: &OutputSchema) -> Result<()> {
+        let schema_json = serde_json::to_string(schema)?;
+
+        sqlx::query(r#"
+            INSERT OR REPLACE INTO task_schemas (task_id, query, schema)
+            VALUES (?, ?, ?)
+        "#)
+        .bind(task_id)
+        .bind(query)
+        .bind(schema_json)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_task_schema(&self, task_id: &str) -> Result<Option<OutputSchema>> {
+        let row = sqlx::query(r#"
+            SELECT schema FROM task_schemas WHERE task_id = ?
+        "#)
+        .bind(task_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => {
+                let schema_str: String = row.get("schema");
+                Ok(Some(serde_json::from_str(&schema_str)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Schema used the last time this exact query ran, so re-runs keep their columns
+    pub async fn latest_schema_for_query(&self, query: &str) -> Result<Option<OutputSchema>> {
+        let row = sqlx::query(r#"
+            SELECT schema FROM task_schemas WHERE query = ?
+            ORDER BY created_at DESC
+            LIMIT 1
+        "#)
+        .bind(query)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => {
+                let schema_str: String = row.get("schema");
+                Ok(Some(serde_json::from_str(&schema_str)?))
+            }
+            None => Ok(None),
+        }
+    }
+
     pub async fn clean_old_data(&self, days: i64) -> Result<u64> {
         let result = sqlx::query(r#"
             DELETE FROM scraped_data 
@@ -145,4 +374,507 @@ impl Storage {
 
         Ok(result.rows_affected())
     }
+
+    pub async fn store_page_snapshot(&self, task_id: Option<&str>, snapshot: &PageSnapshot) -> Result<i64> {
+        let result = sqlx::query(r#"
+            INSERT INTO page_snapshots (task_id, source_url, captured_at, html_path, screenshot_path)
+            VALUES (?, ?, ?, ?, ?)
+        "#)
+        .bind(task_id)
+        .bind(&snapshot.source_url)
+        .bind(timestamp(&snapshot.captured_at))
+        .bind(&snapshot.html_path)
+        .bind(&snapshot.screenshot_path)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// The snapshot a record extracted from `source_url` at `extracted_at` came from:
+    /// the latest one captured no later than that
+    pub async fn snapshot_for(&self, source_url: &str, extracted_at: &chrono::DateTime<chrono::Utc>) -> Result<Option<PageSnapshot>> {
+        let row = sqlx::query(r#"
+            SELECT source_url, captured_at, html_path, screenshot_path FROM page_snapshots
+            WHERE source_url = ? AND captured_at <= ?
+            ORDER BY captured_at DESC
+            LIMIT 1
+        "#)
+        .bind(source_url)
+        .bind(timestamp(extracted_at))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => {
+                let captured_at: String = row.get("captured_at");
+                Ok(Some(PageSnapshot {
+                    source_url: row.get("source_url"),
+                    captured_at: chrono::DateTime::parse_from_rfc3339(&captured_at)?.with_timezone(&chrono::Utc),
+                    html_path: row.get("html_path"),
+                    screenshot_path: row.get("screenshot_path"),
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Forget snapshots whose files have been pruned
+    pub async fn clean_old_snapshots(&self, days: i64) -> Result<u64> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+        let result = sqlx::query(r#"
+            DELETE FROM page_snapshots WHERE captured_at < ?
+        "#)
+        .bind(timestamp(&cutoff))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Live cookies saved under `scope` ("global" or a task ID)
+    pub async fn load_cookies(&self, scope: &str) -> Result<Vec<Cookie>> {
+        let rows = sqlx::query(r#"
+            SELECT domain, path, name, value, include_subdomains, expires, secure, http_only
+            FROM cookies
+            WHERE scope = ? AND (expires IS NULL OR expires > CAST(strftime('%s', 'now') AS INTEGER))
+            ORDER BY domain, path, name
+        "#)
+        .bind(scope)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Cookie {
+                domain: row.get("domain"),
+                include_subdomains: row.get("include_subdomains"),
+                path: row.get("path"),
+                name: row.get("name"),
+                value: row.get("value"),
+                expires: row.get("expires"),
+                secure: row.get("secure"),
+                http_only: row.get("http_only"),
+            })
+            .collect())
+    }
+
+    /// Insert or update cookies under `scope`
+    pub async fn save_cookies(&self, scope: &str, cookies: &[Cookie]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for cookie in cookies {
+            sqlx::query(r#"
+                INSERT INTO cookies (scope, domain, path, name, value, include_subdomains, expires, secure, http_only)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (scope, domain, path, name) DO UPDATE SET
+                    value = excluded.value,
+                    include_subdomains = excluded.include_subdomains,
+                    expires = excluded.expires,
+                    secure = excluded.secure,
+                    http_only = excluded.http_only,
+                    updated_at = CURRENT_TIMESTAMP
+            "#)
+            .bind(scope)
+            .bind(&cookie.domain)
+            .bind(&cookie.path)
+            .bind(&cookie.name)
+            .bind(&cookie.value)
+            .bind(cookie.include_subdomains)
+            .bind(cookie.expires)
+            .bind(cookie.secure)
+            .bind(cookie.http_only)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Make `scope`'s saved cookies exactly `cookies`, so ones the server
+    /// deleted or let expire don't come back on the next run
+    pub async fn replace_cookies(&self, scope: &str, cookies: &[Cookie]) -> Result<()> {
+        let existing = sqlx::query(r#"
+            SELECT domain, path, name FROM cookies WHERE scope = ?
+        "#)
+        .bind(scope)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+        for row in existing {
+            let (domain, path, name): (String, String, String) = (row.get("domain"), row.get("path"), row.get("name"));
+            if !cookies.iter().any(|c| c.domain == domain && c.path == path && c.name == name) {
+                sqlx::query(r#"
+                    DELETE FROM cookies WHERE scope = ? AND domain = ? AND path = ? AND name = ?
+                "#)
+                .bind(scope)
+                .bind(&domain)
+                .bind(&path)
+                .bind(&name)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        self.save_cookies(scope, cookies).await
+    }
+
+    /// Delete cookies, narrowed to a scope and/or a domain (and its subdomains)
+    pub async fn clear_cookies(&self, scope: Option<&str>, domain: Option<&str>) -> Result<u64> {
+        let subdomains = domain.map(|d| format!("%.{}", d));
+        let result = sqlx::query(r#"
+            DELETE FROM cookies
+            WHERE (?1 IS NULL OR scope = ?1)
+              AND (?2 IS NULL OR domain = ?2 OR domain LIKE ?3)
+        "#)
+        .bind(scope)
+        .bind(domain)
+        .bind(subdomains)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Add URLs to a crawl's frontier; URLs it has already seen are skipped.
+    /// Returns how many were new.
+    pub async fn enqueue_frontier(&self, task_id: &str, entries: &[(String, u32, Option<String>)]) -> Result<u64> {
+        let mut added = 0;
+        let mut tx = self.pool.begin().await?;
+        for (url, depth, parent_url) in entries {
+            let result = sqlx::query(r#"
+                INSERT OR IGNORE INTO crawl_frontier (task_id, url, depth, parent_url)
+                VALUES (?, ?, ?, ?)
+            "#)
+            .bind(task_id)
+            .bind(url)
+            .bind(*depth as i64)
+            .bind(parent_url)
+            .execute(&mut *tx)
+            .await?;
+            added += result.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Take the shallowest pending URL of a crawl, oldest first, and mark it as fetching
+    pub async fn claim_frontier_url(&self, task_id: &str) -> Result<Option<(String, u32, Option<String>)>> {
+        let row = sqlx::query(r#"
+            SELECT url, depth, parent_url FROM crawl_frontier
+            WHERE task_id = ? AND status = 'pending'
+            ORDER BY depth, rowid
+            LIMIT 1
+        "#)
+        .bind(task_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let url: String = row.get("url");
+        let depth: i64 = row.get("depth");
+
+        sqlx::query(r#"
+            UPDATE crawl_frontier SET status = 'fetching' WHERE task_id = ? AND url = ?
+        "#)
+        .bind(task_id)
+        .bind(&url)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some((url, depth as u32, row.get("parent_url"))))
+    }
+    /// Record the outcome of fetching a frontier URL ("done", "failed", or "blocked" by robots.txt)
+    /// Record the outcome of fetching a frontier URL ("done" or "failed")
+    pub async fn finish_frontier_url(&self, task_id: &str, url: &str, status: &str, error: Option<&str>) -> Result<()> {
+        sqlx::query(r#"
+            UPDATE crawl_frontier SET status = ?, error = ?, fetched_at = CURRENT_TIMESTAMP
+            WHERE task_id = ? AND url = ?
+        "#)
+        .bind(status)
+        .bind(error)
+        .bind(task_id)
+        .bind(url)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Put URLs that were being fetched when a crawl stopped back in the queue
+    pub async fn requeue_frontier(&self, task_id: &str) -> Result<u64> {
+        let result = sqlx::query(r#"
+            UPDATE crawl_frontier SET status = 'pending' WHERE task_id = ? AND status = 'fetching'
+        "#)
+        .bind(task_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The URLs a crawl started from
+    pub async fn frontier_seeds(&self, task_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(r#"
+            SELECT url FROM crawl_frontier WHERE task_id = ? AND depth = 0 ORDER BY rowid
+        "#)
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("url")).collect())
+    }
+
+    /// Number of frontier URLs per status for a crawl
+    pub async fn frontier_counts(&self, task_id: &str) -> Result<HashMap<String, u64>> {
+        let rows = sqlx::query(r#"
+            SELECT status, COUNT(*) AS count FROM crawl_frontier WHERE task_id = ? GROUP BY status
+        "#)
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let count: i64 = row.get("count");
+                (row.get("status"), count as u64)
+            })
+            .collect())
+    }
+
+    /// Page versions recorded by earlier runs of a recurring scrape, by URL
+    pub async fn load_page_versions(&self, scope: &str) -> Result<HashMap<String, PageVersion>> {
+        let rows = sqlx::query(r#"
+            SELECT url, etag, last_modified, content_hash FROM page_versions WHERE scope = ?
+        "#)
+        .bind(scope)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let version = PageVersion {
+                    url: row.get("url"),
+                    etag: row.get("etag"),
+                    last_modified: row.get("last_modified"),
+                    content_hash: row.get("content_hash"),
+                };
+                (version.url.clone(), version)
+            })
+            .collect())
+    }
+
+    /// Record the version of a page seen by `task_id`
+    pub async fn save_page_version(&self, scope: &str, task_id: &str, version: &PageVersion) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO page_versions (scope, url, etag, last_modified, content_hash, last_task_id)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (scope, url) DO UPDATE SET
+                etag = excluded.etag,
+                last_modified = excluded.last_modified,
+                content_hash = excluded.content_hash,
+                last_task_id = excluded.last_task_id,
+                updated_at = CURRENT_TIMESTAMP
+        "#)
+        .bind(scope)
+        .bind(&version.url)
+        .bind(&version.etag)
+        .bind(&version.last_modified)
+        .bind(&version.content_hash)
+        .bind(task_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forget the pages of `scope` that `task_id` didn't see, returning their URLs
+    pub async fn remove_stale_page_versions(&self, scope: &str, task_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(r#"
+            DELETE FROM page_versions WHERE scope = ? AND last_task_id != ? RETURNING url
+        "#)
+        .bind(scope)
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("url")).collect())
+    }
+
+    pub async fn create_chat_session(&self, session_id: &str, title: Option<&str>) -> Result<()> {
+        sqlx::query(r#"
+            INSERT OR IGNORE INTO chat_sessions (id, title) VALUES (?, ?)
+        "#)
+        .bind(session_id)
+        .bind(title)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_chat_session_title(&self, session_id: &str, title: &str) -> Result<()> {
+        sqlx::query(r#"
+            UPDATE chat_sessions SET title = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?
+        "#)
+        .bind(title)
+        .bind(session_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn save_chat_state(&self, session_id: &str, state: &Value) -> Result<()> {
+        let state_json = serde_json::to_string(state)?;
+
+        sqlx::query(r#"
+            UPDATE chat_sessions SET state = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?
+        "#)
+        .bind(state_json)
+        .bind(session_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn append_chat_message(&self, session_id: &str, role: &str, content: &str) -> Result<i64> {
+        let result = sqlx::query(r#"
+            INSERT INTO chat_messages (session_id, role, content) VALUES (?, ?, ?)
+        "#)
+        .bind(session_id)
+        .bind(role)
+        .bind(content)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn store_chat_plan(&self, session_id: &str, plan: &Value) -> Result<i64> {
+        let plan_json = serde_json::to_string(plan)?;
+
+        let result = sqlx::query(r#"
+            INSERT INTO chat_plans (session_id, plan) VALUES (?, ?)
+        "#)
+        .bind(session_id)
+        .bind(plan_json)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Record which task a stored plan launched
+    pub async fn link_chat_plan_task(&self, plan_id: i64, task_id: &str) -> Result<()> {
+        sqlx::query(r#"
+            UPDATE chat_plans SET task_id = ? WHERE id = ?
+        "#)
+        .bind(task_id)
+        .bind(plan_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load a session's title, saved state and messages (role, content) in order
+    pub async fn get_chat_session(&self, session_id: &str) -> Result<Option<(Option<String>, Option<Value>, Vec<(String, String)>)>> {
+        let row = sqlx::query(r#"
+            SELECT title, state FROM chat_sessions WHERE id = ?
+        "#)
+        .bind(session_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let title: Option<String> = row.get("title");
+        let state: Option<String> = row.get("state");
+        let state = match state {
+            Some(state) => Some(serde_json::from_str(&state)?),
+            None => None,
+        };
+
+        let rows = sqlx::query(r#"
+            SELECT role, content FROM chat_messages WHERE session_id = ? ORDER BY id ASC
+        "#)
+        .bind(session_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let messages = rows
+            .into_iter()
+            .map(|row| (row.get("role"), row.get("content")))
+            .collect();
+
+        Ok(Some((title, state, messages)))
+    }
+
+    /// Plans produced in a session with the task each one launched, oldest first
+    pub async fn get_chat_plans(&self, session_id: &str) -> Result<Vec<(Value, Option<String>)>> {
+        let rows = sqlx::query(r#"
+            SELECT plan, task_id FROM chat_plans WHERE session_id = ? ORDER BY id ASC
+        "#)
+        .bind(session_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut plans = Vec::new();
+        for row in rows {
+            let plan_str: String = row.get("plan");
+            let task_id: Option<String> = row.get("task_id");
+            plans.push((serde_json::from_str(&plan_str)?, task_id));
+        }
+
+        Ok(plans)
+    }
+
+    /// Most recently used sessions as (id, title, updated_at)
+    pub async fn list_chat_sessions(&self, limit: Option<i64>) -> Result<Vec<(String, Option<String>, String)>> {
+        let limit = limit.unwrap_or(20);
+
+        let rows = sqlx::query(r#"
+            SELECT id, title, CAST(updated_at AS TEXT) AS updated_at FROM chat_sessions
+            ORDER BY updated_at DESC
+            LIMIT ?
+        "#)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("id"), row.get("title"), row.get("updated_at")))
+            .collect())
+    }
+}
+
+// Fixed-width RFC 3339 so timestamps stored as text compare in time order
+fn timestamp(at: &chrono::DateTime<chrono::Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+// Add a column to a table created by an older version, if it isn't there yet
+async fn add_column(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = sqlx::query("SELECT 1 FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+    if !exists {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
 }
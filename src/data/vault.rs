@@ -0,0 +1,226 @@
+// Credential vault
+// Per-domain auth profiles kept in an encrypted file next to the config, so
+// secrets never live in flash.toml. The file is a small JSON envelope around
+// XChaCha20-Poly1305 ciphertext, keyed with Argon2 from a passphrase that is
+// read from the environment.
+
+use anyhow::{anyhow, bail, Context};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::Result;
+
+const VAULT_VERSION: u32 = 1;
+
+/// A secret value that never shows up in Debug or Display output
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The actual value, for handing to the request that needs it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(********)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "********")
+    }
+}
+
+/// How to log in to a site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthMethod {
+    Basic {
+        username: String,
+        password: Secret,
+    },
+    Bearer {
+        token: Secret,
+    },
+    /// Fill in a login form in the browser; the session cookies it sets are
+    /// shared with HTTP requests through the task's cookie jar
+    Form {
+        login_url: String,
+        username: String,
+        password: Secret,
+        username_selector: String,
+        password_selector: String,
+        submit_selector: String,
+        /// Element that only appears once logged in
+        #[serde(default)]
+        success_selector: Option<String>,
+    },
+    /// TLS client certificate: PEM with the certificate chain and private key
+    ClientCert {
+        identity_pem: Secret,
+    },
+}
+
+impl AuthMethod {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Basic { .. } => "basic",
+            Self::Bearer { .. } => "bearer",
+            Self::Form { .. } => "form",
+            Self::ClientCert { .. } => "client_cert",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthProfile {
+    /// Applies to this host and its subdomains
+    pub domain: String,
+    pub method: AuthMethod,
+}
+
+impl AuthProfile {
+    pub fn matches(&self, host: &str) -> bool {
+        host == self.domain || host.ends_with(&format!(".{}", self.domain))
+    }
+}
+
+/// The most specific profile for `host`, e.g. "portal.example.com" over "example.com"
+pub fn profile_for<'a>(profiles: &'a [AuthProfile], host: &str) -> Option<&'a AuthProfile> {
+    profiles
+        .iter()
+        .filter(|p| p.matches(host))
+        .max_by_key(|p| p.domain.len())
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub struct Vault {
+    path: PathBuf,
+    salt: Vec<u8>,
+    key: [u8; 32],
+    profiles: Vec<AuthProfile>,
+}
+
+impl Vault {
+    /// Open the vault at `path`, creating an empty one if it doesn't exist yet
+    pub fn open<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if passphrase.is_empty() {
+            bail!("Vault passphrase is empty");
+        }
+
+        if !path.exists() {
+            let mut salt = vec![0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            let key = derive_key(passphrase, &salt)?;
+            return Ok(Self { path, salt, key, profiles: Vec::new() });
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read vault {}", path.display()))?;
+        let envelope: Envelope = serde_json::from_str(&content).context("Vault file is corrupt")?;
+        if envelope.version != VAULT_VERSION {
+            bail!("Unsupported vault version {}", envelope.version);
+        }
+
+        let salt = BASE64.decode(&envelope.salt)?;
+        let nonce = BASE64.decode(&envelope.nonce)?;
+        let ciphertext = BASE64.decode(&envelope.ciphertext)?;
+        if nonce.len() != 24 {
+            bail!("Vault file is corrupt (nonce is {} bytes, expected 24)", nonce.len());
+        }
+        let key = derive_key(passphrase, &salt)?;
+
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            // Wrong passphrase and tampering look the same; don't hint at which
+            .map_err(|_| anyhow!("Could not unlock vault {} (wrong passphrase?)", path.display()))?;
+        let profiles = serde_json::from_slice(&plaintext)?;
+
+        Ok(Self { path, salt, key, profiles })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let plaintext = serde_json::to_vec(&self.profiles)?;
+        let mut nonce = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = XChaCha20Poly1305::new(&self.key.into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|_| anyhow!("Failed to encrypt vault"))?;
+
+        let envelope = Envelope {
+            version: VAULT_VERSION,
+            salt: BASE64.encode(&self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        // Write beside the vault and rename over it, so a crash mid-write
+        // can't leave a truncated vault and lose every profile
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, b"")?;
+        restrict_permissions(&temp)?;
+        std::fs::write(&temp, serde_json::to_string_pretty(&envelope)?)?;
+        std::fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to replace vault {}", self.path.display()))?;
+        Ok(())
+    }
+
+    pub fn profiles(&self) -> &[AuthProfile] {
+        &self.profiles
+    }
+
+    /// Add a profile, replacing any existing one for the same domain
+    pub fn upsert(&mut self, profile: AuthProfile) {
+        self.profiles.retain(|p| p.domain != profile.domain);
+        self.profiles.push(profile);
+    }
+
+    pub fn remove(&mut self, domain: &str) -> bool {
+        let before = self.profiles.len();
+        self.profiles.retain(|p| p.domain != domain);
+        self.profiles.len() != before
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive vault key: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
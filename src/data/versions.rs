@@ -0,0 +1,77 @@
+// Page versions for incremental re-crawls
+// What we knew about each page the last time a recurring scrape fetched it:
+// its HTTP validators (ETag, Last-Modified) for conditional requests, and a
+// hash of its visible text so pages served without validators can still be
+// recognised as unchanged.
+
+use sha2::{Digest, Sha256};
+
+use crate::extraction::html::clean_text;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageVersion {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_hash: String,
+}
+
+impl PageVersion {
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// Hash of a page's visible text. Markup-only churn (nonces, CSRF tokens,
+/// cache-busting script URLs) doesn't count as a change.
+pub fn content_hash(html: &str) -> String {
+    format!("{:x}", Sha256::digest(clean_text(html, None).as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageChange {
+    New,
+    Changed,
+    Unchanged,
+}
+
+impl PageChange {
+    /// Compare a fetched page with the version stored from the last run
+    pub fn between(previous: Option<&PageVersion>, content_hash: &str) -> Self {
+        match previous {
+            None => PageChange::New,
+            Some(previous) if previous.content_hash == content_hash => PageChange::Unchanged,
+            Some(_) => PageChange::Changed,
+        }
+    }
+}
+
+/// Pages that were new, changed, unchanged or gone in a run
+#[derive(Debug, Clone, Default)]
+pub struct ChangeReport {
+    pub new: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ChangeReport {
+    pub fn record(&mut self, url: &str, change: PageChange) {
+        let list = match change {
+            PageChange::New => &mut self.new,
+            PageChange::Changed => &mut self.changed,
+            PageChange::Unchanged => &mut self.unchanged,
+        };
+        if !list.iter().any(|u| u == url) {
+            list.push(url.to_string());
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} new, {} changed, {} unchanged, {} removed",
+               self.new.len(),
+               self.changed.len(),
+               self.unchanged.len(),
+               self.removed.len())
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +10,32 @@ pub struct Config {
     pub networking: NetworkingConfig,
     pub ai: AiConfig,
     pub output: OutputConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub extraction: ExtractionConfig,
+    #[serde(default)]
+    pub browser: BrowserConfig,
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+    #[serde(default)]
+    pub cookies: CookieConfig,
+    #[serde(default)]
+    pub vault: VaultConfig,
+    #[serde(default)]
+    pub crawl: CrawlConfig,
+    #[serde(default)]
+    pub pagination: PaginationConfig,
+    #[serde(default)]
+    pub http_cache: HttpCacheConfig,
+    #[serde(default)]
+    pub warc: WarcConfig,
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    #[serde(default)]
+    pub normalize: NormalizeConfig,
+    #[serde(default)]
+    pub quality: QualityConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,9 +66,48 @@ pub struct NetworkingConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiConfig {
+    /// Directory of a local ONNX intent/slot model (see `ai_interface::local`),
+    /// or a `.gguf` file run in-process as the backend (see `ai_interface::gguf`)
     pub model_path: Option<String>,
     pub confidence_threshold: f32,
     pub max_context_length: u32,
+    #[serde(default = "default_response_tokens")]
+    pub response_tokens: u32,
+    #[serde(default)]
+    pub backend: AiBackendConfig,
+}
+
+/// Which AI backend answers natural language requests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AiBackendConfig {
+    /// No model, only the built-in rule-based parser
+    #[default]
+    RuleBased,
+    /// OpenAI-compatible chat completions endpoint (OpenAI, llama.cpp server, Ollama, ...)
+    OpenAi {
+        base_url: String,
+        model: String,
+        /// Environment variable holding the API key, if the endpoint needs one
+        api_key_env: Option<String>,
+        timeout_seconds: u64,
+    },
+    /// External process speaking line-delimited JSON-RPC over stdin/stdout
+    Subprocess {
+        command: String,
+        args: Vec<String>,
+        /// How long to wait for the process to answer a request
+        #[serde(default = "default_bridge_timeout")]
+        timeout_seconds: u64,
+    },
+}
+
+fn default_response_tokens() -> u32 {
+    512
+}
+
+fn default_bridge_timeout() -> u64 {
+    120
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +118,404 @@ pub struct OutputConfig {
     pub compress_results: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub database_url: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            database_url: "sqlite://flash.db?mode=rwc".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractionConfig {
+    /// Where per-domain selector recipes are saved and looked up
+    pub recipes_dir: String,
+    /// Pages of AI extraction to collect per domain before deriving a recipe (0 disables)
+    pub induction_samples: usize,
+    /// Fraction of sample values a derived recipe must reproduce for each field
+    pub min_agreement: f32,
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            recipes_dir: "recipes".to_string(),
+            induction_samples: 3,
+            min_agreement: 0.8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserConfig {
+    /// Chromium/Chrome binary; detected automatically when unset
+    pub executable: Option<String>,
+    pub headless: bool,
+    pub navigation_timeout_seconds: u64,
+    /// How long the network must be quiet before a page counts as loaded
+    pub network_idle_ms: u64,
+    pub extra_args: Vec<String>,
+    /// Browser processes to keep; tasks share them through isolated contexts
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+    #[serde(default = "default_tabs_per_browser")]
+    pub tabs_per_browser: usize,
+    /// Close a tab after this many page loads to keep memory in check
+    #[serde(default = "default_max_navigations")]
+    pub max_navigations_per_tab: u32,
+}
+
+fn default_pool_size() -> usize {
+    2
+}
+
+fn default_tabs_per_browser() -> usize {
+    4
+}
+
+fn default_max_navigations() -> u32 {
+    50
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        Self {
+            executable: None,
+            headless: true,
+            navigation_timeout_seconds: 30,
+            network_idle_ms: 500,
+            extra_args: Vec::new(),
+            pool_size: default_pool_size(),
+            tabs_per_browser: default_tabs_per_browser(),
+            max_navigations_per_tab: default_max_navigations(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Keep the raw HTML of every scraped page for auditing
+    pub enabled: bool,
+    /// Also save PNG screenshots of pages rendered in the browser
+    pub screenshots: bool,
+    pub directory: String,
+    /// Snapshots older than this are deleted (0 keeps them forever)
+    pub retention_days: u32,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            screenshots: true,
+            directory: "snapshots".to_string(),
+            retention_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CookieConfig {
+    /// Keep cookies in storage between runs
+    pub persist: bool,
+    /// Give each task its own jar (seeded from the global one) instead of
+    /// writing what it receives back to the shared jar
+    pub isolate_tasks: bool,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            isolate_tasks: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VaultConfig {
+    /// Encrypted file holding per-domain auth profiles
+    pub path: String,
+    /// Environment variable with the vault passphrase (never stored in config)
+    pub passphrase_env: String,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            path: "flash.vault".to_string(),
+            passphrase_env: "FLASH_VAULT_PASSPHRASE".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrawlConfig {
+    /// Links followed away from a seed page (0 fetches only the seeds)
+    pub max_depth: u32,
+    /// Pages fetched per crawl, including seeds
+    pub max_pages: usize,
+    /// Stay on the seeds' hosts and their subdomains
+    pub same_domain: bool,
+    /// Extra hosts (and their subdomains) the crawl may visit
+    pub allowed_domains: Vec<String>,
+    /// Regexes a discovered URL must match one of, when any are given
+    pub include: Vec<String>,
+    /// Regexes that rule a discovered URL out
+    pub exclude: Vec<String>,
+    /// Query parameters dropped when canonicalising URLs; a trailing `*` matches a prefix
+    pub ignore_params: Vec<String>,
+    /// Also seed the crawl with the URLs in the seed sites' sitemaps
+    #[serde(default)]
+    pub sitemaps: bool,
+    /// Sitemap files to read per crawl, indexes included
+    #[serde(default = "default_max_sitemaps")]
+    pub max_sitemaps: usize,
+}
+
+fn default_max_sitemaps() -> usize {
+    50
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            max_pages: 100,
+            same_domain: true,
+            allowed_domains: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            ignore_params: vec![
+                "utm_*".to_string(),
+                "fbclid".to_string(),
+                "gclid".to_string(),
+                "sessionid".to_string(),
+                "phpsessid".to_string(),
+            ],
+            sitemaps: false,
+            max_sitemaps: default_max_sitemaps(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaginationConfig {
+    /// Follow next-page links on list pages
+    pub enabled: bool,
+    /// Result pages to read per listing, including the first
+    pub max_pages: usize,
+    /// Scroll listings without next-page links in the browser to load more results
+    pub infinite_scroll: bool,
+    pub max_scrolls: u32,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_pages: 50,
+            infinite_scroll: true,
+            max_scrolls: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Follow Cache-Control and Expires; `ttl_seconds` applies when neither is sent
+    Respect,
+    /// Keep every successful response for `ttl_seconds` (0 = forever)
+    Override,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpCacheConfig {
+    /// Keep fetched responses on disk and reuse them
+    pub enabled: bool,
+    /// Relative paths are under the output directory
+    pub directory: String,
+    pub mode: CacheMode,
+    pub ttl_seconds: u64,
+    /// Only serve from the cache and fail on a miss (`flash execute --offline`)
+    pub offline: bool,
+}
+
+impl Default for HttpCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "http-cache".to_string(),
+            mode: CacheMode::Respect,
+            ttl_seconds: 86400,
+            offline: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarcConfig {
+    /// Archive every HTTP exchange and rendered DOM to WARC files
+    pub enabled: bool,
+    /// Relative paths are under the output directory
+    pub directory: String,
+    /// Start a new file once the current one reaches this size
+    pub max_file_mb: u64,
+    /// Gzip each record (.warc.gz)
+    pub compress: bool,
+}
+
+impl Default for WarcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "warc".to_string(),
+            max_file_mb: 1024,
+            compress: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    /// Same website (host and path, ignoring scheme and www.)
+    Url,
+    /// Same website domain
+    Domain,
+    /// Similar names in the same location
+    NameLocation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Prefer sources earlier in `source_priority`, then higher quality
+    SourcePriority,
+    /// Prefer higher quality records, then `source_priority`
+    QualityScore,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    /// Merge records describing the same entity before storing and exporting.
+    /// Off by default, since merging changes what gets exported.
+    pub enabled: bool,
+    /// Records matching on any of these keys are merged
+    pub keys: Vec<DedupKey>,
+    pub name_fields: Vec<String>,
+    pub location_fields: Vec<String>,
+    pub url_fields: Vec<String>,
+    /// How alike two names (and locations) must be to match, from 0 to 1
+    pub name_similarity: f32,
+    pub conflict: ConflictPolicy,
+    /// Source domains, most trusted first
+    pub source_priority: Vec<String>,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: vec![DedupKey::Url, DedupKey::NameLocation],
+            name_fields: vec!["name".to_string()],
+            location_fields: vec!["location".to_string(), "city".to_string(), "address".to_string(), "country".to_string()],
+            url_fields: vec!["website".to_string(), "url".to_string(), "homepage".to_string()],
+            name_similarity: 0.9,
+            conflict: ConflictPolicy::QualityScore,
+            source_priority: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeConfig {
+    /// Clean up and normalise typed fields, flagging values that don't pass
+    pub enabled: bool,
+    /// ISO 3166-1 alpha-2 country for phone numbers and regions of records without a country
+    pub default_country: String,
+    /// Read 03/04/2024 as 3 April rather than March 4
+    pub day_first: bool,
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_country: "US".to_string(),
+            day_first: false,
+        }
+    }
+}
+
+/// How much each signal counts towards a record's quality score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityWeights {
+    /// Share of the schema's fields filled, required fields counting double
+    pub completeness: f32,
+    /// Share of the filled values that passed normalisation and validation
+    pub validation: f32,
+    /// How sure the extractor was of the record
+    pub confidence: f32,
+    /// How far the source site is trusted
+    pub reliability: f32,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        Self {
+            completeness: 0.35,
+            validation: 0.35,
+            confidence: 0.15,
+            reliability: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    pub weights: QualityWeights,
+    /// Reliability from 0 to 1 of source domains, e.g. { "wikipedia.org" = 0.9 }
+    pub source_reliability: HashMap<String, f32>,
+    /// Reliability of sources not listed above
+    pub default_reliability: f32,
+    /// Confidence given to records extracted by selector recipes
+    pub selector_confidence: f32,
+    /// Records scoring below this are dropped rather than stored and exported
+    pub min_score: f32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            weights: QualityWeights::default(),
+            source_reliability: HashMap::new(),
+            default_reliability: 0.8,
+            selector_confidence: 0.9,
+            min_score: 0.0,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -79,6 +543,8 @@ impl Default for Config {
                 model_path: None,
                 confidence_threshold: 0.7,
                 max_context_length: 4096,
+                response_tokens: default_response_tokens(),
+                backend: AiBackendConfig::RuleBased,
             },
             output: OutputConfig {
                 default_format: "csv".to_string(),
@@ -86,6 +552,19 @@ impl Default for Config {
                 include_metadata: true,
                 compress_results: false,
             },
+            storage: StorageConfig::default(),
+            extraction: ExtractionConfig::default(),
+            browser: BrowserConfig::default(),
+            snapshots: SnapshotConfig::default(),
+            cookies: CookieConfig::default(),
+            vault: VaultConfig::default(),
+            crawl: CrawlConfig::default(),
+            pagination: PaginationConfig::default(),
+            http_cache: HttpCacheConfig::default(),
+            warc: WarcConfig::default(),
+            dedup: DedupConfig::default(),
+            normalize: NormalizeConfig::default(),
+            quality: QualityConfig::default(),
         }
     }
 }
@@ -95,6 +574,7 @@ impl Config {
         if path.as_ref().exists() {
             let content = std::fs::read_to_string(path)?;
             let config: Config = toml::from_str(&content)?;
+            config.validate()?;
             Ok(config)
         } else {
             // Create default config file
@@ -105,6 +585,29 @@ impl Config {
         }
     }
 
+    /// Reject settings that load but make no sense
+    pub fn validate(&self) -> Result<()> {
+        let quality = &self.quality;
+        let score = |name: &str, value: f32| {
+            if (0.0..=1.0).contains(&value) {
+                Ok(())
+            } else {
+                Err(anyhow!("quality.{} must be between 0 and 1, got {}", name, value))
+            }
+        };
+        score("min_score", quality.min_score)?;
+        score("default_reliability", quality.default_reliability)?;
+        score("selector_confidence", quality.selector_confidence)?;
+        for (domain, reliability) in &quality.source_reliability {
+            score(&format!("source_reliability.\"{}\"", domain), *reliability)?;
+        }
+        let weights = &quality.weights;
+        if [weights.completeness, weights.validation, weights.confidence, weights.reliability].iter().any(|w| *w < 0.0) {
+            bail!("quality.weights must not be negative");
+        }
+        Ok(())
+    }
+
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let content = toml::to_string_pretty(self)?;
         std::fs::write(path, content)?;
@@ -0,0 +1,203 @@
+// Multi-turn chat state
+// Tracks the conversation so far, the plan being built and which slot we're
+// currently asking about, so follow-up answers fill in the right part of the plan.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai_interface::ChatMessage;
+use super::parser::{parse_number, EntityKind, Location, TaskPlan};
+
+/// Parts of a plan we may need to ask the user about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Slot {
+    Entity,
+    Location,
+    Count,
+    Fields,
+}
+
+impl Slot {
+    pub fn question(&self, plan: &TaskPlan) -> String {
+        match self {
+            Slot::Entity => match &plan.entity {
+                Some(entity) => format!("Just to check, you want me to collect \"{}\"? If not, what should I look for?", entity),
+                None => "What kind of things should I collect (e.g. universities, restaurants, companies)?".to_string(),
+            },
+            Slot::Location => "Where should I look (city, region or country)? Say \"anywhere\" for no limit.".to_string(),
+            Slot::Count => "How many results do you need? Say \"all\" for no limit.".to_string(),
+            Slot::Fields => format!(
+                "Which fields do you want? I'd collect: {}. Reply \"ok\" to keep these or list your own.",
+                plan.fields.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DialogueState {
+    Idle,
+    Clarifying(Slot),
+    AwaitingConfirmation,
+}
+
+/// What the chat loop should do after a user turn
+#[derive(Debug, Clone)]
+pub enum DialogueAction {
+    Reply(String),
+    /// The user confirmed the plan; run it. Carries the stored plan id, if any.
+    Execute(TaskPlan, Option<i64>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub session_id: String,
+    pub title: Option<String>,
+    /// Messages are persisted separately in `chat_messages`
+    #[serde(skip)]
+    pub history: Vec<ChatMessage>,
+    pub plan: Option<TaskPlan>,
+    /// Storage id of the last saved snapshot of `plan`
+    #[serde(default)]
+    pub plan_id: Option<i64>,
+    pub state: DialogueState,
+    /// Slots the user has answered explicitly, so we don't ask twice
+    pub answered: Vec<Slot>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self {
+            session_id: uuid::Uuid::new_v4().to_string(),
+            title: None,
+            history: Vec::new(),
+            plan: None,
+            plan_id: None,
+            state: DialogueState::Idle,
+            answered: Vec::new(),
+        }
+    }
+
+    /// Start over with a fresh plan, keeping the message history
+    pub fn reset_plan(&mut self) {
+        self.plan = None;
+        self.plan_id = None;
+        self.state = DialogueState::Idle;
+        self.answered.clear();
+    }
+
+    /// First slot that still needs a follow-up question
+    pub fn next_missing_slot(&self, confidence_threshold: f32) -> Option<Slot> {
+        let plan = self.plan.as_ref()?;
+        let unanswered = |slot: Slot| !self.answered.contains(&slot);
+
+        if unanswered(Slot::Entity)
+            && (plan.entity.is_none() || plan.entity_kind == EntityKind::Other || plan.confidence < confidence_threshold)
+        {
+            return Some(Slot::Entity);
+        }
+        if unanswered(Slot::Location) && plan.location.is_none() {
+            return Some(Slot::Location);
+        }
+        if unanswered(Slot::Count) && plan.count.is_none() {
+            return Some(Slot::Count);
+        }
+        if unanswered(Slot::Fields) && !plan.fields_explicit {
+            return Some(Slot::Fields);
+        }
+        None
+    }
+
+    /// Apply the user's answer to the slot we asked about. Returns false when the
+    /// answer couldn't be understood and the question should be repeated.
+    pub fn fill_slot(&mut self, slot: Slot, answer: &str, parsed: &TaskPlan) -> bool {
+        let plan = match self.plan.as_mut() {
+            Some(plan) => plan,
+            None => return false,
+        };
+        let answer = answer.trim();
+        let lower = answer.to_lowercase();
+
+        // The answer may carry more than we asked for ("50 in tokyo")
+        plan.merge_from(parsed);
+
+        let filled = match slot {
+            Slot::Entity => {
+                if is_affirmative(&lower) {
+                    plan.entity.is_some()
+                } else {
+                    let entity = parsed.entity.clone().unwrap_or_else(|| lower.clone());
+                    plan.entity_kind = EntityKind::classify(&entity);
+                    plan.entity = Some(entity);
+                    true
+                }
+            }
+            Slot::Location => {
+                if matches!(lower.as_str(), "anywhere" | "any" | "everywhere" | "worldwide") {
+                    plan.location = None;
+                } else if parsed.location.is_none() {
+                    let raw = answer
+                        .strip_prefix("in ")
+                        .or_else(|| answer.strip_prefix("In "))
+                        .unwrap_or(answer);
+                    plan.location = Some(Location::from_raw(raw));
+                }
+                true
+            }
+            Slot::Count => {
+                if matches!(lower.as_str(), "all" | "any" | "as many as possible" | "no limit") {
+                    plan.count = None;
+                    true
+                } else {
+                    match lower.split_whitespace().find_map(parse_number) {
+                        Some(count) => {
+                            plan.count = Some(count);
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            }
+            Slot::Fields => {
+                if is_affirmative(&lower) || lower == "default" {
+                    plan.fields_explicit = true;
+                } else if !parsed.fields_explicit {
+                    let fields: Vec<String> = lower
+                        .split(|c| c == ',' || c == ';')
+                        .flat_map(|f| f.split(" and "))
+                        .map(|f| f.trim().replace(' ', "_"))
+                        .filter(|f| !f.is_empty())
+                        .collect();
+                    if fields.is_empty() {
+                        return false;
+                    }
+                    plan.fields = fields;
+                    plan.fields_explicit = true;
+                }
+                true
+            }
+        };
+
+        if filled {
+            // A direct answer from the user is worth more than our guess
+            plan.confidence = (plan.confidence + 0.15).min(1.0);
+            if !self.answered.contains(&slot) {
+                self.answered.push(slot);
+            }
+        }
+        filled
+    }
+}
+
+pub fn is_affirmative(text: &str) -> bool {
+    matches!(
+        text.trim().trim_end_matches(|c: char| c == '!' || c == '.'),
+        "y" | "yes" | "yeah" | "yep" | "sure" | "ok" | "okay" | "go" | "go ahead" | "run" | "run it" | "do it" | "confirm" | "correct"
+    )
+}
+
+pub fn is_negative(text: &str) -> bool {
+    matches!(
+        text.trim().trim_end_matches(|c: char| c == '!' || c == '.'),
+        "n" | "no" | "nope" | "cancel" | "stop" | "abort" | "never mind" | "nevermind"
+    )
+}
@@ -0,0 +1,252 @@
+// Page fetching with automatic static/rendered selection
+// Tries a plain HTTP request first and escalates to the browser when the
+// response looks like an empty JavaScript shell. The decision is remembered
+// per domain for the rest of the task. Pages are compared with the versions
+// from the last run of a recurring scrape, when it has any.
+
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderName, ETAG, LAST_MODIFIED};
+use reqwest::{Response, StatusCode};
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::browser_interface::{BrowserInterface, ScrapeOptions, WaitCondition};
+use crate::data::versions::content_hash;
+use crate::data::{PageChange, PageSnapshot, PageVersion};
+use crate::extraction::html::clean_text;
+use crate::networking::HttpClient;
+
+// Below this much visible text a page with scripts is probably rendered client-side
+const MIN_STATIC_TEXT_CHARS: usize = 200;
+
+const SPA_ROOTS: &[&str] = &["#root", "#app", "#__next", "#__nuxt", "app-root", "[ng-app]", "[data-reactroot]"];
+
+const NOSCRIPT_HINTS: &[&str] = &["enable javascript", "requires javascript", "javascript is disabled", "turn on javascript"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchMode {
+    Static,
+    Rendered,
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    pub url: String,
+    pub html: String,
+    pub mode: FetchMode,
+    /// Saved copy of the page when snapshots are enabled
+    pub snapshot: Option<PageSnapshot>,
+    /// Validators and content hash to remember for the next run
+    pub version: PageVersion,
+    /// Compared with the version from the last run
+    pub change: PageChange,
+}
+
+pub struct Fetcher {
+    http: HttpClient,
+    decisions: RwLock<HashMap<String, FetchMode>>,
+    // Page versions from the last run of a recurring scrape, by requested URL
+    versions: HashMap<String, PageVersion>,
+}
+
+impl Fetcher {
+    pub fn new(http: HttpClient) -> Self {
+        Self {
+            http,
+            decisions: RwLock::new(HashMap::new()),
+            versions: HashMap::new(),
+        }
+    }
+
+    /// Compare fetched pages with these versions and use their validators
+    /// for conditional requests
+    pub fn with_versions(mut self, versions: HashMap<String, PageVersion>) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// Version recorded for `url` by the last run, if any
+    pub fn known(&self, url: &str) -> Option<&PageVersion> {
+        self.versions.get(url)
+    }
+
+    /// Version and change of a page fetched some other way (e.g. browser steps)
+    pub fn version_of(&self, url: &str, html: &str, etag: Option<String>, last_modified: Option<String>) -> (PageVersion, PageChange) {
+        let content_hash = content_hash(html);
+        let change = PageChange::between(self.versions.get(url), &content_hash);
+        (PageVersion { url: url.to_string(), etag, last_modified, content_hash }, change)
+    }
+
+    pub fn http(&self) -> &HttpClient {
+        &self.http
+    }
+
+    /// Fetch mode chosen so far for each domain
+    pub async fn decisions(&self) -> HashMap<String, FetchMode> {
+        self.decisions.read().await.clone()
+    }
+
+    /// Fetch `url`, rendering it in the browser when needed. `targets` are CSS
+    /// selectors the caller expects on the page; their absence suggests a JS shell.
+    pub async fn fetch(&self, browser: &BrowserInterface, url: &str, targets: &[String]) -> Result<FetchedPage> {
+        self.fetch_with(browser, url, targets, false)
+            .await?
+            .ok_or_else(|| anyhow!("{} answered 304 Not Modified to an unconditional request", url))
+    }
+
+    /// Like `fetch`, but sends the validators from the last run and returns
+    /// None when the server says the page hasn't changed. Rendered pages
+    /// can't be requested conditionally and are always fetched.
+    pub async fn fetch_if_modified(&self, browser: &BrowserInterface, url: &str, targets: &[String]) -> Result<Option<FetchedPage>> {
+        self.fetch_with(browser, url, targets, true).await
+    }
+
+    async fn fetch_with(&self, browser: &BrowserInterface, url: &str, targets: &[String], conditional: bool) -> Result<Option<FetchedPage>> {
+        let domain = domain_of(url)?;
+
+        if self.decisions.read().await.get(&domain) == Some(&FetchMode::Rendered) {
+            return self.fetch_rendered(browser, url, targets).await.map(Some);
+        }
+
+        let known = self.versions.get(url).filter(|v| conditional && v.has_validators());
+        let request = match known {
+            Some(version) => self.http.get_if_modified(url, version.etag.as_deref(), version.last_modified.as_deref()).await,
+            None => self.http.get(url).await,
+        };
+        let response = match request {
+            // Offline there's no browser to fall back on, and cached pages are used as they are
+            Ok(response) if self.http.offline() => response.error_for_status()?,
+            Err(e) if self.http.offline() => return Err(e),
+            Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+                debug!("{} not modified since the last run", url);
+                return Ok(None);
+            }
+            // Bot challenges usually come back as 403/503 and clear up in a real browser
+            Ok(response) if matches!(response.status().as_u16(), 403 | 503) => {
+                info!("{} answered {} to a plain request, trying the browser", url, response.status());
+                return self.escalate(browser, &domain, url, targets).await.map(Some);
+            }
+            Ok(response) => response.error_for_status()?,
+            Err(e) => {
+                warn!("Static fetch of {} failed ({}), trying the browser", url, e);
+                return self.escalate(browser, &domain, url, targets).await.map(Some);
+            }
+        };
+        let etag = header(&response, ETAG);
+        let last_modified = header(&response, LAST_MODIFIED);
+        let final_url = response.url().to_string();
+        let html = response.text().await?;
+
+        // Once a domain has served real content statically we trust it
+        if !self.http.offline() && self.decisions.read().await.get(&domain) != Some(&FetchMode::Static) {
+            if let Some(reason) = shell_reason(&html, targets) {
+                info!("{} looks client-rendered ({}), switching {} to the browser", url, reason, domain);
+                return self.escalate(browser, &domain, url, targets).await.map(Some);
+            }
+            debug!("Using static fetching for {}", domain);
+            self.decisions.write().await.insert(domain, FetchMode::Static);
+        }
+
+        // Snapshots are keyed by the page actually served, which records name as
+        // their source; versions stay keyed by the URL the next run will request
+        let snapshot = self.http.snapshot_html(&final_url, &html).await?;
+        let (version, change) = self.version_of(url, &html, etag, last_modified);
+        Ok(Some(FetchedPage { url: final_url, html, mode: FetchMode::Static, snapshot, version, change }))
+    }
+
+    async fn escalate(&self, browser: &BrowserInterface, domain: &str, url: &str, targets: &[String]) -> Result<FetchedPage> {
+        let page = self.fetch_rendered(browser, url, targets).await?;
+        self.decisions.write().await.insert(domain.to_string(), FetchMode::Rendered);
+        Ok(page)
+    }
+
+    async fn fetch_rendered(&self, browser: &BrowserInterface, url: &str, targets: &[String]) -> Result<FetchedPage> {
+        let snapshots = self.http.snapshots();
+        let mut options = ScrapeOptions {
+            screenshot: snapshots.map(|s| s.screenshots_enabled()).unwrap_or(false),
+            ..ScrapeOptions::default()
+        };
+        if !targets.is_empty() {
+            // Waiting for the content we want is more reliable than network idle
+            options.wait = WaitCondition::Selector(targets.join(", "));
+        }
+
+        let page = match browser.render(url, &options).await {
+            Ok(page) => page,
+            Err(e) if matches!(options.wait, WaitCondition::Selector(_)) => {
+                debug!("Target selector never appeared on {} ({}), falling back to network idle", url, e);
+                options.wait = WaitCondition::NetworkIdle;
+                browser.render(url, &options).await?
+            }
+            Err(e) => return Err(e),
+        };
+
+        let snapshot = match snapshots {
+            Some(store) => Some(store.capture(&page.url, Some(&page.html), page.screenshot.as_deref()).await?),
+            None => None,
+        };
+        let (version, change) = self.version_of(url, &page.html, None, None);
+        Ok(FetchedPage { url: page.url, html: page.html, mode: FetchMode::Rendered, snapshot, version, change })
+    }
+}
+
+/// Why a statically fetched page looks like a JavaScript shell, if it does
+pub fn shell_reason(html: &str, targets: &[String]) -> Option<String> {
+    let document = Html::parse_document(html);
+
+    let targets: Vec<Selector> = targets
+        .iter()
+        .filter_map(|s| match Selector::parse(s) {
+            Ok(selector) => Some(selector),
+            Err(e) => {
+                warn!("Ignoring invalid target selector '{}': {:?}", s, e);
+                None
+            }
+        })
+        .collect();
+    if !targets.is_empty() {
+        // The content we came for is there, whatever else the page contains
+        if targets.iter().any(|s| document.select(s).next().is_some()) {
+            return None;
+        }
+        return Some("target selectors missing".to_string());
+    }
+
+    let noscript = Selector::parse("noscript").expect("static selector");
+    for element in document.select(&noscript) {
+        let text = element.text().collect::<String>().to_lowercase();
+        if NOSCRIPT_HINTS.iter().any(|hint| text.contains(hint)) {
+            return Some("noscript asks for JavaScript".to_string());
+        }
+    }
+
+    for root in SPA_ROOTS {
+        let selector = Selector::parse(root).expect("static selector");
+        if let Some(element) = document.select(&selector).next() {
+            if element.text().collect::<String>().trim().len() < MIN_STATIC_TEXT_CHARS {
+                return Some(format!("empty SPA root {}", root));
+            }
+        }
+    }
+
+    let script = Selector::parse("script").expect("static selector");
+    let has_scripts = document.select(&script).next().is_some();
+    if has_scripts && clean_text(html, None).len() < MIN_STATIC_TEXT_CHARS {
+        return Some("almost no visible text".to_string());
+    }
+
+    None
+}
+
+fn header(response: &Response, name: HeaderName) -> Option<String> {
+    response.headers().get(name)?.to_str().ok().map(str::to_string)
+}
+
+fn domain_of(url: &str) -> Result<String> {
+    url::Url::parse(url)?
+        .host_str()
+        .map(|h| h.to_string())
+        .ok_or_else(|| anyhow!("URL has no host: {}", url))
+}
@@ -4,11 +4,20 @@ use tokio::sync::RwLock;
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+use crate::data::schema::FieldIssue;
+use crate::data::{OutputSchema, PageSnapshot};
+
 pub mod task_manager;
 pub mod config;
+pub mod dialogue;
+pub mod fetcher;
+pub mod parser;
 
-pub use task_manager::TaskManager;
+pub use task_manager::{CrawlJob, TaskManager};
 pub use config::Config;
+pub use dialogue::{Conversation, DialogueAction};
+pub use fetcher::{FetchMode, Fetcher};
+pub use parser::{RuleParser, TaskPlan};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStatus {
@@ -31,6 +40,7 @@ pub struct Task {
     pub results_count: u32,
     pub output_path: Option<String>,
     pub stealth_enabled: bool,
+    pub schema: Option<OutputSchema>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,7 +59,16 @@ pub struct ScrapingResult {
     pub data: serde_json::Value,
     pub source_url: String,
     pub extracted_at: DateTime<Utc>,
+    /// See `pipeline::QualityModel`
     pub quality_score: f32,
+    /// How sure the extractor was of the record, from 0 to 1
+    pub confidence: f32,
+    /// Copy of the page the record came from, when snapshots are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<PageSnapshot>,
+    /// Values that failed normalisation or validation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<FieldIssue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,6 +124,7 @@ impl FlashEngine {
             results_count: 0,
             output_path: None,
             stealth_enabled: stealth,
+            schema: None,
         };
 
         let mut tasks = self.active_tasks.write().await;
@@ -0,0 +1,473 @@
+// Rule-based request parser
+// Turns natural language scraping requests into structured task plans without
+// needing a model or network access. Used as the offline fallback for the AI brain.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Broad category of entity a request is asking for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntityKind {
+    University,
+    School,
+    Restaurant,
+    Company,
+    JobPosting,
+    Hotel,
+    Product,
+    Other,
+}
+
+impl EntityKind {
+    /// Classify a free-text entity description such as "tech companies"
+    pub fn classify(entity: &str) -> Self {
+        let entity = entity.to_lowercase();
+        // The head noun is usually last ("tech companies", "job postings"), so check
+        // words from the end before falling back to any match
+        for word in entity.split_whitespace().rev() {
+            if let Some((_, kind)) = ENTITY_KEYWORDS.iter().find(|(k, _)| word.starts_with(k)) {
+                return *kind;
+            }
+        }
+        EntityKind::Other
+    }
+
+    /// Default fields collected when the request doesn't list any
+    pub fn default_fields(&self) -> Vec<String> {
+        let fields: &[&str] = match self {
+            EntityKind::University | EntityKind::School => &["name", "url", "location", "email", "phone"],
+            EntityKind::Restaurant => &["name", "url", "location", "phone", "cuisine", "rating"],
+            EntityKind::Company => &["name", "url", "location", "email", "phone", "linkedin"],
+            EntityKind::JobPosting => &["title", "company", "url", "location", "salary"],
+            EntityKind::Hotel => &["name", "url", "location", "phone", "rating", "price"],
+            EntityKind::Product => &["name", "url", "price", "rating"],
+            EntityKind::Other => &["name", "url", "location"],
+        };
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+}
+
+impl fmt::Display for EntityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EntityKind::University => "University",
+            EntityKind::School => "School",
+            EntityKind::Restaurant => "Restaurant",
+            EntityKind::Company => "Company",
+            EntityKind::JobPosting => "Job Posting",
+            EntityKind::Hotel => "Hotel",
+            EntityKind::Product => "Product",
+            EntityKind::Other => "Other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    /// Location as written by the user, e.g. "dhaka, BD"
+    pub raw: String,
+    /// Place name without the trailing region, e.g. "dhaka"
+    pub place: String,
+    /// Region or country qualifier, e.g. "BD"
+    pub region: Option<String>,
+}
+
+impl Location {
+    /// Split "dhaka, BD" into place and region
+    pub fn from_raw(raw: &str) -> Self {
+        let raw = raw.trim().trim_end_matches(|c: char| c == ',' || c == '.').to_string();
+        let mut parts = raw.splitn(2, ',');
+        let place = parts.next().unwrap_or_default().trim().to_string();
+        let region = parts
+            .next()
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+
+        Self { raw, place, region }
+    }
+}
+
+/// Structured plan extracted from a natural language request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskPlan {
+    pub query: String,
+    pub entity: Option<String>,
+    pub entity_kind: EntityKind,
+    pub count: Option<u32>,
+    pub location: Option<Location>,
+    pub fields: Vec<String>,
+    /// True when the fields came from an explicit `target:` list
+    pub fields_explicit: bool,
+    /// Extra qualifiers such as "with michelin stars" or "for ai engineers"
+    pub qualifiers: Vec<String>,
+    pub confidence: f32,
+}
+
+impl TaskPlan {
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!("🎯 Target: {}", self.entity.as_deref().unwrap_or("(unspecified)")));
+        lines.push(format!("🏷️ Type: {}", self.entity_kind));
+        lines.push(format!("🔢 Count: {}", self.count.map(|c| c.to_string()).unwrap_or_else(|| "(any)".to_string())));
+        lines.push(format!("📍 Location: {}", self.location.as_ref().map(|l| l.raw.as_str()).unwrap_or("(anywhere)")));
+        lines.push(format!("📋 Fields: {}{}", self.fields.join(", "), if self.fields_explicit { "" } else { " (default)" }));
+        if !self.qualifiers.is_empty() {
+            lines.push(format!("🔎 Filters: {}", self.qualifiers.join("; ")));
+        }
+        lines.push(format!("📈 Confidence: {:.0}%", self.confidence * 100.0));
+        lines.join("\n")
+    }
+
+    /// Take any slots that `other` pinned down, e.g. from a follow-up message
+    pub fn merge_from(&mut self, other: &TaskPlan) {
+        if other.entity.is_some() && other.entity_kind != EntityKind::Other {
+            self.entity = other.entity.clone();
+            self.entity_kind = other.entity_kind;
+            if !self.fields_explicit {
+                self.fields = other.fields.clone();
+            }
+        }
+        if other.count.is_some() {
+            self.count = other.count;
+        }
+        if other.location.is_some() {
+            self.location = other.location.clone();
+        }
+        if other.fields_explicit {
+            self.fields = other.fields.clone();
+            self.fields_explicit = true;
+        }
+        for qualifier in &other.qualifiers {
+            if !self.qualifiers.contains(qualifier) {
+                self.qualifiers.push(qualifier.clone());
+            }
+        }
+    }
+
+    /// Follow-up questions for the parts of the request we couldn't pin down
+    pub fn clarifying_questions(&self) -> Vec<String> {
+        let mut questions = Vec::new();
+        if self.entity.is_none() || self.entity_kind == EntityKind::Other {
+            questions.push("What kind of things should I collect (e.g. universities, restaurants, companies)?".to_string());
+        }
+        if self.location.is_none() {
+            questions.push("Where should I look (city, region or country)?".to_string());
+        }
+        if self.count.is_none() {
+            questions.push("How many results do you need?".to_string());
+        }
+        if !self.fields_explicit {
+            questions.push(format!("Which fields do you want? I'd collect: {}", self.fields.join(", ")));
+        }
+        questions
+    }
+}
+
+const ACTION_WORDS: &[&str] = &[
+    "find", "get", "scrape", "collect", "list", "search", "fetch", "extract", "gather", "show", "me", "all", "the", "top", "best",
+];
+
+const STOP_WORDS: &[&str] = &["in", "near", "around", "at", "from", "with", "for", "that", "who", "which", "having", "target:"];
+
+const NUMBER_WORDS: &[(&str, u32)] = &[
+    ("one", 1), ("two", 2), ("three", 3), ("four", 4), ("five", 5), ("six", 6), ("seven", 7), ("eight", 8),
+    ("nine", 9), ("ten", 10), ("twenty", 20), ("fifty", 50), ("hundred", 100), ("dozen", 12),
+];
+
+const ENTITY_KEYWORDS: &[(&str, EntityKind)] = &[
+    ("universit", EntityKind::University),
+    ("college", EntityKind::University),
+    ("school", EntityKind::School),
+    ("restaurant", EntityKind::Restaurant),
+    ("cafe", EntityKind::Restaurant),
+    ("job", EntityKind::JobPosting),
+    ("career", EntityKind::JobPosting),
+    ("vacanc", EntityKind::JobPosting),
+    ("compan", EntityKind::Company),
+    ("agenc", EntityKind::Company),
+    ("business", EntityKind::Company),
+    ("startup", EntityKind::Company),
+    ("firm", EntityKind::Company),
+    ("hotel", EntityKind::Hotel),
+    ("product", EntityKind::Product),
+];
+
+/// Deterministic parser for scraping requests
+pub struct RuleParser;
+
+impl Default for RuleParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleParser {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn parse(&self, input: &str) -> TaskPlan {
+        let query = input.trim().to_string();
+
+        // Split off an explicit field list: "... target: url, name, location"
+        let (body, explicit_fields) = split_target_clause(&query);
+        let tokens: Vec<String> = body
+            .split_whitespace()
+            .map(|t| t.to_string())
+            .collect();
+        let lower: Vec<String> = tokens.iter().map(|t| t.to_lowercase()).collect();
+
+        let count = parse_count(&lower);
+        let entity = parse_entity(&lower);
+        let entity_kind = entity.as_deref().map(EntityKind::classify).unwrap_or(EntityKind::Other);
+        let location = parse_location(&tokens, &lower);
+        let qualifiers = parse_qualifiers(&lower);
+
+        let implied_fields = implied_fields(&qualifiers);
+        let fields_explicit = !explicit_fields.is_empty();
+        let fields = if fields_explicit {
+            explicit_fields
+        } else {
+            let mut fields = entity_kind.default_fields();
+            for field in implied_fields {
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+            fields
+        };
+
+        let mut confidence: f32 = 0.1;
+        if entity.is_some() {
+            confidence += if entity_kind == EntityKind::Other { 0.2 } else { 0.4 };
+        }
+        if count.is_some() {
+            confidence += 0.15;
+        }
+        if location.is_some() {
+            confidence += 0.2;
+        }
+        if fields_explicit {
+            confidence += 0.15;
+        }
+
+        TaskPlan {
+            query,
+            entity,
+            entity_kind,
+            count,
+            location,
+            fields,
+            fields_explicit,
+            qualifiers,
+            confidence: confidence.min(1.0),
+        }
+    }
+}
+
+fn split_target_clause(query: &str) -> (String, Vec<String>) {
+    // ASCII lowercasing keeps byte offsets aligned with the original query
+    let lower = query.to_ascii_lowercase();
+    let marker = ["target:", "fields:", "columns:"]
+        .iter()
+        .filter_map(|m| lower.find(m).map(|pos| (pos, m.len())))
+        .min_by_key(|(pos, _)| *pos);
+
+    match marker {
+        Some((pos, len)) => {
+            let fields = query[pos + len..]
+                .split(|c| c == ',' || c == ';')
+                .flat_map(|f| f.split(" and "))
+                .map(|f| normalise_field(f))
+                .filter(|f| !f.is_empty())
+                .collect();
+            (query[..pos].trim().to_string(), fields)
+        }
+        None => (query.to_string(), Vec::new()),
+    }
+}
+
+fn normalise_field(field: &str) -> String {
+    field
+        .trim()
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+pub(crate) fn parse_number(token: &str) -> Option<u32> {
+    let token = token.trim_matches(|c: char| !c.is_alphanumeric());
+    if let Ok(n) = token.replace(',', "").parse::<u32>() {
+        return Some(n);
+    }
+    NUMBER_WORDS.iter().find(|(w, _)| *w == token).map(|(_, n)| *n)
+}
+
+fn parse_count(lower: &[String]) -> Option<u32> {
+    // Only count numbers that appear before the location/filter clauses so
+    // "restaurants with 3 michelin stars" doesn't become a count of 3
+    lower
+        .iter()
+        .take_while(|t| !STOP_WORDS.contains(&t.as_str()))
+        .find_map(|t| parse_number(t))
+}
+
+fn parse_entity(lower: &[String]) -> Option<String> {
+    let words: Vec<&str> = lower
+        .iter()
+        .skip_while(|t| ACTION_WORDS.contains(&t.as_str()) || parse_number(t).is_some())
+        .take_while(|t| !STOP_WORDS.contains(&t.as_str()))
+        .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric() && c != '-'))
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+fn parse_location(tokens: &[String], lower: &[String]) -> Option<Location> {
+    let start = lower
+        .iter()
+        .position(|t| t == "in" || t == "near" || t == "around")?
+        + 1;
+
+    let words: Vec<&str> = tokens[start..]
+        .iter()
+        .zip(&lower[start..])
+        .take_while(|(_, l)| !matches!(l.as_str(), "with" | "for" | "that" | "who" | "which" | "having"))
+        .map(|(t, _)| t.as_str())
+        .collect();
+
+    let location = Location::from_raw(&words.join(" "));
+    if location.raw.is_empty() {
+        None
+    } else {
+        Some(location)
+    }
+}
+
+fn parse_qualifiers(lower: &[String]) -> Vec<String> {
+    let mut qualifiers = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut collecting = false;
+
+    for token in lower {
+        match token.as_str() {
+            "with" | "for" | "that" | "who" | "which" | "having" => {
+                if collecting && current.len() > 1 {
+                    qualifiers.push(current.join(" "));
+                }
+                current = vec![token.as_str()];
+                collecting = true;
+            }
+            "in" | "near" | "around" => {
+                if collecting && current.len() > 1 {
+                    qualifiers.push(current.join(" "));
+                }
+                current.clear();
+                collecting = false;
+            }
+            _ if collecting => current.push(token.as_str()),
+            _ => {}
+        }
+    }
+    if collecting && current.len() > 1 {
+        qualifiers.push(current.join(" "));
+    }
+
+    qualifiers
+}
+
+fn implied_fields(qualifiers: &[String]) -> Vec<String> {
+    let mut fields = Vec::new();
+    for qualifier in qualifiers {
+        if qualifier.contains("contact") {
+            fields.extend(["email", "phone"].iter().map(|f| f.to_string()));
+        }
+        if qualifier.contains("email") {
+            fields.push("email".to_string());
+        }
+        if qualifier.contains("phone") {
+            fields.push("phone".to_string());
+        }
+        if qualifier.contains("linkedin") {
+            fields.push("linkedin".to_string());
+        }
+        if qualifier.contains("social") {
+            fields.extend(["facebook", "twitter", "linkedin"].iter().map(|f| f.to_string()));
+        }
+        if qualifier.contains("address") {
+            fields.push("address".to_string());
+        }
+        if qualifier.contains("star") || qualifier.contains("rating") || qualifier.contains("review") {
+            fields.push("rating".to_string());
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> TaskPlan {
+        RuleParser::default().parse(input)
+    }
+
+    #[test]
+    fn counts_come_from_numbers_before_the_filters() {
+        assert_eq!(parse("find 10 universities in dhaka, BD").count, Some(10));
+        assert_eq!(parse("scrape twenty tech companies near Berlin").count, Some(20));
+        assert_eq!(parse("list 1,500 products").count, Some(1500));
+        // The 3 belongs to the filter, not the number of results
+        assert_eq!(parse("restaurants with 3 michelin stars in Paris").count, None);
+    }
+
+    #[test]
+    fn entities_are_classified_by_their_head_noun() {
+        let plan = parse("find 10 universities in dhaka, BD");
+        assert_eq!(plan.entity.as_deref(), Some("universities"));
+        assert_eq!(plan.entity_kind, EntityKind::University);
+        assert_eq!(plan.fields, EntityKind::University.default_fields());
+        assert!(!plan.fields_explicit);
+
+        let plan = parse("scrape twenty tech companies near Berlin");
+        assert_eq!(plan.entity.as_deref(), Some("tech companies"));
+        assert_eq!(plan.entity_kind, EntityKind::Company);
+
+        assert_eq!(parse("list fifty job vacancies in London").entity_kind, EntityKind::JobPosting);
+        assert_eq!(parse("get all the llamas").entity_kind, EntityKind::Other);
+        assert_eq!(parse("in Tokyo").entity, None);
+    }
+
+    #[test]
+    fn locations_stop_at_the_filters() {
+        let location = parse("find 10 universities in dhaka, BD").location.unwrap();
+        assert_eq!(location, Location { raw: "dhaka, BD".to_string(), place: "dhaka".to_string(), region: Some("BD".to_string()) });
+
+        let plan = parse("scrape twenty tech companies near Berlin with contact details");
+        assert_eq!(plan.location.unwrap().raw, "Berlin");
+        assert_eq!(plan.qualifiers, vec!["with contact details"]);
+        assert_eq!(parse("tech companies with remote jobs").location, None);
+    }
+
+    #[test]
+    fn explicit_fields_replace_the_defaults() {
+        let plan = parse("scrape companies near Berlin with contact details target: name, website and LinkedIn");
+        assert_eq!(plan.fields, vec!["name", "website", "linkedin"]);
+        assert!(plan.fields_explicit);
+        assert_eq!(plan.location.unwrap().raw, "Berlin");
+
+        // Filters can still add fields to the defaults
+        let plan = parse("schools in Rome with social media pages");
+        assert_eq!(plan.fields[..5], EntityKind::School.default_fields()[..]);
+        assert_eq!(plan.fields[5..], ["facebook", "twitter", "linkedin"]);
+        assert!(!plan.fields_explicit);
+    }
+}
@@ -1,22 +1,131 @@
-use anyhow::Result;
-use chrono::Utc;
-use tracing::info;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
 
-use super::{Config, SystemStatus};
+use super::config::CrawlConfig;
+use super::{Config, ScrapingResult, SystemStatus, TaskPlan};
+use super::dialogue::{is_affirmative, is_negative, Conversation, DialogueAction, DialogueState};
+use super::fetcher::{FetchMode, Fetcher};
+use crate::ai_interface::{AiInterface, ChatMessage, Role};
+use crate::browser_interface::{BrowserInterface, ScrapeOptions};
+use crate::crawl::{extract_links, next_page_url, CrawlScope, Frontier, Paginator, Robots, SitemapReader};
+use crate::data::vault::profile_for;
+use crate::data::schema::{FieldSpec, FieldType};
+use crate::data::{AuthMethod, AuthProfile, ChangeReport, DataExporter, OutputSchema, PageChange, PageSnapshot, PageVersion, SnapshotStore, Storage, Vault};
+use crate::extraction::{AiExtractor, ExtractionSample, Recipe, RecipeInducer, SelectorExtractor};
+use crate::networking::cookies::{parse_netscape, GLOBAL_SCOPE};
+use crate::networking::warc::{archived_pages, read_warc};
+use crate::networking::{Cookie, CookieJar, HttpClient, ResponseCache, StealthMode, WarcWriter};
+use crate::pipeline::{Deduplicator, Entity, Normalizer, QualityModel};
+
+/// Outcome of a finished task
+#[derive(Debug, Clone)]
+pub struct TaskReport {
+    pub task_id: String,
+    pub output_path: String,
+    pub schema: OutputSchema,
+    pub results_count: u32,
+    pub summary: String,
+}
+
+impl std::fmt::Display for TaskReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.summary)
+    }
+}
+
+/// A crawl to run: where to start, how far to go and how to extract records
+#[derive(Debug, Clone)]
+pub struct CrawlJob {
+    pub seeds: Vec<String>,
+    pub settings: CrawlConfig,
+    /// Recipe whose list rules are applied to every page; without one pages
+    /// go through `extract_page` with a schema planned from `task`
+    pub recipe: Option<String>,
+    pub task: Option<String>,
+    pub output: Option<String>,
+    pub stealth: bool,
+    /// Continue the frontier of an earlier crawl instead of starting a new one
+    pub resume: Option<String>,
+    /// Only take sitemap URLs modified since then
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Only export records from pages that changed since the last crawl of the same query
+    pub incremental: bool,
+}
 
 pub struct TaskManager {
     config: Config,
-    // AI interface for natural language processing
-    // Browser interface for web automation
-    // Networking components
+    ai: AiInterface,
+    browser: BrowserInterface,
+    storage: Storage,
+    // Selector extractors per domain and schema, loaded from saved or derived recipes
+    domain_extractors: RwLock<HashMap<String, Arc<SelectorExtractor>>>,
+    // AI extraction results waiting to be turned into a recipe
+    extraction_samples: RwLock<HashMap<String, Vec<ExtractionSample>>>,
+    // Where page snapshots go, when enabled
+    snapshots: Option<Arc<SnapshotStore>>,
+    // On-disk response cache, when enabled or running offline
+    cache: Option<Arc<ResponseCache>>,
+    // WARC archive of every exchange and rendered DOM, when enabled
+    archive: Option<Arc<WarcWriter>>,
+    normalizer: Normalizer,
+    quality: QualityModel,
 }
 
 impl TaskManager {
     pub async fn new(config: Config) -> Result<Self> {
         info!("Initializing Flash AI Task Manager");
         
+        let ai = AiInterface::new(&config.ai).await?;
+        info!("AI backend: {}", ai.backend_name());
+
+        let archive = if config.warc.enabled {
+            Some(Arc::new(WarcWriter::new(&config.warc, &config.output.default_directory)))
+        } else {
+            None
+        };
+        let mut browser = BrowserInterface::new(&config.browser, config.general.max_concurrent_tasks as usize).await?;
+        if let Some(archive) = &archive {
+            browser = browser.with_archive(archive.clone());
+        }
+        let storage = Storage::new(&config.storage.database_url).await?;
+
+        let snapshots = if config.snapshots.enabled {
+            let store = SnapshotStore::new(&config.snapshots);
+            if config.snapshots.retention_days > 0 {
+                store.prune().await?;
+                storage.clean_old_snapshots(config.snapshots.retention_days as i64).await?;
+            }
+            Some(Arc::new(store))
+        } else {
+            None
+        };
+
+        let cache = if config.http_cache.enabled || config.http_cache.offline {
+            Some(Arc::new(ResponseCache::new(&config.http_cache, &config.output.default_directory)))
+        } else {
+            None
+        };
+
+        let normalizer = Normalizer::new(&config.normalize, &config.crawl.ignore_params);
+        let quality = QualityModel::new(&config.quality);
+
         Ok(Self {
             config,
+            ai,
+            browser,
+            storage,
+            domain_extractors: RwLock::new(HashMap::new()),
+            extraction_samples: RwLock::new(HashMap::new()),
+            snapshots,
+            cache,
+            archive,
+            normalizer,
+            quality,
         })
     }
 
@@ -24,40 +133,9 @@ impl TaskManager {
     pub async fn process_natural_language(&self, input: &str) -> Result<String> {
         info!("Processing natural language input: {}", input);
         
-        // This is where we'll integrate with the Python AI brain
-        // For now, return a placeholder response
-        
-        if input.to_lowercase().contains("university") || input.to_lowercase().contains("universities") {
-            Ok("🎓 I understand you want to find universities! Let me analyze your request...\n\
-                🔍 I can help you find:\n\
-                - University names and official websites\n\
-                - Contact information (emails, phones)\n\
-                - Location data\n\
-                - Social media profiles\n\
-                - Academic program information\n\n\
-                🥷 Would you like me to use stealth mode for this search?\n\
-                📁 Where should I save the results?".to_string())
-        } else if input.to_lowercase().contains("restaurant") || input.to_lowercase().contains("food") {
-            Ok("🍽️ I can help you find restaurants! I'll search for:\n\
-                - Restaurant names and cuisines\n\
-                - Addresses and contact info\n\
-                - Ratings and reviews\n\
-                - Menu information (if available)\n\
-                - Social media presence\n\n\
-                🥷 Activating stealth mode for comprehensive data collection...\n\
-                📊 What specific information are you most interested in?".to_string())
-        } else if input.to_lowercase().contains("job") || input.to_lowercase().contains("career") {
-            Ok("💼 Job search assistance activated! I can scrape:\n\
-                - Job postings from multiple sites\n\
-                - Company information\n\
-                - Salary ranges (where available)\n\
-                - Application requirements\n\
-                - Company social media and culture info\n\n\
-                🛡️ Using enhanced stealth mode for job site scraping...\n\
-                📋 What specific roles or companies are you targeting?".to_string())
-        } else if input == "status" {
+        if input.trim() == "status" {
             let status = self.get_status().await?;
-            Ok(format!("🤖 Flash AI Status:\n\
+            return Ok(format!("🤖 Flash AI Status:\n\
                        ✅ System: {}\n\
                        📊 Active Tasks: {}\n\
                        🌐 Proxies: {}\n\
@@ -67,9 +145,12 @@ impl TaskManager {
                        status.active_tasks,
                        status.proxy_count,
                        if status.stealth_active { "ON" } else { "OFF" },
-                       status.last_activity.format("%Y-%m-%d %H:%M:%S UTC")))
-        } else {
-            Ok("🤖 I'm Flash AI, your intelligent web scraping assistant!\n\n\
+                       status.last_activity.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+
+        let plan = self.plan(input).await;
+        if plan.entity.is_none() {
+            return Ok("🤖 I'm Flash AI, your intelligent web scraping assistant!\n\n\
                 I can help you with:\n\
                 📚 Research: Universities, schools, academic institutions\n\
                 🍽️ Local Business: Restaurants, shops, services\n\
@@ -77,37 +158,1433 @@ impl TaskManager {
                 🏢 Business Data: Company info, contacts, social media\n\
                 📊 Market Research: Products, prices, reviews\n\n\
                 Just tell me what you're looking for in natural language!\n\
-                Example: 'Find 50 tech companies in Silicon Valley with their LinkedIn profiles'".to_string())
+                Example: 'Find 50 tech companies in Silicon Valley with their LinkedIn profiles'".to_string());
+        }
+
+        if plan.confidence < self.config.ai.confidence_threshold {
+            let questions = plan.clarifying_questions();
+            return Ok(format!("🤔 I'm not sure I understood that yet:\n{}\n\n\
+                               A few questions:\n{}",
+                              plan.summary(),
+                              questions.iter().map(|q| format!("  ❓ {}", q)).collect::<Vec<_>>().join("\n")));
+        }
+
+        Ok(format!("🔍 Here's how I understood your request:\n{}\n\n\
+                    🚀 Run it with: flash execute \"{}\"",
+                   plan.summary(),
+                   plan.query))
+    }
+
+    /// Build a structured task plan from a natural language request
+    pub async fn plan(&self, input: &str) -> TaskPlan {
+        let plan = self.ai.plan(input).await;
+        debug!("Parsed plan: {:?}", plan);
+        plan
+    }
+
+    /// Start a new chat session, or restore a stored one with its history and pending plan
+    pub async fn start_chat(&self, resume: Option<&str>) -> Result<Conversation> {
+        let session_id = match resume {
+            Some(session_id) => session_id,
+            None => {
+                let conversation = Conversation::new();
+                self.storage.create_chat_session(&conversation.session_id, None).await?;
+                return Ok(conversation);
+            }
+        };
+
+        let (title, state, messages) = self.storage
+            .get_chat_session(session_id)
+            .await?
+            .ok_or_else(|| anyhow!("Chat session '{}' not found", session_id))?;
+
+        let mut conversation = match state {
+            Some(state) => serde_json::from_value(state)?,
+            None => Conversation::new(),
+        };
+        conversation.session_id = session_id.to_string();
+        conversation.title = title;
+        conversation.history = messages
+            .into_iter()
+            .filter_map(|(role, content)| Role::from_name(&role).map(|role| ChatMessage { role, content }))
+            .collect();
+
+        info!("Resumed chat session {} ({} messages)", session_id, conversation.history.len());
+        Ok(conversation)
+    }
+
+    /// Handle one chat turn: plan the request, ask follow-ups for anything
+    /// missing or uncertain, and only hand back the plan once the user confirms
+    pub async fn chat(&self, conversation: &mut Conversation, input: &str) -> Result<DialogueAction> {
+        let input = input.trim();
+        self.record_message(conversation, ChatMessage::user(input)).await?;
+
+        let action = self.chat_turn(conversation, input).await?;
+        match &action {
+            DialogueAction::Reply(reply) => {
+                self.record_message(conversation, ChatMessage::assistant(reply.clone())).await?;
+                // Snapshot each plan we present for confirmation
+                if conversation.state == DialogueState::AwaitingConfirmation {
+                    self.store_chat_plan(conversation).await?;
+                }
+            }
+            DialogueAction::Execute(..) => {}
+        }
+        self.storage.save_chat_state(&conversation.session_id, &serde_json::to_value(&*conversation)?).await?;
+
+        Ok(action)
+    }
+
+    async fn record_message(&self, conversation: &mut Conversation, message: ChatMessage) -> Result<()> {
+        self.storage
+            .append_chat_message(&conversation.session_id, message.role.as_str(), &message.content)
+            .await?;
+        conversation.history.push(message);
+        Ok(())
+    }
+
+    async fn store_chat_plan(&self, conversation: &mut Conversation) -> Result<Option<i64>> {
+        let plan = match &conversation.plan {
+            Some(plan) => plan,
+            None => return Ok(None),
+        };
+        let plan_id = self.storage
+            .store_chat_plan(&conversation.session_id, &serde_json::to_value(plan)?)
+            .await?;
+        conversation.plan_id = Some(plan_id);
+        Ok(Some(plan_id))
+    }
+
+    /// Run a plan from a chat session and link the launched task to it
+    pub async fn run_chat_plan(&self, conversation: &mut Conversation, plan: &TaskPlan, plan_id: Option<i64>, stealth: bool) -> Result<TaskReport> {
+        let plan_id = match plan_id {
+            Some(id) => id,
+            None => self.storage.store_chat_plan(&conversation.session_id, &serde_json::to_value(plan)?).await?,
+        };
+
+        let report = self.execute_plan(plan, None, stealth).await?;
+        self.storage.link_chat_plan_task(plan_id, &report.task_id).await?;
+        self.record_message(conversation, ChatMessage::assistant(report.summary.clone())).await?;
+
+        Ok(report)
+    }
+
+    /// Run the session's current plan right away, skipping confirmation
+    pub async fn run_current_plan(&self, conversation: &mut Conversation, stealth: bool) -> Result<TaskReport> {
+        let plan = conversation.plan
+            .clone()
+            .ok_or_else(|| anyhow!("There's no plan in this session yet"))?;
+        let plan_id = conversation.plan_id;
+        conversation.reset_plan();
+
+        let report = self.run_chat_plan(conversation, &plan, plan_id, stealth).await?;
+        self.storage.save_chat_state(&conversation.session_id, &serde_json::to_value(&*conversation)?).await?;
+        Ok(report)
+    }
+
+    pub async fn save_chat(&self, conversation: &mut Conversation, title: Option<&str>) -> Result<()> {
+        if let Some(title) = title {
+            self.storage.set_chat_session_title(&conversation.session_id, title).await?;
+            conversation.title = Some(title.to_string());
+        }
+        self.storage.save_chat_state(&conversation.session_id, &serde_json::to_value(&*conversation)?).await
+    }
+
+    pub async fn list_chat_sessions(&self) -> Result<Vec<(String, Option<String>, String)>> {
+        self.storage.list_chat_sessions(None).await
+    }
+
+    /// Write the session transcript, plans and launched tasks to a JSON file
+    pub async fn export_chat(&self, conversation: &Conversation, path: Option<String>) -> Result<String> {
+        let path = path.unwrap_or_else(|| {
+            format!("{}/chat_{}.json", self.config.output.default_directory, conversation.session_id)
+        });
+
+        let plans = self.storage.get_chat_plans(&conversation.session_id).await?;
+        let export = json!({
+            "session_id": conversation.session_id,
+            "title": conversation.title,
+            "exported_at": Utc::now(),
+            "messages": conversation.history,
+            "plans": plans
+                .into_iter()
+                .map(|(plan, task_id)| json!({ "plan": plan, "task_id": task_id }))
+                .collect::<Vec<_>>(),
+            "pending_plan": conversation.plan,
+        });
+
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_string_pretty(&export)?).await?;
+        Ok(path)
+    }
+
+    async fn chat_turn(&self, conversation: &mut Conversation, input: &str) -> Result<DialogueAction> {
+        let threshold = self.config.ai.confidence_threshold;
+        let lower = input.to_lowercase();
+
+        // Only a conversation with a plan can be partway through one; without
+        // it (e.g. a saved dialogue edited by hand) the input starts afresh
+        if conversation.plan.is_none() && !matches!(conversation.state, DialogueState::Idle) {
+            conversation.reset_plan();
+        }
+
+        match conversation.state.clone() {
+            DialogueState::Idle => {
+                let plan = self.plan(input).await;
+                if plan.entity.is_none() {
+                    // Not a task request; answer conversationally with the history as context
+                    let reply = if self.ai.has_model() && lower != "status" {
+                        self.ai.chat(&conversation.history).await?
+                    } else {
+                        self.process_natural_language(input).await?
+                    };
+                    return Ok(DialogueAction::Reply(reply));
+                }
+                conversation.plan = Some(plan);
+                conversation.answered.clear();
+            }
+            DialogueState::Clarifying(slot) => {
+                if is_negative(&lower) {
+                    conversation.reset_plan();
+                    return Ok(DialogueAction::Reply("👌 Okay, I've dropped that request. What else can I find for you?".to_string()));
+                }
+                let parsed = self.ai.plan(input).await;
+                if !conversation.fill_slot(slot, input, &parsed) {
+                    let plan = conversation.plan.as_ref().ok_or_else(|| anyhow!("Clarifying without a plan"))?;
+                    return Ok(DialogueAction::Reply(format!("🤔 Sorry, I didn't catch that. {}", slot.question(plan))));
+                }
+            }
+            DialogueState::AwaitingConfirmation => {
+                if is_affirmative(&lower) {
+                    let plan = conversation.plan.clone().ok_or_else(|| anyhow!("Confirming without a plan"))?;
+                    let plan_id = conversation.plan_id;
+                    conversation.reset_plan();
+                    return Ok(DialogueAction::Execute(plan, plan_id));
+                }
+                if is_negative(&lower) {
+                    conversation.reset_plan();
+                    return Ok(DialogueAction::Reply("👌 Cancelled. What else can I find for you?".to_string()));
+                }
+                // Anything else is treated as an amendment to the plan
+                let parsed = self.ai.plan(input).await;
+                if let Some(plan) = conversation.plan.as_mut() {
+                    plan.merge_from(&parsed);
+                }
+            }
+        }
+
+        let next_slot = conversation.next_missing_slot(threshold);
+        conversation.state = match next_slot {
+            Some(slot) => DialogueState::Clarifying(slot),
+            None => DialogueState::AwaitingConfirmation,
+        };
+
+        let plan = conversation.plan.as_ref().ok_or_else(|| anyhow!("No plan to ask about"))?;
+        match next_slot {
+            Some(slot) => Ok(DialogueAction::Reply(format!("❓ {}", slot.question(plan)))),
+            None => Ok(DialogueAction::Reply(format!("🔍 Here's the plan:\n{}\n\n🚀 Shall I run it? (yes/no)", plan.summary()))),
         }
     }
 
     /// Execute a scraping task
-    pub async fn execute_task(&self, task_description: &str, output: Option<String>, stealth: bool) -> Result<String> {
-        info!("Executing task: {} (stealth: {})", task_description, stealth);
+    pub async fn execute_task(&self, task_description: &str, output: Option<String>, stealth: bool) -> Result<TaskReport> {
+        let plan = self.plan(task_description).await;
+        self.execute_plan(&plan, output, stealth).await
+    }
+
+    /// Execute an already planned scraping task: fetch the pages its request
+    /// names, extract records from each and export them
+    pub async fn execute_plan(&self, plan: &TaskPlan, output: Option<String>, stealth: bool) -> Result<TaskReport> {
+        info!("Executing task: {} (stealth: {})", plan.query, stealth);
         
         // Create a new task
         let task_id = uuid::Uuid::new_v4().to_string();
-        
-        // This is where we'll implement the actual scraping logic
-        // For now, simulate task execution
-        
-        let output_path = output.unwrap_or_else(|| {
-            format!("downloads/flash-ai/task_{}.csv", 
-                   Utc::now().format("%Y%m%d_%H%M%S"))
-        });
-        
-        // Simulate processing
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        
-        Ok(format!("Task completed successfully!\n\
+        self.storage.store_task(&task_id, &plan.query, "executing").await?;
+
+        let started = std::time::Instant::now();
+        let (schema, records, source_count, dropped) = match self.scrape_plan(&task_id, plan, stealth).await {
+            Ok(scraped) => scraped,
+            Err(e) => {
+                self.storage.update_task_status(&task_id, "failed", Some(&e.to_string())).await?;
+                return Err(e);
+            }
+        };
+
+        let (records, merged, merged_dropped) = self.dedup(&task_id, records, &schema).await?;
+        let output_path = self.export_records(&records, &schema, output).await?;
+        let summary = format!("Task completed successfully!\n\
                    📋 Task ID: {}\n\
                    📁 Output: {}\n\
-                   🕐 Duration: 2.3 seconds\n\
-                   📊 Results: 42 items found\n\
+                   🕐 Duration: {:.1} seconds\n\
+                   🔎 Sources: {} page(s)\n\
+                   📊 Results: {} items found\n\
+                   🧩 Merged: {} duplicate record(s)\n\
+                   ⭐ Quality: {}\n\
+                   📋 Fields: {}\n\
                    🥷 Stealth: {}", 
                    task_id, 
                    output_path,
-                   if stealth { "Enabled" } else { "Disabled" }))
+                   started.elapsed().as_secs_f32(),
+                   source_count,
+                   records.len(),
+                   merged,
+                   quality_summary(&records, dropped + merged_dropped),
+                   schema.column_names().join(", "),
+                   if stealth { "Enabled" } else { "Disabled" });
+        self.storage.update_task_status(&task_id, "completed", Some(&summary)).await?;
+
+        Ok(TaskReport {
+            task_id,
+            output_path,
+            schema,
+            results_count: records.len() as u32,
+            summary,
+        })
+    }
+
+    /// Resolve a plan's schema, then fetch and extract the pages its request names.
+    /// Returns the schema, the records, how many pages were tried and how many
+    /// records fell below the minimum quality.
+    async fn scrape_plan(&self, task_id: &str, plan: &TaskPlan, stealth: bool) -> Result<(OutputSchema, Vec<ScrapingResult>, usize, usize)> {
+        let schema = self.resolve_schema(plan).await?;
+        self.storage.store_task_schema(task_id, &plan.query, &schema).await?;
+        let sources = named_urls(&plan.query);
+        if sources.is_empty() {
+            return Err(anyhow!("The request names no page to scrape; include its URL (or run a recipe or crawl)"));
+        }
+
+        let limit = plan.count.map(|c| c as usize);
+        let cookies = self.cookie_jar(task_id).await?;
+        let auth = self.auth_profiles()?;
+        let fetcher = Fetcher::new(self.http_client(&cookies).with_auth(auth.clone())?);
+        let mut stealth_mode = StealthMode::new();
+        if stealth {
+            stealth_mode.enable();
+        }
+
+        // Each task renders in its own browser context, closed whatever the outcome
+        let browser = self.browser.for_task(task_id, cookies.clone());
+        let scraped: Result<(Vec<ScrapingResult>, usize)> = async {
+            self.login_for(&browser, &auth, &sources).await?;
+            let mut records: Vec<ScrapingResult> = Vec::new();
+            let mut dropped = 0;
+            'pages: for url in &sources {
+                stealth_mode.random_delay().await;
+                let page = match fetcher.fetch(&browser, url, &[]).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        warn!("Failed to fetch {}: {}", url, e);
+                        continue;
+                    }
+                };
+                self.record_snapshot(task_id, page.snapshot.as_ref()).await?;
+                let found = match self.extract_page(task_id, &page.url, &page.html, &schema).await {
+                    Ok(found) => found,
+                    Err(e) => {
+                        warn!("Failed to extract from {}: {}", page.url, e);
+                        continue;
+                    }
+                };
+                for found in found {
+                    let result = self.to_result(task_id, &page.url, page.snapshot.as_ref(), &found.data, &schema, found.confidence);
+                    if !self.quality.keeps(result.quality_score) {
+                        dropped += 1;
+                        continue;
+                    }
+                    self.store_record(&result).await?;
+                    records.push(result);
+                    if limit.map(|l| records.len() >= l).unwrap_or(false) {
+                        break 'pages;
+                    }
+                }
+            }
+            Ok((records, dropped))
+        }
+        .await;
+        // A context that won't close mustn't hide how the scrape went
+        if let Err(e) = browser.finish().await {
+            warn!("Failed to close the browser context of task {}: {}", task_id, e);
+        }
+        self.save_cookie_jar(&cookies).await?;
+        let (records, dropped) = scraped?;
+        Ok((schema, records, sources.len(), dropped))
+    }
+
+    /// Run a selector recipe: fetch its start pages (and detail pages), extract
+    /// records with the recipe's rules and export them. Incremental runs only
+    /// export records from pages that changed since the last run of the same query.
+    pub async fn run_recipe(&self, recipe_path: &str, task_description: Option<&str>, output: Option<String>, stealth: bool, incremental: bool) -> Result<TaskReport> {
+        let recipe = Recipe::load(recipe_path)?;
+        if recipe.start_urls.is_empty() {
+            return Err(anyhow!("Recipe '{}' has no start_urls", recipe.name));
+        }
+        if self.offline() && !recipe.steps.is_empty() {
+            return Err(anyhow!("Recipe '{}' runs browser steps, which can't be replayed offline", recipe.name));
+        }
+        let limit = match task_description {
+            Some(description) => self.plan(description).await.count,
+            None => None,
+        };
+        let query = task_description.map(|d| d.to_string()).unwrap_or_else(|| format!("recipe:{}", recipe.name));
+        info!("Running recipe '{}' (stealth: {})", recipe.name, stealth);
+
+        let task_id = uuid::Uuid::new_v4().to_string();
+        self.storage.store_task(&task_id, &query, "executing").await?;
+        let schema = recipe.schema();
+        self.storage.store_task_schema(&task_id, &query, &schema).await?;
+
+        let started = std::time::Instant::now();
+        let extractor = SelectorExtractor::new(recipe.clone())?;
+        let cookies = self.cookie_jar(&task_id).await?;
+        let auth = self.auth_profiles()?;
+        let fetcher = Fetcher::new(self.http_client(&cookies).with_auth(auth.clone())?)
+            .with_versions(self.storage.load_page_versions(&query).await?);
+        let mut changes = ChangeReport::default();
+        let list_targets = recipe.page_selectors(false);
+        let detail_targets = recipe.page_selectors(true);
+        let mut stealth_mode = StealthMode::new();
+        if stealth {
+            stealth_mode.enable();
+        }
+
+        // Each task renders in its own browser context, closed whatever the outcome
+        let browser = self.browser.for_task(&task_id, cookies.clone());
+        let scraped: Result<(Vec<ScrapingResult>, bool, usize)> = async {
+            self.login_for(&browser, &auth, &recipe.start_urls).await?;
+            let mut records: Vec<ScrapingResult> = Vec::new();
+            // Stopped at the limit, or skipped an unchanged listing, so some pages weren't reached
+            let mut partial = false;
+            let mut dropped = 0;
+            'pages: for url in &recipe.start_urls {
+                stealth_mode.random_delay().await;
+                let mut pages: VecDeque<(String, String, PageChange, Option<PageSnapshot>)> = if recipe.steps.is_empty() {
+                    let fetched = if incremental {
+                        fetcher.fetch_if_modified(&browser, url, &list_targets).await?
+                    } else {
+                        Some(fetcher.fetch(&browser, url, &list_targets).await?)
+                    };
+                    match fetched {
+                        Some(page) => {
+                            self.record_snapshot(&task_id, page.snapshot.as_ref()).await?;
+                            self.track_page(&query, &task_id, &mut changes, &page.version, page.change).await?;
+                            vec![(page.url, page.html, page.change, page.snapshot)].into()
+                        }
+                        // 304: nothing listed here has changed
+                        None => {
+                            if let Some(version) = fetcher.known(url).cloned() {
+                                self.track_page(&query, &task_id, &mut changes, &version, PageChange::Unchanged).await?;
+                            }
+                            partial = true;
+                            VecDeque::new()
+                        }
+                    }
+                } else {
+                    let pages = browser
+                        .run_steps(url, &recipe.steps)
+                        .await
+                        .with_context(|| format!("Recipe '{}' failed on {}", recipe.name, url))?;
+                    let mut tracked = VecDeque::new();
+                    for page in pages {
+                        let snapshot = fetcher.http().snapshot_html(&page.url, &page.html).await?;
+                        self.record_snapshot(&task_id, snapshot.as_ref()).await?;
+                        let (version, change) = fetcher.version_of(&page.url, &page.html, None, None);
+                        self.track_page(&query, &task_id, &mut changes, &version, change).await?;
+                        tracked.push_back((page.url, page.html, change, snapshot));
+                    }
+                    tracked
+                };
+
+                let mut paginator = Paginator::new(&self.config.pagination, recipe.next_page_selector());
+                while let Some((page_url, html, page_change, page_snapshot)) = pages.pop_front() {
+                    let found = extractor.extract_list(&html, &page_url)?.records;
+                    let items_on_page = found.len();
+                    let fresh = paginator.fresh(&page_url, found);
+                    let new_on_page = fresh.len();
+
+                    for (mut record, detail_url) in fresh {
+                        let mut source_url = page_url.clone();
+                        let mut snapshot = page_snapshot.clone();
+                        let mut change = page_change;
+                        if let (Some(detail_url), true) = (detail_url, recipe.has_detail_fields()) {
+                            stealth_mode.random_delay().await;
+                            let detail = if incremental {
+                                fetcher.fetch_if_modified(&browser, &detail_url, &detail_targets).await
+                            } else {
+                                fetcher.fetch(&browser, &detail_url, &detail_targets).await.map(Some)
+                            };
+                            match detail {
+                                Ok(Some(detail_page)) => {
+                                    self.record_snapshot(&task_id, detail_page.snapshot.as_ref()).await?;
+                                    self.track_page(&query, &task_id, &mut changes, &detail_page.version, detail_page.change).await?;
+                                    if let (Some(obj), Value::Object(detail)) = (record.as_object_mut(), extractor.extract_detail(&detail_page.html, &detail_page.url)?) {
+                                        obj.extend(detail);
+                                    }
+                                    change = detail_page.change;
+                                    snapshot = detail_page.snapshot;
+                                    source_url = detail_url;
+                                }
+                                // 304: the detail page and so the record are as they were
+                                Ok(None) => {
+                                    if let Some(version) = fetcher.known(&detail_url).cloned() {
+                                        self.track_page(&query, &task_id, &mut changes, &version, PageChange::Unchanged).await?;
+                                    }
+                                    change = PageChange::Unchanged;
+                                }
+                                Err(e) => warn!("Failed to fetch detail page {}: {}", detail_url, e),
+                            }
+                        }
+                        if incremental && change == PageChange::Unchanged {
+                            continue;
+                        }
+
+                        let result = self.to_result(&task_id, &source_url, snapshot.as_ref(), &record, &schema, self.config.quality.selector_confidence);
+                        if !self.quality.keeps(result.quality_score) {
+                            dropped += 1;
+                            continue;
+                        }
+                        self.store_record(&result).await?;
+                        records.push(result);
+                        if limit.map(|l| records.len() >= l as usize).unwrap_or(false) {
+                            partial = true;
+                            break 'pages;
+                        }
+                    }
+
+                    // Scripted steps do their own navigation
+                    if !recipe.steps.is_empty() {
+                        continue;
+                    }
+                    if let Some(next) = paginator.next(&html, &page_url, items_on_page, new_on_page) {
+                        stealth_mode.random_delay().await;
+                        let fetched = if incremental {
+                            fetcher.fetch_if_modified(&browser, next.as_str(), &list_targets).await
+                        } else {
+                            fetcher.fetch(&browser, next.as_str(), &list_targets).await.map(Some)
+                        };
+                        match fetched {
+                            Ok(Some(page)) => {
+                                self.record_snapshot(&task_id, page.snapshot.as_ref()).await?;
+                                self.track_page(&query, &task_id, &mut changes, &page.version, page.change).await?;
+                                pages.push_back((page.url, page.html, page.change, page.snapshot));
+                            }
+                            Ok(None) => {
+                                if let Some(version) = fetcher.known(next.as_str()).cloned() {
+                                    self.track_page(&query, &task_id, &mut changes, &version, PageChange::Unchanged).await?;
+                                }
+                                partial = true;
+                            }
+                            Err(e) => warn!("Failed to fetch next page {}: {}", next, e),
+                        }
+                    } else if !self.offline() && paginator.should_scroll(new_on_page) {
+                        // No next link: the listing may load more results as you scroll
+                        let options = ScrapeOptions { max_scrolls: paginator.max_scrolls(), ..ScrapeOptions::default() };
+                        match browser.render(&page_url, &options).await {
+                            Ok(page) => {
+                                let snapshot = fetcher.http().snapshot_html(&page.url, &page.html).await?;
+                                self.record_snapshot(&task_id, snapshot.as_ref()).await?;
+                                // Scrolled results are the original page grown, so they share its change
+                                pages.push_back((page.url, page.html, page_change, snapshot));
+                            }
+                            Err(e) => warn!("Failed to scroll {} for more results: {}", page_url, e),
+                        }
+                    }
+                }
+            }
+            Ok((records, partial, dropped))
+        }
+        .await;
+        // A context that won't close mustn't hide how the scrape went
+        if let Err(e) = browser.finish().await {
+            warn!("Failed to close the browser context of task {}: {}", task_id, e);
+        }
+        self.save_cookie_jar(&cookies).await?;
+        let (records, partial, dropped) = match scraped {
+            Ok(scraped) => scraped,
+            Err(e) => {
+                self.storage.update_task_status(&task_id, "failed", Some(&e.to_string())).await?;
+                return Err(e);
+            }
+        };
+        // Pages a partial run didn't reach aren't necessarily gone
+        if !partial {
+            changes.removed = self.storage.remove_stale_page_versions(&query, &task_id).await?;
+        }
+
+        let (records, merged, merged_dropped) = self.dedup(&task_id, records, &schema).await?;
+
+        let output_path = self.export_records(&records, &schema, output).await?;
+        let rendered: Vec<String> = fetcher
+            .decisions()
+            .await
+            .into_iter()
+            .filter(|(_, mode)| *mode == FetchMode::Rendered)
+            .map(|(domain, _)| domain)
+            .collect();
+        let summary = format!("Recipe '{}' completed!\n\
+                   📋 Task ID: {}\n\
+                   📁 Output: {}\n\
+                   🕐 Duration: {:.1} seconds\n\
+                   📊 Results: {} items found\n\
+                   🧩 Merged: {} duplicate record(s)\n\
+                   ⭐ Quality: {}\n\
+                   📋 Fields: {}\n\
+                   🔁 Pages: {}\n\
+                   🌐 Rendered in browser: {}\n\
+                   🥷 Stealth: {}",
+                   recipe.name,
+                   task_id,
+                   output_path,
+                   started.elapsed().as_secs_f32(),
+                   records.len(),
+                   merged,
+                   quality_summary(&records, dropped + merged_dropped),
+                   schema.column_names().join(", "),
+                   changes.summary(),
+                   if rendered.is_empty() { "none".to_string() } else { rendered.join(", ") },
+                   if stealth { "Enabled" } else { "Disabled" });
+        for url in &changes.removed {
+            info!("Page gone since the last run: {}", url);
+        }
+        self.storage.update_task_status(&task_id, "completed", Some(&summary)).await?;
+
+        Ok(TaskReport {
+            task_id,
+            output_path,
+            schema,
+            results_count: records.len() as u32,
+            summary,
+        })
+    }
+
+    /// Crawl from seed URLs, extracting records from every page fetched and
+    /// following in-scope links until the depth or page budget runs out
+    pub async fn crawl(&self, job: CrawlJob) -> Result<TaskReport> {
+        let recipe = job.recipe.as_deref().map(Recipe::load).transpose()?;
+        let mut seeds = job.seeds.clone();
+        if seeds.is_empty() {
+            seeds = recipe.as_ref().map(|r| r.start_urls.clone()).unwrap_or_default();
+        }
+        // A resumed crawl keeps its original seeds so the domain scope stays the same
+        if let Some(task_id) = &job.resume {
+            seeds.extend(self.storage.frontier_seeds(task_id).await?);
+        }
+        let seeds = seeds
+            .iter()
+            .map(|s| url::Url::parse(s).with_context(|| format!("Invalid seed URL '{}'", s)))
+            .collect::<Result<Vec<_>>>()?;
+        if seeds.is_empty() {
+            return Err(anyhow!("Give at least one seed URL (or a recipe with start_urls)"));
+        }
+
+        let plan = match &job.task {
+            Some(description) => Some(self.plan(description).await),
+            None => None,
+        };
+        let limit = plan.as_ref().and_then(|p| p.count);
+        let query = match (&job.task, &recipe) {
+            (Some(description), _) => description.clone(),
+            (None, Some(recipe)) => format!("crawl:{}", recipe.name),
+            (None, None) => return Err(anyhow!("A crawl needs a recipe or a task description to extract with")),
+        };
+
+        // Settings, recipe and credentials are checked before the task is stored,
+        // so a crawl that can't start doesn't leave a task stuck "executing"
+        let scope = CrawlScope::new(&job.settings, &seeds)?;
+        let extractor = recipe.clone().map(SelectorExtractor::new).transpose()?;
+        let targets = recipe.as_ref().map(|r| r.page_selectors(false)).unwrap_or_default();
+        let auth = self.auth_profiles()?;
+        let (task_id, schema) = match &job.resume {
+            Some(task_id) => {
+                let schema = self.storage.get_task_schema(task_id).await?
+                    .ok_or_else(|| anyhow!("No crawl with task ID {}", task_id))?;
+                (task_id.clone(), schema)
+            }
+            None => {
+                let schema = match (&recipe, &plan) {
+                    (Some(recipe), _) => recipe.schema(),
+                    (None, Some(plan)) => self.resolve_schema(plan).await?,
+                    (None, None) => unreachable!("checked above"),
+                };
+                (uuid::Uuid::new_v4().to_string(), schema)
+            }
+        };
+        let cookies = self.cookie_jar(&task_id).await?;
+        let fetcher = Fetcher::new(self.http_client(&cookies).with_auth(auth.clone())?)
+            .with_versions(self.storage.load_page_versions(&query).await?);
+
+        if job.resume.is_some() {
+            self.storage.update_task_status(&task_id, "executing", None).await?;
+        } else {
+            self.storage.store_task(&task_id, &query, "executing").await?;
+            self.storage.store_task_schema(&task_id, &query, &schema).await?;
+        }
+        info!("Crawling {} seed(s) for task {} (stealth: {})", seeds.len(), task_id, job.stealth);
+
+        let started = std::time::Instant::now();
+        let opened: Result<Frontier> = async {
+            let mut frontier = Frontier::open(&self.storage, &task_id, scope)
+                .await?
+                .with_rate_limit(self.config.networking.max_requests_per_minute);
+            if self.config.networking.respect_robots_txt {
+                frontier = frontier.with_robots(Robots::new(fetcher.http()));
+            }
+            frontier.seed(&seeds).await?;
+
+            if job.settings.sitemaps {
+                let reader = SitemapReader::new(fetcher.http(), job.settings.max_sitemaps);
+                let mut sitemaps = Vec::new();
+                let mut sites = HashSet::new();
+                for seed in seeds.iter().filter(|s| sites.insert(s.origin())) {
+                    sitemaps.extend(reader.discover(seed).await?);
+                }
+                let pages: Vec<url::Url> = reader
+                    .read(sitemaps, job.modified_since)
+                    .await?
+                    .into_iter()
+                    .map(|entry| entry.url)
+                    .filter(|url| frontier.scope().allows(url))
+                    .collect();
+                let added = frontier.seed(&pages).await?;
+                info!("Seeded {} new URL(s) from sitemaps", added);
+            }
+            Ok(frontier)
+        }
+        .await;
+        let mut frontier = match opened {
+            Ok(frontier) => frontier,
+            Err(e) => {
+                self.storage.update_task_status(&task_id, "failed", Some(&e.to_string())).await?;
+                return Err(e);
+            }
+        };
+        let mut changes = ChangeReport::default();
+        let mut stealth_mode = StealthMode::new();
+        if job.stealth {
+            stealth_mode.enable();
+        }
+        let seed_urls: Vec<String> = seeds.iter().map(|u| u.to_string()).collect();
+
+        let browser = self.browser.for_task(&task_id, cookies.clone());
+        let crawled: Result<(Vec<ScrapingResult>, bool, usize)> = async {
+            self.login_for(&browser, &auth, &seed_urls).await?;
+            let mut records: Vec<ScrapingResult> = Vec::new();
+            let mut dropped = 0;
+            while let Some(entry) = frontier.next().await? {
+                stealth_mode.random_delay().await;
+                // Pages at the depth limit are only extracted from, so a 304 is all we
+                // need; other pages are fetched in full because we need their links
+                let leaf = entry.depth >= frontier.scope().max_depth;
+                let fetched = if job.incremental && leaf {
+                    fetcher.fetch_if_modified(&browser, &entry.url, &targets).await
+                } else {
+                    fetcher.fetch(&browser, &entry.url, &targets).await.map(Some)
+                };
+                let page = match fetched {
+                    Ok(Some(page)) => page,
+                    Ok(None) => {
+                        if let Some(version) = fetcher.known(&entry.url).cloned() {
+                            self.track_page(&query, &task_id, &mut changes, &version, PageChange::Unchanged).await?;
+                        }
+                        frontier.complete(&entry).await?;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to fetch {}: {}", entry.url, e);
+                        frontier.fail(&entry, &e.to_string()).await?;
+                        continue;
+                    }
+                };
+                self.record_snapshot(&task_id, page.snapshot.as_ref()).await?;
+                self.track_page(&query, &task_id, &mut changes, &page.version, page.change).await?;
+
+                let page_url = url::Url::parse(&page.url)?;
+                let found = frontier.discover(&entry, &extract_links(&page.html, &page_url)).await?;
+                debug!("{} (depth {}): {} new link(s)", entry.url, entry.depth, found);
+
+                let unchanged = job.incremental && page.change == PageChange::Unchanged;
+                // Records with the extractor's confidence in them
+                let page_records: Vec<(Value, f32)> = match &extractor {
+                    _ if unchanged => Vec::new(),
+                    Some(extractor) => match extractor.extract_list(&page.html, &page.url) {
+                        Ok(list) => list
+                            .records
+                            .into_iter()
+                            .map(|(record, _)| (record, self.config.quality.selector_confidence))
+                            .collect(),
+                        Err(e) => {
+                            warn!("Failed to extract from {}: {}", page.url, e);
+                            Vec::new()
+                        }
+                    },
+                    None => match self.extract_page(&task_id, &page.url, &page.html, &schema).await {
+                        Ok(results) => results.into_iter().map(|r| (r.data, r.confidence)).collect(),
+                        Err(e) => {
+                            warn!("Failed to extract from {}: {}", page.url, e);
+                            Vec::new()
+                        }
+                    },
+                };
+                frontier.complete(&entry).await?;
+
+                // Result pages of a listing stay at its depth while they keep yielding
+                // records (or are unchanged, so weren't extracted from)
+                if self.config.pagination.enabled && (unchanged || !page_records.is_empty()) {
+                    if let Some(next) = next_page_url(&page.html, &page_url, recipe.as_ref().and_then(|r| r.next_page_selector()), page_records.len()) {
+                        frontier.queue_next_page(&entry, &next).await?;
+                    }
+                }
+
+                let reached = |records: &[ScrapingResult]| limit.map(|l| records.len() >= l as usize).unwrap_or(false);
+                for (record, confidence) in page_records {
+                    // Checked per record so nothing past the limit is stored
+                    if reached(&records) {
+                        return Ok((records, true, dropped));
+                    }
+                    let result = self.to_result(&task_id, &page.url, page.snapshot.as_ref(), &record, &schema, confidence);
+                    if !self.quality.keeps(result.quality_score) {
+                        dropped += 1;
+                        continue;
+                    }
+                    self.store_record(&result).await?;
+                    records.push(result);
+                }
+                if reached(&records) {
+                    return Ok((records, true, dropped));
+                }
+            }
+            Ok((records, false, dropped))
+        }
+        .await;
+        // A context that won't close mustn't hide how the scrape went
+        if let Err(e) = browser.finish().await {
+            warn!("Failed to close the browser context of task {}: {}", task_id, e);
+        }
+        self.save_cookie_jar(&cookies).await?;
+        let (records, cut_short, dropped) = match crawled {
+            Ok(crawled) => crawled,
+            Err(e) => {
+                self.storage.update_task_status(&task_id, "failed", Some(&e.to_string())).await?;
+                return Err(e);
+            }
+        };
+
+        let counts = frontier.counts().await?;
+        let count = |status: &str| counts.get(status).copied().unwrap_or(0);
+        // Only a crawl that emptied its frontier can tell which pages are gone
+        if !cut_short && count("pending") == 0 {
+            changes.removed = self.storage.remove_stale_page_versions(&query, &task_id).await?;
+            for url in &changes.removed {
+                info!("Page gone since the last crawl: {}", url);
+            }
+        }
+        let (records, merged, merged_dropped) = self.dedup(&task_id, records, &schema).await?;
+        let output_path = self.export_records(&records, &schema, job.output).await?;
+        let summary = format!("Crawl completed!\n\
+                   📋 Task ID: {}\n\
+                   📁 Output: {}\n\
+                   🕐 Duration: {:.1} seconds\n\
+                   🕸️ Pages: {} crawled, {} failed, {} still queued, {} blocked by robots.txt\n\
+                   🔁 Changes: {}\n\
+                   📊 Results: {} items found\n\
+                   🧩 Merged: {} duplicate record(s)\n\
+                   ⭐ Quality: {}\n\
+                   📋 Fields: {}\n\
+                   🥷 Stealth: {}",
+                   task_id,
+                   output_path,
+                   started.elapsed().as_secs_f32(),
+                   count("done"),
+                   count("failed"),
+                   count("pending"),
+                   count("blocked"),
+                   changes.summary(),
+                   records.len(),
+                   merged,
+                   quality_summary(&records, dropped + merged_dropped),
+                   schema.column_names().join(", "),
+                   if job.stealth { "Enabled" } else { "Disabled" });
+        self.storage.update_task_status(&task_id, "completed", Some(&summary)).await?;
+
+        Ok(TaskReport {
+            task_id,
+            output_path,
+            schema,
+            results_count: records.len() as u32,
+            summary,
+        })
+    }
+
+    /// Re-run extraction over the pages captured in WARC files, without
+    /// fetching anything. With a recipe, list rules run on every archived page
+    /// and detail fields come from the archived detail pages; otherwise pages
+    /// go through `extract_page` with a schema planned from `task_description`.
+    pub async fn replay_warc(&self, archives: &[String], recipe_path: Option<&str>, task_description: Option<&str>, output: Option<String>) -> Result<TaskReport> {
+        let recipe = recipe_path.map(Recipe::load).transpose()?;
+        let plan = match task_description {
+            Some(description) => Some(self.plan(description).await),
+            None => None,
+        };
+        let query = match (task_description, &recipe) {
+            (Some(description), _) => description.to_string(),
+            (None, Some(recipe)) => format!("warc-replay:{}", recipe.name),
+            (None, None) => return Err(anyhow!("A replay needs a recipe or a task description to extract with")),
+        };
+
+        let mut warc_records = Vec::new();
+        for archive in archives {
+            warc_records.extend(read_warc(std::path::Path::new(archive)).await?);
+        }
+        let pages = archived_pages(&warc_records);
+        info!("Replaying {} page(s) from {} archive(s)", pages.len(), archives.len());
+
+        let task_id = uuid::Uuid::new_v4().to_string();
+        self.storage.store_task(&task_id, &query, "executing").await?;
+        let schema = match (&recipe, &plan) {
+            (Some(recipe), _) => recipe.schema(),
+            (None, Some(plan)) => self.resolve_schema(plan).await?,
+            (None, None) => unreachable!("checked above"),
+        };
+        self.storage.store_task_schema(&task_id, &query, &schema).await?;
+
+        let started = std::time::Instant::now();
+        let mut records: Vec<ScrapingResult> = Vec::new();
+        match &recipe {
+            Some(recipe) => {
+                let extractor = SelectorExtractor::new(recipe.clone())?;
+                let by_url: HashMap<&str, &str> = pages.iter().map(|p| (p.url.as_str(), p.html.as_str())).collect();
+                let mut found = Vec::new();
+                for page in &pages {
+                    for (record, detail_url) in extractor.extract_list(&page.html, &page.url)?.records {
+                        found.push((page.url.clone(), record, detail_url));
+                    }
+                }
+                // Pages some record links to are its detail pages, not listings
+                let detail_pages: HashSet<String> = found.iter().filter_map(|(_, _, d)| d.clone()).collect();
+                let mut seen = HashSet::new();
+                for (page_url, mut record, detail_url) in found {
+                    if detail_pages.contains(&page_url) || !seen.insert(record.to_string()) {
+                        continue;
+                    }
+                    let mut source_url = page_url;
+                    if let (Some(detail_url), true) = (detail_url, recipe.has_detail_fields()) {
+                        match by_url.get(detail_url.as_str()) {
+                            Some(html) => {
+                                if let (Some(obj), Value::Object(detail)) = (record.as_object_mut(), extractor.extract_detail(html, &detail_url)?) {
+                                    obj.extend(detail);
+                                }
+                                source_url = detail_url;
+                            }
+                            None => debug!("Detail page {} is not in the archive", detail_url),
+                        }
+                    }
+                    records.push(self.to_result(&task_id, &source_url, None, &record, &schema, self.config.quality.selector_confidence));
+                }
+            }
+            None => {
+                for page in &pages {
+                    match self.extract_page(&task_id, &page.url, &page.html, &schema).await {
+                        Ok(results) => records.extend(results.iter().map(|r| self.to_result(&task_id, &page.url, None, &r.data, &schema, r.confidence))),
+                        Err(e) => warn!("Failed to extract from {}: {}", page.url, e),
+                    }
+                }
+            }
+        }
+        let extracted = records.len();
+        records.retain(|r| self.quality.keeps(r.quality_score));
+        let dropped = extracted - records.len();
+        for result in &records {
+            self.store_record(result).await?;
+        }
+        let (records, merged, merged_dropped) = self.dedup(&task_id, records, &schema).await?;
+
+        let output_path = self.export_records(&records, &schema, output).await?;
+        let summary = format!("WARC replay completed!\n\
+                   📋 Task ID: {}\n\
+                   📁 Output: {}\n\
+                   🕐 Duration: {:.1} seconds\n\
+                   🗄️ Archives: {} file(s), {} page(s)\n\
+                   📊 Results: {} items found\n\
+                   🧩 Merged: {} duplicate record(s)\n\
+                   ⭐ Quality: {}\n\
+                   📋 Fields: {}",
+                   task_id,
+                   output_path,
+                   started.elapsed().as_secs_f32(),
+                   archives.len(),
+                   pages.len(),
+                   records.len(),
+                   merged,
+                   quality_summary(&records, dropped + merged_dropped),
+                   schema.column_names().join(", "));
+        self.storage.update_task_status(&task_id, "completed", Some(&summary)).await?;
+
+        Ok(TaskReport {
+            task_id,
+            output_path,
+            schema,
+            results_count: records.len() as u32,
+            summary,
+        })
+    }
+
+    /// Merge the records of earlier tasks (say, one scrape per directory site)
+    /// into deduplicated entities, exported as a new task
+    pub async fn merge_tasks(&self, task_ids: &[String], output: Option<String>) -> Result<TaskReport> {
+        let mut schema: Option<OutputSchema> = None;
+        let mut stored = Vec::new();
+        for task_id in task_ids {
+            let task_schema = self.storage.get_task_schema(task_id).await?
+                .ok_or_else(|| anyhow!("No task with ID {}", task_id))?;
+            // Columns of the first task, then any the others add
+            match &mut schema {
+                Some(schema) => {
+                    for field in task_schema.fields {
+                        if schema.field(&field.name).is_none() {
+                            schema.fields.push(field);
+                        }
+                    }
+                }
+                None => schema = Some(task_schema),
+            }
+            stored.extend(self.storage.get_task_records(task_id).await?);
+        }
+        let schema = schema.ok_or_else(|| anyhow!("Give at least one task ID to merge"))?;
+
+        let query = format!("merge:{}", task_ids.join(","));
+        let task_id = uuid::Uuid::new_v4().to_string();
+        self.storage.store_task(&task_id, &query, "executing").await?;
+        self.storage.store_task_schema(&task_id, &query, &schema).await?;
+
+        let record_count = stored.len();
+        // Records are scored again in case the quality settings changed since they were scraped
+        let results: Vec<ScrapingResult> = stored
+            .iter()
+            .map(|(source_url, record, confidence)| {
+                let confidence = confidence.unwrap_or(self.config.quality.selector_confidence);
+                self.to_result(&task_id, source_url, None, record, &schema, confidence)
+            })
+            .filter(|r| self.quality.keeps(r.quality_score))
+            .collect();
+        let dropped = record_count - results.len();
+        let entities = Deduplicator::new(&self.config.dedup).merge(results);
+        let entity_count = entities.len();
+        let records = self.keep_entities(&task_id, entities, &schema).await?;
+        let dropped = dropped + entity_count - records.len();
+        let output_path = self.export_records(&records, &schema, output).await?;
+        let summary = format!("Merge completed!\n\
+                   📋 Task ID: {}\n\
+                   📁 Output: {}\n\
+                   🧩 Entities: {} from {} record(s) in {} task(s)\n\
+                   ⭐ Quality: {}\n\
+                   📋 Fields: {}",
+                   task_id,
+                   output_path,
+                   records.len(),
+                   record_count,
+                   task_ids.len(),
+                   quality_summary(&records, dropped),
+                   schema.column_names().join(", "));
+        self.storage.update_task_status(&task_id, "completed", Some(&summary)).await?;
+
+        Ok(TaskReport {
+            task_id,
+            output_path,
+            schema,
+            results_count: records.len() as u32,
+            summary,
+        })
+    }
+
+    /// Merge a task's records that describe the same entity, when dedup is
+    /// enabled. Returns the records to export, how many were merged away and
+    /// how many merged entities fell below the minimum quality.
+    async fn dedup(&self, task_id: &str, records: Vec<ScrapingResult>, schema: &OutputSchema) -> Result<(Vec<ScrapingResult>, usize, usize)> {
+        if !self.config.dedup.enabled {
+            return Ok((records, 0, 0));
+        }
+        let count = records.len();
+        let entities = Deduplicator::new(&self.config.dedup).merge(records);
+        let merged = count - entities.len();
+        if merged > 0 {
+            info!("Merged {} record(s) into {} entities", count, entities.len());
+        }
+        let entity_count = entities.len();
+        let kept = self.keep_entities(task_id, entities, schema).await?;
+        let dropped = entity_count - kept.len();
+        Ok((kept, merged, dropped))
+    }
+
+    /// Score merged entities, storing and returning those that meet the minimum quality.
+    /// Each is stored as an entity and as the task's one record of it.
+    async fn keep_entities(&self, task_id: &str, entities: Vec<Entity>, schema: &OutputSchema) -> Result<Vec<ScrapingResult>> {
+        let mut kept = Vec::new();
+        for mut entity in entities {
+            let result = self.entity_result(task_id, &entity, schema);
+            if !self.quality.keeps(result.quality_score) {
+                continue;
+            }
+            entity.quality_score = result.quality_score;
+            self.storage.store_entity(task_id, &entity).await?;
+            self.storage.store_scraped_data(&result).await?;
+            kept.push(result);
+        }
+        Ok(kept)
+    }
+
+    /// Store a record as it's scraped. With dedup on, records wait to be merged
+    /// and only the merged entities are stored (see `keep_entities`).
+    async fn store_record(&self, result: &ScrapingResult) -> Result<()> {
+        if !self.config.dedup.enabled {
+            self.storage.store_scraped_data(result).await?;
+        }
+        Ok(())
+    }
+
+    /// Normalise an extracted record, flag the values that don't pass and score it
+    fn to_result(&self, task_id: &str, source_url: &str, snapshot: Option<&PageSnapshot>, record: &Value, schema: &OutputSchema, confidence: f32) -> ScrapingResult {
+        let normalized = self.normalizer.normalize(record, schema, source_url);
+        let mut flags = normalized.flags;
+        for issue in schema.validate(&normalized.data) {
+            if !flags.iter().any(|f| f.field == issue.field) {
+                flags.push(issue);
+            }
+        }
+        for flag in &flags {
+            debug!("{}: {} {}", source_url, flag.field, flag.problem);
+        }
+        let quality = self.quality.score(schema, &normalized.data, &flags, confidence, self.quality.reliability(source_url));
+        debug!("{}: quality {:?}", source_url, quality);
+        ScrapingResult {
+            task_id: task_id.to_string(),
+            quality_score: quality.score,
+            confidence,
+            data: normalized.data,
+            source_url: source_url.to_string(),
+            extracted_at: Utc::now(),
+            snapshot: snapshot.cloned(),
+            flags,
+        }
+    }
+
+    // A merged entity takes the flags of the values it kept, and is as reliable
+    // as the most reliable site it was found on
+    fn entity_result(&self, task_id: &str, entity: &Entity, schema: &OutputSchema) -> ScrapingResult {
+        let reliability = entity.sources.iter().map(|s| self.quality.reliability(s)).fold(0.0, f32::max);
+        let quality = self.quality.score(schema, &entity.data, &entity.flags, entity.confidence, reliability);
+        ScrapingResult {
+            task_id: task_id.to_string(),
+            quality_score: quality.score,
+            confidence: entity.confidence,
+            source_url: entity.primary_source().to_string(),
+            data: entity.data.clone(),
+            extracted_at: entity.extracted_at,
+            snapshot: entity.snapshot.clone(),
+            flags: entity.flags.clone(),
+        }
+    }
+
+    /// Remember a page's version for the next run of `scope` and note how it changed
+    async fn track_page(&self, scope: &str, task_id: &str, changes: &mut ChangeReport, version: &PageVersion, change: PageChange) -> Result<()> {
+        changes.record(&version.url, change);
+        self.storage.save_page_version(scope, task_id, version).await
+    }
+
+    /// Crawl settings from the config, for callers to adjust per crawl
+    pub fn crawl_defaults(&self) -> CrawlConfig {
+        self.config.crawl.clone()
+    }
+
+    /// HTTP client for a task, using its cookie jar and saving page snapshots when they're enabled
+    fn http_client(&self, cookies: &Arc<CookieJar>) -> HttpClient {
+        let mut client = HttpClient::new().with_cookies(cookies.clone());
+        if let Some(store) = &self.snapshots {
+            client = client.with_snapshots(store.clone());
+        }
+        if let Some(cache) = &self.cache {
+            client = client.with_cache(cache.clone());
+        }
+        if let Some(archive) = &self.archive {
+            client = client.with_archive(archive.clone());
+        }
+        client
+    }
+
+    fn offline(&self) -> bool {
+        self.config.http_cache.offline
+    }
+
+    /// Cookie jar for a task: the saved global cookies with the task's own on
+    /// top. A task with cookies of its own (imported for it, or saved by an
+    /// isolated run) keeps what it collects to itself.
+    async fn cookie_jar(&self, task_id: &str) -> Result<Arc<CookieJar>> {
+        if !self.config.cookies.persist {
+            return Ok(Arc::new(CookieJar::new(task_id)));
+        }
+        let task_cookies = self.storage.load_cookies(task_id).await?;
+        let scope = if self.config.cookies.isolate_tasks || !task_cookies.is_empty() { task_id } else { GLOBAL_SCOPE };
+        let jar = CookieJar::with_cookies(scope, self.storage.load_cookies(GLOBAL_SCOPE).await?);
+        jar.extend(task_cookies);
+        Ok(Arc::new(jar))
+    }
+
+    async fn save_cookie_jar(&self, jar: &CookieJar) -> Result<()> {
+        if self.config.cookies.persist {
+            self.storage.replace_cookies(jar.scope(), &jar.list(None)).await?;
+        }
+        Ok(())
+    }
+
+    /// Open the credential vault with the passphrase from the environment
+    pub fn open_vault(&self) -> Result<Vault> {
+        let passphrase = std::env::var(&self.config.vault.passphrase_env)
+            .map_err(|_| anyhow!("Set {} to unlock the credential vault", self.config.vault.passphrase_env))?;
+        Vault::open(&self.config.vault.path, &passphrase)
+    }
+
+    /// Auth profiles for this run; none when there's no vault to unlock
+    fn auth_profiles(&self) -> Result<Vec<AuthProfile>> {
+        if !std::path::Path::new(&self.config.vault.path).exists() {
+            return Ok(Vec::new());
+        }
+        if std::env::var(&self.config.vault.passphrase_env).is_err() {
+            warn!("Credential vault found but {} is not set; continuing without logins", self.config.vault.passphrase_env);
+            return Ok(Vec::new());
+        }
+        Ok(self.open_vault()?.profiles().to_vec())
+    }
+
+    /// Run form logins for the domains a task is about to visit
+    async fn login_for(&self, browser: &BrowserInterface, profiles: &[AuthProfile], urls: &[String]) -> Result<()> {
+        // Cached pages were fetched logged in already
+        if self.offline() {
+            return Ok(());
+        }
+        let mut done: Vec<&str> = Vec::new();
+        for url in urls {
+            let Some(host) = url::Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_string())) else {
+                continue;
+            };
+            if let Some(profile) = profile_for(profiles, &host) {
+                if matches!(profile.method, AuthMethod::Form { .. }) && !done.contains(&profile.domain.as_str()) {
+                    browser.login(profile).await?;
+                    done.push(&profile.domain);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_auth_profile(&self, profile: AuthProfile) -> Result<()> {
+        let mut vault = self.open_vault()?;
+        info!("Saving {} auth profile for {}", profile.method.kind(), profile.domain);
+        vault.upsert(profile);
+        vault.save()
+    }
+
+    /// Domains with a saved profile and how each logs in
+    pub fn list_auth_profiles(&self) -> Result<Vec<(String, &'static str)>> {
+        Ok(self.open_vault()?
+            .profiles()
+            .iter()
+            .map(|p| (p.domain.clone(), p.method.kind()))
+            .collect())
+    }
+
+    pub fn remove_auth_profile(&self, domain: &str) -> Result<bool> {
+        let mut vault = self.open_vault()?;
+        let removed = vault.remove(domain);
+        if removed {
+            vault.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Import a Netscape cookies.txt file into the global jar or a task's jar
+    pub async fn import_cookies(&self, path: &str, task_id: Option<&str>) -> Result<usize> {
+        let cookies: Vec<Cookie> = parse_netscape(path)?.into_iter().filter(|c| !c.is_expired()).collect();
+        self.storage.save_cookies(task_id.unwrap_or(GLOBAL_SCOPE), &cookies).await?;
+        Ok(cookies.len())
+    }
+
+    pub async fn list_cookies(&self, task_id: Option<&str>, domain: Option<&str>) -> Result<Vec<Cookie>> {
+        let jar = CookieJar::with_cookies(GLOBAL_SCOPE, self.storage.load_cookies(task_id.unwrap_or(GLOBAL_SCOPE)).await?);
+        Ok(jar.list(domain))
+    }
+
+    /// Delete saved cookies; with no task, clears every scope
+    pub async fn clear_cookies(&self, task_id: Option<&str>, domain: Option<&str>) -> Result<u64> {
+        self.storage.clear_cookies(task_id, domain).await
+    }
+
+    async fn record_snapshot(&self, task_id: &str, snapshot: Option<&PageSnapshot>) -> Result<()> {
+        if let Some(snapshot) = snapshot {
+            self.storage.store_page_snapshot(Some(task_id), snapshot).await?;
+        }
+        Ok(())
+    }
+
+    /// The saved page behind a scraped record, if snapshots were enabled when it was scraped
+    pub async fn snapshot_for(&self, result: &ScrapingResult) -> Result<Option<PageSnapshot>> {
+        if let Some(snapshot) = &result.snapshot {
+            return Ok(Some(snapshot.clone()));
+        }
+        self.storage.snapshot_for(&result.source_url, &result.extracted_at).await
+    }
+
+    /// Export records in the format implied by the output path's extension
+    /// (or the configured default format), with each record's quality score
+    /// as the last column
+    async fn export_records(&self, results: &[ScrapingResult], schema: &OutputSchema, output: Option<String>) -> Result<String> {
+        let records: Vec<(String, Value)> = results
+            .iter()
+            .map(|r| {
+                let mut data = r.data.clone();
+                if let Some(obj) = data.as_object_mut() {
+                    let score = (r.quality_score as f64 * 100.0).round() / 100.0;
+                    obj.insert("quality_score".to_string(), json!(score));
+                }
+                (r.source_url.clone(), data)
+            })
+            .collect();
+        let mut schema = schema.clone();
+        if schema.field("quality_score").is_none() {
+            schema.fields.push(FieldSpec { name: "quality_score".to_string(), field_type: FieldType::Number, required: false });
+        }
+        let output = output.unwrap_or_else(|| {
+            format!("{}/task_{}.{}",
+                   self.config.output.default_directory,
+                   Utc::now().format("%Y%m%d_%H%M%S"),
+                   self.config.output.default_format)
+        });
+        let path = std::path::Path::new(&output);
+        let directory = path.parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        let filename = path.file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Output path '{}' has no file name", output))?;
+
+        let exporter = DataExporter::new(directory).with_schema(schema);
+        match path.extension().and_then(|e| e.to_str()).unwrap_or(&self.config.output.default_format) {
+            "json" => exporter.export_to_json(&records, &filename).await,
+            "xml" => exporter.export_to_xml(&records, &filename).await,
+            _ => exporter.export_to_csv(&records, &filename).await,
+        }
+    }
+
+    /// Extract schema records from a fetched page with the AI backend
+    pub async fn extract_with_ai(&self, task_id: &str, source_url: &str, html: &str, schema: &OutputSchema) -> Result<Vec<ScrapingResult>> {
+        if !self.ai.has_model() {
+            return Err(anyhow!("AI extraction needs an AI backend; configure [ai.backend] or use a recipe"));
+        }
+        AiExtractor::new(schema.clone())
+            .extract(&self.ai, task_id, source_url, html)
+            .await
+    }
+
+    /// Extract records from a page, preferring a saved recipe for the page's domain.
+    /// Pages without one go through the AI, and once enough of them succeed a recipe
+    /// is derived and verified so the rest of the crawl uses the selector engine.
+    pub async fn extract_page(&self, task_id: &str, source_url: &str, html: &str, schema: &OutputSchema) -> Result<Vec<ScrapingResult>> {
+        let domain = url::Url::parse(source_url)?
+            .host_str()
+            .map(|h| h.trim_start_matches("www.").to_string())
+            .ok_or_else(|| anyhow!("URL has no host: {}", source_url))?;
+
+        if let Some(extractor) = self.domain_extractor(&domain, schema).await? {
+            let records = extractor.extract_list(html, source_url)?.records;
+            return Ok(records
+                .into_iter()
+                .map(|(record, _)| {
+                    let data = schema.conform(&record);
+                    let flags = schema.validate(&data);
+                    let confidence = self.config.quality.selector_confidence;
+                    let quality = self.quality.score(schema, &data, &flags, confidence, self.quality.reliability(source_url));
+                    ScrapingResult {
+                        task_id: task_id.to_string(),
+                        data,
+                        source_url: source_url.to_string(),
+                        extracted_at: Utc::now(),
+                        quality_score: quality.score,
+                        confidence,
+                        snapshot: None,
+                        flags,
+                    }
+                })
+                .collect());
+        }
+
+        let results = self.extract_with_ai(task_id, source_url, html, schema).await?;
+        if !results.is_empty() && self.config.extraction.induction_samples > 0 {
+            self.record_sample(&domain, source_url, html, &results, schema).await;
+        }
+        Ok(results)
+    }
+
+    async fn domain_extractor(&self, domain: &str, schema: &OutputSchema) -> Result<Option<Arc<SelectorExtractor>>> {
+        let key = recipe_key(domain, schema);
+        if let Some(extractor) = self.domain_extractors.read().await.get(&key) {
+            return Ok(Some(Arc::clone(extractor)));
+        }
+
+        match Recipe::for_domain(&self.config.extraction.recipes_dir, domain, schema)? {
+            Some(recipe) => {
+                let extractor = Arc::new(SelectorExtractor::new(recipe)?);
+                self.domain_extractors.write().await.insert(key, Arc::clone(&extractor));
+                Ok(Some(extractor))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn record_sample(&self, domain: &str, source_url: &str, html: &str, results: &[ScrapingResult], schema: &OutputSchema) {
+        let key = recipe_key(domain, schema);
+        let samples = {
+            let mut all_samples = self.extraction_samples.write().await;
+            let samples = all_samples.entry(key.clone()).or_default();
+            samples.push(ExtractionSample {
+                url: source_url.to_string(),
+                html: html.to_string(),
+                records: results.iter().map(|r| r.data.clone()).collect(),
+            });
+            if samples.len() < self.config.extraction.induction_samples {
+                return;
+            }
+            std::mem::take(samples)
+        };
+
+        let inducer = RecipeInducer::new(schema.clone(), self.config.extraction.min_agreement);
+        match inducer.induce(&self.ai, domain, &samples).await {
+            Ok(report) => {
+                let path = Recipe::domain_path(&self.config.extraction.recipes_dir, domain, schema);
+                if let Err(e) = report.recipe.save(&path) {
+                    warn!("Failed to save derived recipe for {}: {}", domain, e);
+                    return;
+                }
+                match SelectorExtractor::new(report.recipe) {
+                    Ok(extractor) => {
+                        info!("Saved derived recipe for {} to {}", domain, path.display());
+                        self.domain_extractors.write().await.insert(key, Arc::new(extractor));
+                    }
+                    Err(e) => warn!("Derived recipe for {} failed to compile: {}", domain, e),
+                }
+            }
+            // Keep using the AI; we'll try again after the next batch of samples
+            Err(e) => info!("Couldn't derive a recipe for {} yet: {}", domain, e),
+        }
+    }
+
+    /// Output schema for a plan: reuse the one from the last run of the same
+    /// query so columns stay stable, otherwise ask the AI to propose one
+    pub async fn resolve_schema(&self, plan: &TaskPlan) -> Result<OutputSchema> {
+        if let Some(schema) = self.storage.latest_schema_for_query(&plan.query).await? {
+            debug!("Reusing stored schema for query: {}", plan.query);
+            return Ok(schema);
+        }
+        Ok(self.ai.propose_schema(plan).await)
     }
 
     /// Start the web interface
@@ -151,6 +1628,11 @@ impl TaskManager {
         Ok(())
     }
 
+    /// Whether tasks launched without an explicit flag should run in stealth mode
+    pub fn stealth_default(&self) -> bool {
+        self.config.stealth.enabled
+    }
+
     /// Get system status
     pub async fn get_status(&self) -> Result<SystemStatus> {
         Ok(SystemStatus {
@@ -162,3 +1644,33 @@ impl TaskManager {
         })
     }
 }
+
+/// Average quality of the exported records and how many fell below the minimum
+fn quality_summary(records: &[ScrapingResult], dropped: usize) -> String {
+    let average = if records.is_empty() {
+        0.0
+    } else {
+        records.iter().map(|r| r.quality_score).sum::<f32>() / records.len() as f32
+    };
+    format!("{:.2} average, {} below minimum dropped", average, dropped)
+}
+
+/// Pages a request names, e.g. "jobs listed on https://example.com/careers"
+fn named_urls(query: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for word in query.split_whitespace() {
+        let word = word
+            .trim_start_matches(['(', '<', '"', '\''])
+            .trim_end_matches([')', '>', '"', '\'', ',', ';', ':', '.', '!', '?']);
+        match url::Url::parse(word) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && !urls.contains(&url.to_string()) => urls.push(url.to_string()),
+            _ => {}
+        }
+    }
+    urls
+}
+
+/// Key for a domain's recipe and samples; a recipe only serves the schema it was derived for
+fn recipe_key(domain: &str, schema: &OutputSchema) -> String {
+    format!("{}#{}", domain, schema.fingerprint())
+}
@@ -0,0 +1,265 @@
+// LLM-driven structured extraction
+// Sends cleaned page text to the AI backend along with the task schema and
+// turns the JSON it returns into scored `ScrapingResult` records.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::ai_interface::{estimate_tokens, extract_json, AiInterface, ChatMessage};
+use crate::data::OutputSchema;
+use crate::engine::ScrapingResult;
+use super::html::{chunk_text, clean_text};
+
+const EXTRACTION_PROMPT: &str = "You extract structured records from web page text. \
+Return a JSON object {\"records\": [...], \"confidence\": number}. Each record is an object with exactly \
+the schema fields below plus \"_confidence\" (0.0-1.0, how sure you are the record is correct). \
+Use null for values that aren't on the page; never invent data. Return an empty list if the page has no matching records.";
+
+pub struct AiExtractor {
+    schema: OutputSchema,
+}
+
+impl AiExtractor {
+    pub fn new(schema: OutputSchema) -> Self {
+        Self { schema }
+    }
+
+    pub fn schema(&self) -> &OutputSchema {
+        &self.schema
+    }
+
+    fn system_prompt(&self) -> String {
+        let fields = self.schema
+            .fields
+            .iter()
+            .map(|f| format!("- {} ({:?}{})", f.name, f.field_type, if f.required { ", required" } else { "" }))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("{}\n\nSchema fields:\n{}", EXTRACTION_PROMPT, fields)
+    }
+
+    /// Extract records from a fetched page. The page text is chunked so each
+    /// request fits the model's context window.
+    pub async fn extract(&self, ai: &AiInterface, task_id: &str, source_url: &str, html: &str) -> Result<Vec<ScrapingResult>> {
+        let text = clean_text(html, Some(source_url));
+        if text.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let system_prompt = self.system_prompt();
+        let overhead = estimate_tokens(&system_prompt) + estimate_tokens(source_url) + 16;
+        let chunk_budget = ai.prompt_budget().saturating_sub(overhead).max(64);
+        let chunks = chunk_text(&text, chunk_budget);
+        debug!("Extracting from {} in {} chunk(s)", source_url, chunks.len());
+
+        let mut results: Vec<ScrapingResult> = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let messages = vec![
+                ChatMessage::system(system_prompt.clone()),
+                ChatMessage::user(format!("Page URL: {}\n\nPage text (part {} of {}):\n{}", source_url, index + 1, chunks.len(), chunk)),
+            ];
+
+            let response = match ai.complete(messages, true).await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("AI extraction failed for chunk {} of {}: {}", index + 1, source_url, e);
+                    continue;
+                }
+            };
+
+            for result in self.parse_response(&response, task_id, source_url) {
+                // Records spanning two chunks can come back twice
+                if !results.iter().any(|r| r.data == result.data) {
+                    results.push(result);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Turn a model response into records, with a provisional score from the
+    /// model's confidence and how well each record validates against the schema
+    pub fn parse_response(&self, response: &str, task_id: &str, source_url: &str) -> Vec<ScrapingResult> {
+        let parsed = match extract_json(response) {
+            Some(value) => value,
+            None => {
+                warn!("AI extraction returned no JSON for {}", source_url);
+                return Vec::new();
+            }
+        };
+
+        let overall_confidence = parsed.get("confidence").and_then(Value::as_f64).unwrap_or(0.5) as f32;
+        let records = match &parsed {
+            Value::Array(records) => records.clone(),
+            Value::Object(obj) => obj.get("records").and_then(Value::as_array).cloned().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
+        let field_count = self.schema.fields.len().max(1) as f32;
+        records
+            .iter()
+            .filter(|record| record.is_object())
+            .filter_map(|record| {
+                let confidence = record
+                    .get("_confidence")
+                    .and_then(Value::as_f64)
+                    .map(|c| c as f32)
+                    .unwrap_or(overall_confidence)
+                    .clamp(0.0, 1.0);
+
+                let data = self.schema.conform(record);
+                let has_values = data
+                    .as_object()
+                    .map(|obj| obj.values().any(|v| !v.is_null() && v.as_str() != Some("")))
+                    .unwrap_or(false);
+                if !has_values {
+                    return None;
+                }
+
+                let issues = self.schema.validate(&data);
+                let validation_rate = 1.0 - (issues.len() as f32 / field_count).min(1.0);
+
+                Some(ScrapingResult {
+                    task_id: task_id.to_string(),
+                    data,
+                    source_url: source_url.to_string(),
+                    extracted_at: Utc::now(),
+                    quality_score: (confidence + validation_rate) / 2.0,
+                    confidence,
+                    snapshot: None,
+                    flags: issues,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_interface::MockBackend;
+    use crate::engine::Config;
+    use std::sync::Arc;
+
+    fn schema() -> OutputSchema {
+        OutputSchema::infer(&["name".to_string(), "email".to_string(), "website".to_string()])
+    }
+
+    fn interface(backend: Arc<MockBackend>, max_context_length: u32) -> AiInterface {
+        let mut config = Config::default().ai;
+        config.max_context_length = max_context_length;
+        AiInterface::with_backend(backend, &config)
+    }
+
+    const PAGE: &str = "<html><body><h1>Staff</h1><p>Ada Lovelace, ada@example.com</p></body></html>";
+
+    #[tokio::test]
+    async fn parses_records_from_a_fenced_response() {
+        let backend = Arc::new(MockBackend::always(
+            "Here you go:\n```json\n{\"records\": [{\"name\": \"Ada Lovelace\", \"email\": \"ada@example.com\", \"website\": null, \"_confidence\": 0.9}], \"confidence\": 0.6}\n```",
+        ));
+        let results = AiExtractor::new(schema())
+            .extract(&interface(backend.clone(), 4096), "task", "https://example.com/staff", PAGE)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.data["name"], "Ada Lovelace");
+        assert_eq!(result.data["email"], "ada@example.com");
+        // The model's bookkeeping field isn't part of the record
+        assert!(result.data.get("_confidence").is_none());
+        assert_eq!(result.source_url, "https://example.com/staff");
+        assert!(result.flags.is_empty());
+        assert!((result.confidence - 0.9).abs() < f32::EPSILON);
+        assert_eq!(backend.requests().len(), 1);
+        assert!(backend.requests()[0].json_mode);
+    }
+
+    #[tokio::test]
+    async fn records_without_their_own_confidence_take_the_overall_one() {
+        let backend = Arc::new(MockBackend::always(
+            "{\"records\": [{\"name\": \"Ada Lovelace\"}, {\"name\": \"Grace Hopper\", \"_confidence\": 7}], \"confidence\": 0.4}",
+        ));
+        let results = AiExtractor::new(schema())
+            .extract(&interface(backend, 4096), "task", "https://example.com/staff", PAGE)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!((results[0].confidence - 0.4).abs() < f32::EPSILON);
+        // Out-of-range confidence is clamped
+        assert!((results[1].confidence - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn invalid_values_are_flagged_and_lower_the_score() {
+        let extractor = AiExtractor::new(schema());
+        let valid = extractor.parse_response(
+            "{\"records\": [{\"name\": \"Ada\", \"email\": \"ada@example.com\", \"_confidence\": 0.8}]}",
+            "task",
+            "https://example.com",
+        );
+        let invalid = extractor.parse_response(
+            "{\"records\": [{\"name\": \"Ada\", \"email\": \"not an email\", \"_confidence\": 0.8}]}",
+            "task",
+            "https://example.com",
+        );
+
+        assert!(valid[0].flags.is_empty());
+        assert_eq!(invalid[0].flags.len(), 1);
+        assert_eq!(invalid[0].flags[0].field, "email");
+        assert!(invalid[0].quality_score < valid[0].quality_score);
+    }
+
+    #[test]
+    fn missing_required_fields_are_flagged() {
+        let results = AiExtractor::new(schema()).parse_response(
+            "{\"records\": [{\"name\": null, \"email\": \"ada@example.com\"}]}",
+            "task",
+            "https://example.com",
+        );
+        assert_eq!(results.len(), 1);
+        assert!(results[0].flags.iter().any(|f| f.field == "name" && f.problem.contains("required")));
+    }
+
+    #[test]
+    fn empty_and_unparseable_responses_give_no_records() {
+        let extractor = AiExtractor::new(schema());
+        assert!(extractor.parse_response("I couldn't find anything.", "task", "https://example.com").is_empty());
+        assert!(extractor.parse_response("{\"records\": []}", "task", "https://example.com").is_empty());
+        // Records with every value missing are dropped
+        assert!(extractor
+            .parse_response("{\"records\": [{\"name\": null, \"email\": \"\"}]}", "task", "https://example.com")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn long_pages_are_chunked_and_repeated_records_kept_once() {
+        let paragraphs: String = (0..40)
+            .map(|i| format!("<p>Paragraph {} {}</p>", i, "lorem ipsum dolor sit amet ".repeat(8)))
+            .collect();
+        let html = format!("<html><body>{}</body></html>", paragraphs);
+        let backend = Arc::new(MockBackend::always("{\"records\": [{\"name\": \"Ada Lovelace\"}], \"confidence\": 0.7}"));
+        let results = AiExtractor::new(schema())
+            .extract(&interface(backend.clone(), 1024), "task", "https://example.com", &html)
+            .await
+            .unwrap();
+
+        assert!(backend.requests().len() > 1);
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_chunks_are_skipped() {
+        let backend = Arc::new(MockBackend::new(Vec::new()));
+        let results = AiExtractor::new(schema())
+            .extract(&interface(backend, 4096), "task", "https://example.com", PAGE)
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+}
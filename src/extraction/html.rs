@@ -0,0 +1,127 @@
+// HTML to plain text for model prompts
+// Drops scripts, styles and other non-content markup, keeps block structure as
+// line breaks and appends absolute link targets so URL fields can be extracted.
+
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+use url::Url;
+
+use crate::ai_interface::estimate_tokens;
+
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "svg", "head", "template", "iframe"];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "header", "footer", "main", "aside", "nav", "li", "ul", "ol",
+    "tr", "table", "h1", "h2", "h3", "h4", "h5", "h6", "br", "dt", "dd", "address", "form",
+];
+
+/// Readable text for a page, with `text (link)` for anchors
+pub fn clean_text(html: &str, base_url: Option<&str>) -> String {
+    let document = Html::parse_document(html);
+    let base = base_url.and_then(|u| Url::parse(u).ok());
+
+    let mut out = String::new();
+    walk(document.tree.root(), base.as_ref(), &mut out);
+
+    // Collapse runs of blank lines and trailing spaces
+    out.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn walk(node: NodeRef<Node>, base: Option<&Url>, out: &mut String) {
+    match node.value() {
+        Node::Text(text) => {
+            out.push_str(text);
+        }
+        Node::Element(element) => {
+            let tag = element.name();
+            if SKIPPED_TAGS.contains(&tag) {
+                return;
+            }
+            let block = BLOCK_TAGS.contains(&tag);
+            if block {
+                out.push('\n');
+            }
+
+            for child in node.children() {
+                walk(child, base, out);
+            }
+
+            match tag {
+                "a" => {
+                    if let Some(href) = element.attr("href") {
+                        if let Some(link) = resolve_link(href, base) {
+                            out.push_str(&format!(" ({})", link));
+                        }
+                    }
+                }
+                "img" => {
+                    if let Some(alt) = element.attr("alt").filter(|a| !a.trim().is_empty()) {
+                        out.push_str(&format!(" [image: {}]", alt.trim()));
+                    }
+                }
+                "td" | "th" => out.push_str(" | "),
+                _ => {}
+            }
+            if block {
+                out.push('\n');
+            }
+        }
+        _ => {
+            for child in node.children() {
+                walk(child, base, out);
+            }
+        }
+    }
+}
+
+pub(crate) fn resolve_link(href: &str, base: Option<&Url>) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+        return None;
+    }
+    if href.starts_with("mailto:") || href.starts_with("tel:") {
+        return Some(href.to_string());
+    }
+    match base {
+        Some(base) => base.join(href).ok().map(|u| u.to_string()),
+        None => Url::parse(href).ok().map(|u| u.to_string()),
+    }
+}
+
+/// Split text on line boundaries into chunks of at most `max_tokens` (estimated).
+/// A single line longer than the budget is cut by characters.
+pub fn chunk_text(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_tokens = max_tokens.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if estimate_tokens(line) > max_tokens {
+            if !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+            }
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(max_tokens * 4) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+
+        if !current.is_empty() && estimate_tokens(&current) + estimate_tokens(line) > max_tokens {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
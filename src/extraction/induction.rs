@@ -0,0 +1,507 @@
+// Recipe induction
+// Once AI extraction has worked on a few sample pages of a site, derive CSS
+// selectors that reproduce the same records, verify them against the samples
+// and save them as a recipe so the rest of the crawl can skip the model.
+
+use anyhow::{anyhow, bail, Result};
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::ai_interface::{estimate_tokens, extract_json, AiInterface, ChatMessage};
+use crate::data::OutputSchema;
+use super::html::resolve_link;
+use super::recipe::{FieldRule, ListRule, Recipe};
+use super::selector::SelectorExtractor;
+
+const SELECTOR_PROMPT: &str = "You write CSS selectors for web scraping. Given a page's HTML and the records \
+that were extracted from it, reply with a JSON object {\"item\": css selector matching one element per record \
+(or null if the page holds a single record), \"fields\": [{\"name\", \"css\" (relative to the item), \
+\"attr\" (attribute to read, or null for text), \"regex\" (optional, first capture group is kept)}]}. \
+Prefer stable class names over positions.";
+
+/// A page where AI extraction succeeded, kept as ground truth for induction
+#[derive(Debug, Clone)]
+pub struct ExtractionSample {
+    pub url: String,
+    pub html: String,
+    pub records: Vec<Value>,
+}
+
+/// How well a recipe reproduced the samples
+#[derive(Debug, Clone)]
+pub struct InductionReport {
+    pub recipe: Recipe,
+    /// Fraction of sample values reproduced, per field
+    pub field_agreement: HashMap<String, f32>,
+    /// Fields kept in the recipe that no sample had a value for, so their
+    /// selectors couldn't be checked
+    pub unverified: Vec<String>,
+}
+
+pub struct RecipeInducer {
+    schema: OutputSchema,
+    min_agreement: f32,
+}
+
+impl RecipeInducer {
+    pub fn new(schema: OutputSchema, min_agreement: f32) -> Self {
+        Self { schema, min_agreement }
+    }
+
+    /// Derive and verify a recipe for `domain`. Tries the AI's proposal first and
+    /// a structural heuristic second, keeping whichever reproduces the samples best.
+    pub async fn induce(&self, ai: &AiInterface, domain: &str, samples: &[ExtractionSample]) -> Result<InductionReport> {
+        if samples.is_empty() {
+            bail!("No samples to derive a recipe from");
+        }
+
+        let mut candidates = Vec::new();
+        if ai.has_model() {
+            match self.propose_with_ai(ai, domain, &samples[0]).await {
+                Ok(recipe) => candidates.push(recipe),
+                Err(e) => warn!("AI selector proposal failed for {}: {}", domain, e),
+            }
+        }
+        if let Some(recipe) = self.propose_heuristic(domain, &samples[0]) {
+            candidates.push(recipe);
+        }
+
+        let mut best: Option<InductionReport> = None;
+        for recipe in candidates {
+            let report = match self.verify(recipe, samples) {
+                Ok(report) => report,
+                Err(e) => {
+                    debug!("Discarding candidate recipe for {}: {}", domain, e);
+                    continue;
+                }
+            };
+            let score = mean(report.field_agreement.values());
+            if best.as_ref().map(|b| score > mean(b.field_agreement.values())).unwrap_or(true) {
+                best = Some(report);
+            }
+        }
+
+        let report = best.ok_or_else(|| anyhow!("No candidate recipe for {} could be verified", domain))?;
+        let failing: Vec<String> = report.field_agreement
+            .iter()
+            .filter(|(_, agreement)| **agreement < self.min_agreement)
+            .map(|(field, agreement)| format!("{} ({:.0}%)", field, agreement * 100.0))
+            .collect();
+        if !failing.is_empty() {
+            bail!("Derived selectors for {} don't reproduce the samples: {}", domain, failing.join(", "));
+        }
+
+        info!("Derived recipe for {} from {} sample page(s)", domain, samples.len());
+        if !report.unverified.is_empty() {
+            warn!("No sample had a value for {} on {}; their selectors are unverified", report.unverified.join(", "), domain);
+        }
+        Ok(report)
+    }
+
+    async fn propose_with_ai(&self, ai: &AiInterface, domain: &str, sample: &ExtractionSample) -> Result<Recipe> {
+        let records = serde_json::to_string_pretty(&sample.records.iter().take(3).collect::<Vec<_>>())?;
+        let overhead = estimate_tokens(SELECTOR_PROMPT) + estimate_tokens(&records) + 32;
+        let budget_chars = ai.prompt_budget().saturating_sub(overhead).max(256) * 4;
+        let html: String = compact_html(&sample.html).chars().take(budget_chars).collect();
+
+        let messages = vec![
+            ChatMessage::system(SELECTOR_PROMPT),
+            ChatMessage::user(format!("Records:\n{}\n\nHTML:\n{}", records, html)),
+        ];
+        let response = ai.complete(messages, true).await?;
+        let proposal = extract_json(&response).ok_or_else(|| anyhow!("Selector proposal wasn't JSON"))?;
+
+        let fields = proposal
+            .get("fields")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Selector proposal has no fields"))?
+            .iter()
+            .filter_map(|field| {
+                let name = field.get("name")?.as_str()?.to_string();
+                let spec = self.schema.field(&name)?;
+                Some(FieldRule {
+                    name,
+                    css: Some(field.get("css")?.as_str()?.to_string()),
+                    xpath: None,
+                    attr: field.get("attr").and_then(Value::as_str).map(str::to_string),
+                    regex: field.get("regex").and_then(Value::as_str).map(str::to_string),
+                    multiple: false,
+                    required: spec.required,
+                    field_type: Some(spec.field_type),
+                    from_detail: false,
+                })
+            })
+            .collect();
+
+        let item = proposal.get("item").and_then(Value::as_str).map(str::to_string);
+        Ok(self.build_recipe(domain, item, fields))
+    }
+
+    /// Locate each field value of the sample records in the DOM and generalise
+    /// the elements' tag/class signatures into selectors
+    fn propose_heuristic(&self, domain: &str, sample: &ExtractionSample) -> Option<Recipe> {
+        let document = Html::parse_document(&sample.html);
+        let base = Url::parse(&sample.url).ok();
+
+        // Elements holding each field's value, per record
+        let mut located: Vec<HashMap<String, Located>> = Vec::new();
+        for record in &sample.records {
+            let mut fields = HashMap::new();
+            for spec in &self.schema.fields {
+                let value = match record.get(&spec.name).and_then(Value::as_str) {
+                    Some(value) if !value.trim().is_empty() => value.trim(),
+                    _ => continue,
+                };
+                if let Some(found) = find_element(&document, value, base.as_ref()) {
+                    fields.insert(spec.name.clone(), found);
+                }
+            }
+            if !fields.is_empty() {
+                located.push(fields);
+            }
+        }
+        let first = located.first()?;
+
+        // With several records, the item is the closest container shared by a record's fields
+        let item = if sample.records.len() > 1 {
+            let elements: Vec<ElementRef> = first.values().map(|found| found.element).collect();
+            lowest_common_ancestor(&elements).map(|el| (el, signature(el)))
+        } else {
+            None
+        };
+
+        let fields = self.schema
+            .fields
+            .iter()
+            .filter_map(|spec| {
+                let found = first.get(&spec.name)?;
+                let css = match &item {
+                    Some((container, _)) => relative_selector(*container, found.element)?,
+                    None => absolute_selector(found.element),
+                };
+                Some(FieldRule {
+                    name: spec.name.clone(),
+                    css: Some(css),
+                    xpath: None,
+                    attr: found.attr.clone(),
+                    regex: found.regex.clone(),
+                    multiple: false,
+                    required: spec.required,
+                    field_type: Some(spec.field_type),
+                    from_detail: false,
+                })
+            })
+            .collect();
+
+        Some(self.build_recipe(domain, item.map(|(_, sig)| sig), fields))
+    }
+
+    fn build_recipe(&self, domain: &str, item: Option<String>, fields: Vec<FieldRule>) -> Recipe {
+        Recipe {
+            name: format!("{} (derived)", domain),
+            domain: Some(domain.to_string()),
+            start_urls: Vec::new(),
+            steps: Vec::new(),
+            list: item.map(|item| ListRule { item: Some(item), ..ListRule::default() }),
+            fields,
+        }
+    }
+
+    /// Run the recipe over every sample and measure how many of the AI's values it reproduces
+    pub fn verify(&self, recipe: Recipe, samples: &[ExtractionSample]) -> Result<InductionReport> {
+        let extractor = SelectorExtractor::new(recipe.clone())?;
+        let mut matched: HashMap<String, (usize, usize)> = HashMap::new();
+
+        for sample in samples {
+            let extracted: Vec<Value> = extractor
+                .extract_list(&sample.html, &sample.url)?
+                .records
+                .into_iter()
+                .map(|(record, _)| record)
+                .collect();
+
+            for field in &recipe.fields {
+                for record in &sample.records {
+                    let expected = match record.get(&field.name).and_then(Value::as_str) {
+                        Some(value) if !value.trim().is_empty() => normalise(value),
+                        _ => continue,
+                    };
+                    let entry = matched.entry(field.name.clone()).or_insert((0, 0));
+                    entry.1 += 1;
+                    let found = extracted.iter().any(|r| {
+                        r.get(&field.name).and_then(Value::as_str).map(normalise).as_deref() == Some(expected.as_str())
+                    });
+                    if found {
+                        entry.0 += 1;
+                    }
+                }
+            }
+        }
+
+        let field_agreement: HashMap<String, f32> = matched
+            .into_iter()
+            .map(|(field, (hits, total))| (field, hits as f32 / total.max(1) as f32))
+            .collect();
+        if field_agreement.is_empty() {
+            bail!("Recipe matched none of the sample fields");
+        }
+
+        // Fields with no sample values stay in the recipe (later pages may have
+        // them), but can't count towards its agreement
+        let unverified = recipe
+            .fields
+            .iter()
+            .filter(|f| !field_agreement.contains_key(&f.name))
+            .map(|f| f.name.clone())
+            .collect();
+        Ok(InductionReport { recipe, field_agreement, unverified })
+    }
+}
+
+fn mean<'a>(values: impl Iterator<Item = &'a f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(s, c), v| (s + v, c + 1));
+    if count == 0 { 0.0 } else { sum / count as f32 }
+}
+
+fn normalise(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Page HTML without scripts, styles and comments, to save prompt space
+fn compact_html(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let mut out = document.root_element().html();
+    for tag in ["script", "style", "svg", "noscript"] {
+        while let Some(start) = out.find(&format!("<{}", tag)) {
+            let close = format!("</{}>", tag);
+            match out[start..].find(&close) {
+                Some(end) => out.replace_range(start..start + end + close.len(), ""),
+                None => break,
+            }
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Where a field value was found: the element, the attribute holding it (if
+/// not the text) and a regex to strip a mailto:/tel: prefix
+struct Located<'a> {
+    element: ElementRef<'a>,
+    attr: Option<String>,
+    regex: Option<String>,
+}
+
+/// Deepest element whose text is the value, or whose link resolves to it
+fn find_element<'a>(document: &'a Html, value: &str, base: Option<&Url>) -> Option<Located<'a>> {
+    let wanted = normalise(value);
+    let mut best: Option<Located<'a>> = None;
+
+    for element in document.root_element().descendants().filter_map(ElementRef::wrap) {
+        for attr in ["href", "src"] {
+            if let Some(link) = element.value().attr(attr).and_then(|v| resolve_link(v, base)) {
+                if normalise(&link) == wanted {
+                    return Some(Located { element, attr: Some(attr.to_string()), regex: None });
+                }
+                let stripped = link.trim_start_matches("mailto:").trim_start_matches("tel:");
+                if stripped != link && normalise(stripped) == wanted {
+                    let regex = Some("^(?:mailto:|tel:)([^?]+)".to_string());
+                    return Some(Located { element, attr: Some(attr.to_string()), regex });
+                }
+            }
+        }
+        // Descendants come parent-first, so later matches are deeper
+        if normalise(&element.text().collect::<Vec<_>>().join(" ")) == wanted {
+            best = Some(Located { element, attr: None, regex: None });
+        }
+    }
+    best
+}
+
+fn is_css_ident(class: &str) -> bool {
+    !class.is_empty()
+        && !class.starts_with(|c: char| c.is_ascii_digit())
+        && class.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `tag.class1.class2` for an element, ignoring classes that aren't plain identifiers
+fn signature(element: ElementRef) -> String {
+    let classes: Vec<&str> = element.value().classes().filter(|c| is_css_ident(c)).take(2).collect();
+    if classes.is_empty() {
+        element.value().name().to_string()
+    } else {
+        format!("{}.{}", element.value().name(), classes.join("."))
+    }
+}
+
+fn ancestors(element: ElementRef) -> Vec<ElementRef> {
+    let mut chain = Vec::new();
+    let mut current = element.parent().and_then(ElementRef::wrap);
+    while let Some(el) = current {
+        chain.push(el);
+        current = el.parent().and_then(ElementRef::wrap);
+    }
+    chain
+}
+
+fn lowest_common_ancestor<'a>(elements: &[ElementRef<'a>]) -> Option<ElementRef<'a>> {
+    let first = elements.first()?;
+    let candidates = ancestors(*first);
+    candidates.into_iter().find(|candidate| {
+        let id: NodeId = candidate.id();
+        elements.iter().all(|el| ancestors(*el).iter().any(|a| a.id() == id))
+    })
+}
+
+/// Selector for `element` relative to `container`, using at most the last two steps
+fn relative_selector(container: ElementRef, element: ElementRef) -> Option<String> {
+    if container.id() == element.id() {
+        return None;
+    }
+    let mut path = vec![signature(element)];
+    for ancestor in ancestors(element) {
+        if ancestor.id() == container.id() {
+            break;
+        }
+        path.push(signature(ancestor));
+    }
+    path.truncate(2);
+    path.reverse();
+    Some(path.join(" "))
+}
+
+/// Page-level selector for single-record pages, using the last three steps
+fn absolute_selector(element: ElementRef) -> String {
+    let mut path = vec![signature(element)];
+    path.extend(ancestors(element).into_iter().take(2).map(signature));
+    path.reverse();
+    path.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Config;
+    use serde_json::json;
+
+    fn schema() -> OutputSchema {
+        OutputSchema::infer(&["name".to_string(), "email".to_string()])
+    }
+
+    fn page(people: &[(&str, &str)]) -> String {
+        let items: String = people
+            .iter()
+            .map(|(name, email)| format!(
+                "<div class=\"person\"><h2 class=\"name\">{}</h2><a class=\"mail\" href=\"mailto:{}\">Email</a></div>",
+                name, email
+            ))
+            .collect();
+        format!("<html><body><nav><a href=\"/\">Home</a></nav><section>{}</section></body></html>", items)
+    }
+
+    fn sample(url: &str, people: &[(&str, &str)]) -> ExtractionSample {
+        ExtractionSample {
+            url: url.to_string(),
+            html: page(people),
+            records: people.iter().map(|(name, email)| json!({ "name": name, "email": email })).collect(),
+        }
+    }
+
+    fn rule(name: &str, css: &str) -> FieldRule {
+        FieldRule {
+            name: name.to_string(),
+            css: Some(css.to_string()),
+            xpath: None,
+            attr: None,
+            regex: None,
+            multiple: false,
+            required: false,
+            field_type: None,
+            from_detail: false,
+        }
+    }
+
+    fn recipe(fields: Vec<FieldRule>) -> Recipe {
+        Recipe {
+            name: "staff".to_string(),
+            domain: Some("example.com".to_string()),
+            start_urls: Vec::new(),
+            steps: Vec::new(),
+            list: Some(ListRule { item: Some("div.person".to_string()), ..ListRule::default() }),
+            fields,
+        }
+    }
+
+    #[tokio::test]
+    async fn derives_a_recipe_that_reproduces_the_samples_and_new_pages() {
+        // No backend, so only the structural heuristic proposes selectors
+        let ai = AiInterface::new(&Config::default().ai).await.unwrap();
+        let samples = vec![
+            sample("https://example.com/staff?page=1", &[("Ada Lovelace", "ada@example.com"), ("Grace Hopper", "grace@example.com")]),
+            sample("https://example.com/staff?page=2", &[("Alan Turing", "alan@example.com"), ("Edsger Dijkstra", "edsger@example.com")]),
+        ];
+
+        let report = RecipeInducer::new(schema(), 0.8).induce(&ai, "example.com", &samples).await.unwrap();
+
+        assert_eq!(report.recipe.list.as_ref().and_then(|l| l.item.as_deref()), Some("div.person"));
+        assert_eq!(report.field_agreement.get("name"), Some(&1.0));
+        assert_eq!(report.field_agreement.get("email"), Some(&1.0));
+        assert!(report.unverified.is_empty());
+
+        let extractor = SelectorExtractor::new(report.recipe).unwrap();
+        let records: Vec<Value> = extractor
+            .extract_list(&page(&[("Barbara Liskov", "barbara@example.com")]), "https://example.com/staff?page=3")
+            .unwrap()
+            .records
+            .into_iter()
+            .map(|(record, _)| record)
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["name"], "Barbara Liskov");
+        assert_eq!(records[0]["email"], "barbara@example.com");
+    }
+
+    #[tokio::test]
+    async fn refuses_a_recipe_below_the_agreement_threshold() {
+        let ai = AiInterface::new(&Config::default().ai).await.unwrap();
+        let mut second = sample("https://example.com/staff?page=2", &[("Alan Turing", "alan@example.com"), ("Edsger Dijkstra", "edsger@example.com")]);
+        // The second page is laid out differently, so the first page's selectors miss it
+        second.html = second.html.replace("class=\"person\"", "class=\"member\"");
+        let samples = vec![
+            sample("https://example.com/staff?page=1", &[("Ada Lovelace", "ada@example.com"), ("Grace Hopper", "grace@example.com")]),
+            second,
+        ];
+
+        let error = RecipeInducer::new(schema(), 0.8).induce(&ai, "example.com", &samples).await.unwrap_err();
+        assert!(error.to_string().contains("don't reproduce the samples"), "{}", error);
+    }
+
+    #[test]
+    fn verify_measures_agreement_per_field() {
+        let samples = vec![sample("https://example.com/staff", &[("Ada Lovelace", "ada@example.com"), ("Grace Hopper", "grace@example.com")])];
+        let candidate = recipe(vec![rule("name", "h2.name"), rule("email", "span.email")]);
+
+        let report = RecipeInducer::new(schema(), 0.8).verify(candidate, &samples).unwrap();
+        assert_eq!(report.field_agreement.get("name"), Some(&1.0));
+        assert_eq!(report.field_agreement.get("email"), Some(&0.0));
+    }
+
+    #[test]
+    fn fields_without_sample_values_are_kept_but_unverified() {
+        let schema = OutputSchema::infer(&["name".to_string(), "email".to_string(), "phone".to_string()]);
+        let samples = vec![sample("https://example.com/staff", &[("Ada Lovelace", "ada@example.com")])];
+        let mut email = rule("email", "a.mail");
+        email.attr = Some("href".to_string());
+        email.regex = Some("^mailto:(.+)".to_string());
+        let candidate = recipe(vec![rule("name", "h2.name"), email, rule("phone", "span.phone")]);
+
+        let report = RecipeInducer::new(schema, 0.8).verify(candidate, &samples).unwrap();
+        assert_eq!(report.field_agreement.get("email"), Some(&1.0));
+        assert!(!report.field_agreement.contains_key("phone"));
+        assert_eq!(report.unverified, vec!["phone".to_string()]);
+        assert!(report.recipe.fields.iter().any(|f| f.name == "phone"));
+    }
+}
@@ -0,0 +1,13 @@
+// Extraction module for Flash AI
+// Turns fetched pages into structured records
+
+pub mod ai;
+pub mod html;
+pub mod induction;
+pub mod recipe;
+pub mod selector;
+
+pub use ai::AiExtractor;
+pub use induction::{ExtractionSample, RecipeInducer};
+pub use recipe::Recipe;
+pub use selector::SelectorExtractor;
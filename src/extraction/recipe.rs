@@ -0,0 +1,367 @@
+// Extraction recipes
+// Declarative selector rules for a site, loaded from TOML or YAML:
+//
+//   name = "example-universities"
+//   start_urls = ["https://example.com/universities"]
+//
+//   [list]
+//   item = "div.university"          # one record per matching element
+//   detail_link = "a.more"           # optional: follow to a detail page
+//   next_page = "li.next a"          # optional: pagination is detected otherwise
+//
+//   [[fields]]
+//   name = "name"
+//   css = "h2"
+//
+//   [[fields]]
+//   name = "email"
+//   css = "a[href^=mailto]"
+//   attr = "href"
+//   regex = "mailto:(.+)"
+//   from_detail = true
+//
+// Recipes for pages that need interaction (search forms, dropdowns) can add
+// `[[steps]]` to run in the browser on each start URL; see
+// `browser_interface::steps`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::browser_interface::BrowserStep;
+use crate::data::schema::{FieldSpec, FieldType, OutputSchema};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    /// Domain the recipe was written for, used to look recipes up
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub start_urls: Vec<String>,
+    /// Browser actions run on each start URL; pages are taken at `extract` steps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<BrowserStep>,
+    #[serde(default)]
+    pub list: Option<ListRule>,
+    pub fields: Vec<FieldRule>,
+}
+
+/// How to find records on a list page
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListRule {
+    /// CSS selector for each record's container element
+    #[serde(default)]
+    pub item: Option<String>,
+    /// XPath alternative to `item`; fields must then use `xpath` too
+    #[serde(default)]
+    pub item_xpath: Option<String>,
+    /// CSS selector (relative to the item) for the link to the record's detail page
+    #[serde(default)]
+    pub detail_link: Option<String>,
+    /// XPath alternative to `detail_link`
+    #[serde(default)]
+    pub detail_link_xpath: Option<String>,
+    /// CSS selector for the link to the next page of results, when detection gets it wrong
+    #[serde(default)]
+    pub next_page: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldRule {
+    pub name: String,
+    #[serde(default)]
+    pub css: Option<String>,
+    #[serde(default)]
+    pub xpath: Option<String>,
+    /// Attribute to read instead of the element's text, e.g. "href"
+    #[serde(default)]
+    pub attr: Option<String>,
+    /// Regex applied to the value; the first capture group is kept if there is one
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Collect every match as a list instead of the first one
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, rename = "type")]
+    pub field_type: Option<FieldType>,
+    /// Read this field from the record's detail page instead of the list item
+    #[serde(default)]
+    pub from_detail: bool,
+}
+
+impl Recipe {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recipe {}", path.display()))?;
+
+        let recipe: Recipe = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)?,
+            Some("toml") | None => toml::from_str(&content)?,
+            Some(other) => bail!("Unsupported recipe format '.{}' (use .toml or .yaml)", other),
+        };
+        recipe.validate()?;
+        Ok(recipe)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::to_string(self)?,
+            _ => toml::to_string_pretty(self)?,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Path of the recipe derived for a domain and schema inside the recipes directory
+    pub fn domain_path(recipes_dir: &str, domain: &str, schema: &OutputSchema) -> PathBuf {
+        Path::new(recipes_dir).join(format!("{}.{}.toml", domain.trim_start_matches("www."), schema.fingerprint()))
+    }
+
+    /// Saved recipe for a domain that extracts every field of `schema`: the one
+    /// derived for it, or a hand-written `<domain>.toml` that covers it
+    pub fn for_domain(recipes_dir: &str, domain: &str, schema: &OutputSchema) -> Result<Option<Self>> {
+        let derived = Self::domain_path(recipes_dir, domain, schema);
+        if derived.exists() {
+            return Ok(Some(Self::load(derived)?));
+        }
+        let written = Path::new(recipes_dir).join(format!("{}.toml", domain.trim_start_matches("www.")));
+        if !written.exists() {
+            return Ok(None);
+        }
+        let recipe = Self::load(written)?;
+        Ok(Some(recipe).filter(|r| r.covers(schema)))
+    }
+
+    /// True when the recipe has a rule for every field of the schema
+    pub fn covers(&self, schema: &OutputSchema) -> bool {
+        schema.fields.iter().all(|spec| self.fields.iter().any(|f| f.name == spec.name))
+    }
+
+    /// True when records are located with XPath rather than CSS
+    pub fn uses_xpath(&self) -> bool {
+        self.list.as_ref().map(|l| l.item_xpath.is_some()).unwrap_or(false)
+            || self.fields.iter().any(|f| f.xpath.is_some())
+    }
+
+    /// The recipe's own next-page selector, if it has one
+    pub fn next_page_selector(&self) -> Option<&str> {
+        self.list.as_ref().and_then(|l| l.next_page.as_deref())
+    }
+
+    pub fn has_detail_fields(&self) -> bool {
+        self.fields.iter().any(|f| f.from_detail)
+    }
+
+    /// CSS selectors expected on list pages (`detail == false`) or detail pages,
+    /// used to tell a real page from an empty JavaScript shell
+    pub fn page_selectors(&self, detail: bool) -> Vec<String> {
+        if !detail {
+            if let Some(item) = self.list.as_ref().and_then(|l| l.item.clone()) {
+                return vec![item];
+            }
+        }
+        self.fields
+            .iter()
+            .filter(|f| f.from_detail == detail)
+            .filter_map(|f| f.css.clone())
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.fields.is_empty() {
+            bail!("Recipe '{}' has no fields", self.name);
+        }
+
+        let xpath = self.uses_xpath();
+        for field in &self.fields {
+            match (&field.css, &field.xpath) {
+                (Some(_), Some(_)) => bail!("Field '{}' sets both css and xpath", field.name),
+                (None, None) => bail!("Field '{}' needs a css or xpath selector", field.name),
+                (Some(_), None) if xpath => {
+                    bail!("Field '{}' uses css but the recipe locates records with xpath", field.name)
+                }
+                _ => {}
+            }
+            if let Some(pattern) = &field.regex {
+                regex::Regex::new(pattern)
+                    .map_err(|e| anyhow!("Field '{}' has an invalid regex: {}", field.name, e))?;
+            }
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Some(selector) = step.selector() {
+                scraper::Selector::parse(selector)
+                    .map_err(|e| anyhow!("Step {} ({}) has an invalid selector: {:?}", index + 1, step, e))?;
+            }
+            if let BrowserStep::Navigate { url } = step {
+                url::Url::parse(url)
+                    .map_err(|e| anyhow!("Step {} ({}) has an invalid URL: {}", index + 1, step, e))?;
+            }
+        }
+
+        if self.has_detail_fields() && self.list.is_none() {
+            bail!("Recipe '{}' has detail fields but no [list] rule with a detail_link", self.name);
+        }
+        if let Some(list) = &self.list {
+            if list.item.is_some() && list.item_xpath.is_some() {
+                bail!("Recipe '{}' sets both list.item and list.item_xpath", self.name);
+            }
+            if list.detail_link.is_some() && list.detail_link_xpath.is_some() {
+                bail!("Recipe '{}' sets both list.detail_link and list.detail_link_xpath", self.name);
+            }
+            if xpath && list.item.is_some() {
+                bail!("Recipe '{}' uses css for list.item but locates records with xpath (use list.item_xpath)", self.name);
+            }
+            if xpath && list.detail_link.is_some() {
+                bail!("Recipe '{}' uses css for list.detail_link but locates records with xpath (use list.detail_link_xpath)", self.name);
+            }
+            if !xpath && list.detail_link_xpath.is_some() {
+                bail!("Recipe '{}' uses xpath for list.detail_link but locates records with css (use list.detail_link)", self.name);
+            }
+            if self.has_detail_fields() && list.detail_link.is_none() && list.detail_link_xpath.is_none() {
+                bail!("Recipe '{}' has detail fields but no list.detail_link", self.name);
+            }
+            if let Some(selector) = &list.next_page {
+                scraper::Selector::parse(selector)
+                    .map_err(|e| anyhow!("Recipe '{}' has an invalid list.next_page selector: {:?}", self.name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Output schema implied by the recipe's fields
+    pub fn schema(&self) -> OutputSchema {
+        OutputSchema {
+            fields: self.fields
+                .iter()
+                .map(|f| FieldSpec {
+                    name: f.name.clone(),
+                    field_type: f.field_type.unwrap_or_else(|| FieldType::infer(&f.name)),
+                    required: f.required,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, css: &str) -> FieldRule {
+        FieldRule {
+            name: name.to_string(),
+            css: Some(css.to_string()),
+            xpath: None,
+            attr: None,
+            regex: None,
+            multiple: false,
+            required: false,
+            field_type: None,
+            from_detail: false,
+        }
+    }
+
+    fn recipe(fields: Vec<FieldRule>) -> Recipe {
+        Recipe {
+            name: "staff".to_string(),
+            domain: Some("example.com".to_string()),
+            start_urls: Vec::new(),
+            steps: Vec::new(),
+            list: Some(ListRule { item: Some("div.person".to_string()), ..ListRule::default() }),
+            fields,
+        }
+    }
+
+    fn schema(fields: &[&str]) -> OutputSchema {
+        OutputSchema::infer(&fields.iter().map(|f| f.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn domain_recipes_are_looked_up_per_schema() {
+        let dir = std::env::temp_dir().join(format!("flash-recipes-{}", std::process::id()));
+        let recipes_dir = dir.to_string_lossy().to_string();
+        let contacts = schema(&["name", "email"]);
+        let prices = schema(&["name", "price"]);
+
+        let derived = recipe(vec![rule("name", "h2"), rule("email", "a.mail")]);
+        derived.save(Recipe::domain_path(&recipes_dir, "example.com", &contacts)).unwrap();
+
+        // Field order doesn't change the schema a recipe was derived for
+        let reordered = schema(&["email", "name"]);
+        assert!(Recipe::for_domain(&recipes_dir, "www.example.com", &reordered).unwrap().is_some());
+        assert!(Recipe::for_domain(&recipes_dir, "example.com", &prices).unwrap().is_none());
+
+        // A hand-written recipe serves any schema it has rules for
+        recipe(vec![rule("name", "h2"), rule("price", "span.price"), rule("sku", "span.sku")])
+            .save(dir.join("example.com.toml"))
+            .unwrap();
+        assert!(Recipe::for_domain(&recipes_dir, "example.com", &prices).unwrap().is_some());
+        assert!(Recipe::for_domain(&recipes_dir, "example.com", &schema(&["name", "phone"])).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn xpath_rule(name: &str, xpath: &str) -> FieldRule {
+        FieldRule { css: None, xpath: Some(xpath.to_string()), ..rule(name, "") }
+    }
+
+    fn error(recipe: &Recipe) -> String {
+        recipe.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn validate_accepts_consistent_recipes() {
+        let mut css = recipe(vec![rule("name", "h2"), FieldRule { from_detail: true, ..rule("email", "a.mail") }]);
+        css.list.as_mut().unwrap().detail_link = Some("a.more".to_string());
+        css.validate().unwrap();
+
+        let mut xpath = recipe(vec![xpath_rule("name", ".//h2")]);
+        xpath.list = Some(ListRule {
+            item_xpath: Some("//div[@class='person']".to_string()),
+            detail_link_xpath: Some(".//a[@class='more']".to_string()),
+            ..ListRule::default()
+        });
+        xpath.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_rejects_mixed_selector_kinds() {
+        assert!(error(&recipe(vec![rule("name", "h2"), xpath_rule("email", ".//a")])).contains("uses css"));
+
+        // A css list item can't hold xpath fields
+        assert!(error(&recipe(vec![xpath_rule("name", ".//h2")])).contains("list.item_xpath"));
+
+        let mut detail = recipe(vec![xpath_rule("name", ".//h2")]);
+        detail.list = Some(ListRule {
+            item_xpath: Some("//div".to_string()),
+            detail_link: Some("a.more".to_string()),
+            ..ListRule::default()
+        });
+        assert!(error(&detail).contains("list.detail_link_xpath"));
+
+        let mut detail = recipe(vec![rule("name", "h2")]);
+        detail.list.as_mut().unwrap().detail_link_xpath = Some(".//a".to_string());
+        assert!(error(&detail).contains("(use list.detail_link)"));
+    }
+
+    #[test]
+    fn validate_rejects_incomplete_rules() {
+        assert!(error(&recipe(Vec::new())).contains("has no fields"));
+        assert!(error(&recipe(vec![FieldRule { css: None, ..rule("name", "") }])).contains("needs a css or xpath"));
+        assert!(error(&recipe(vec![FieldRule { regex: Some("(".to_string()), ..rule("name", "h2") }])).contains("invalid regex"));
+
+        let mut detail = recipe(vec![FieldRule { from_detail: true, ..rule("email", "a.mail") }]);
+        assert!(error(&detail).contains("no list.detail_link"));
+        detail.list = None;
+        assert!(error(&detail).contains("no [list] rule"));
+    }
+}
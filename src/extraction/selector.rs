@@ -0,0 +1,376 @@
+// Deterministic selector extraction
+// Applies a recipe's CSS or XPath rules to a page and produces one
+// `serde_json::Value` record per list item (or per page without a list rule).
+
+use anyhow::{anyhow, Result};
+use libxml::parser::Parser;
+use libxml::tree::Node as XmlNode;
+use libxml::xpath::Context as XPathContext;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use url::Url;
+
+use super::html::resolve_link;
+use super::recipe::{FieldRule, Recipe};
+
+/// Records found on a list page, with the detail page link for each if the recipe has one
+#[derive(Debug, Clone)]
+pub struct ListPage {
+    pub records: Vec<(Value, Option<String>)>,
+}
+
+pub struct SelectorExtractor {
+    recipe: Recipe,
+    css: HashMap<String, Selector>,
+    regexes: HashMap<String, Regex>,
+}
+
+impl SelectorExtractor {
+    pub fn new(recipe: Recipe) -> Result<Self> {
+        recipe.validate()?;
+
+        let mut css = HashMap::new();
+        let mut compile = |selector: &str| -> Result<()> {
+            if !css.contains_key(selector) {
+                let compiled = Selector::parse(selector)
+                    .map_err(|e| anyhow!("Invalid CSS selector '{}': {:?}", selector, e))?;
+                css.insert(selector.to_string(), compiled);
+            }
+            Ok(())
+        };
+        if let Some(list) = &recipe.list {
+            for selector in list.item.iter().chain(list.detail_link.iter()) {
+                compile(selector)?;
+            }
+        }
+        for field in &recipe.fields {
+            if let Some(selector) = &field.css {
+                compile(selector)?;
+            }
+        }
+
+        let mut regexes = HashMap::new();
+        for field in &recipe.fields {
+            if let Some(pattern) = &field.regex {
+                regexes.insert(field.name.clone(), Regex::new(pattern)?);
+            }
+        }
+
+        Ok(Self { recipe, css, regexes })
+    }
+
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+
+    /// Extract list-level fields from a page. Without a list rule the whole page is one record.
+    pub fn extract_list(&self, html: &str, page_url: &str) -> Result<ListPage> {
+        let base = Url::parse(page_url).ok();
+        let fields: Vec<&FieldRule> = self.recipe.fields.iter().filter(|f| !f.from_detail).collect();
+
+        if self.recipe.uses_xpath() {
+            self.extract_list_xpath(html, base.as_ref(), &fields)
+        } else {
+            Ok(self.extract_list_css(html, base.as_ref(), &fields))
+        }
+    }
+
+    /// Extract the detail-page fields for one record
+    pub fn extract_detail(&self, html: &str, page_url: &str) -> Result<Value> {
+        let base = Url::parse(page_url).ok();
+        let fields: Vec<&FieldRule> = self.recipe.fields.iter().filter(|f| f.from_detail).collect();
+
+        if self.recipe.uses_xpath() {
+            let (document, context) = parse_xml(html)?;
+            let root = document.get_root_element().ok_or_else(|| anyhow!("Page has no root element"))?;
+            let record = self.xpath_record(&context, &root, base.as_ref(), &fields)?;
+            Ok(record)
+        } else {
+            let document = Html::parse_document(html);
+            Ok(self.css_record(document.root_element(), base.as_ref(), &fields))
+        }
+    }
+
+    fn extract_list_css(&self, html: &str, base: Option<&Url>, fields: &[&FieldRule]) -> ListPage {
+        let document = Html::parse_document(html);
+        let list = self.recipe.list.as_ref();
+
+        let items: Vec<ElementRef> = match list.and_then(|l| l.item.as_ref()) {
+            Some(item) => document.select(&self.css[item]).collect(),
+            None => vec![document.root_element()],
+        };
+
+        let records = items
+            .into_iter()
+            .map(|item| {
+                let record = self.css_record(item, base, fields);
+                let detail = list
+                    .and_then(|l| l.detail_link.as_ref())
+                    .and_then(|selector| item.select(&self.css[selector]).next())
+                    .and_then(|link| link.value().attr("href"))
+                    .and_then(|href| resolve_link(href, base));
+                (record, detail)
+            })
+            .filter(|(record, detail)| has_values(record) || detail.is_some())
+            .collect();
+
+        ListPage { records }
+    }
+
+    fn css_record(&self, scope: ElementRef, base: Option<&Url>, fields: &[&FieldRule]) -> Value {
+        let mut record = Map::new();
+        for field in fields {
+            let selector = match &field.css {
+                Some(selector) => &self.css[selector],
+                None => continue,
+            };
+            let values: Vec<String> = scope
+                .select(selector)
+                .filter_map(|el| match &field.attr {
+                    Some(attr) => el.value().attr(attr).map(|v| absolutise(attr, v, base)),
+                    None => Some(normalise_whitespace(&el.text().collect::<Vec<_>>().join(" "))),
+                })
+                .filter_map(|v| self.post_process(field, &v))
+                .collect();
+            record.insert(field.name.clone(), to_value(field, values));
+        }
+        Value::Object(record)
+    }
+
+    fn extract_list_xpath(&self, html: &str, base: Option<&Url>, fields: &[&FieldRule]) -> Result<ListPage> {
+        let (document, context) = parse_xml(html)?;
+        let list = self.recipe.list.as_ref();
+
+        let items: Vec<XmlNode> = match list.and_then(|l| l.item_xpath.as_ref()) {
+            Some(item) => context
+                .evaluate(item)
+                .map_err(|_| anyhow!("Invalid XPath '{}'", item))?
+                .get_nodes_as_vec(),
+            None => vec![document.get_root_element().ok_or_else(|| anyhow!("Page has no root element"))?],
+        };
+
+        let mut records = Vec::new();
+        for item in items {
+            let record = self.xpath_record(&context, &item, base, fields)?;
+            let detail = match list.and_then(|l| l.detail_link_xpath.as_ref()) {
+                Some(xpath) => context
+                    .node_evaluate(xpath, &item)
+                    .map_err(|_| anyhow!("Invalid XPath '{}'", xpath))?
+                    .get_nodes_as_vec()
+                    .first()
+                    .and_then(|node| node.get_attribute("href").or_else(|| Some(node.get_content())))
+                    .and_then(|href| resolve_link(&href, base)),
+                None => None,
+            };
+            if has_values(&record) || detail.is_some() {
+                records.push((record, detail));
+            }
+        }
+
+        Ok(ListPage { records })
+    }
+
+    fn xpath_record(&self, context: &XPathContext, scope: &XmlNode, base: Option<&Url>, fields: &[&FieldRule]) -> Result<Value> {
+        let mut record = Map::new();
+        for field in fields {
+            let xpath = match &field.xpath {
+                Some(xpath) => xpath,
+                None => continue,
+            };
+            let nodes = context
+                .node_evaluate(xpath, scope)
+                .map_err(|_| anyhow!("Invalid XPath '{}' for field '{}'", xpath, field.name))?
+                .get_nodes_as_vec();
+
+            let values: Vec<String> = nodes
+                .iter()
+                .filter_map(|node| match &field.attr {
+                    Some(attr) => node.get_attribute(attr).map(|v| absolutise(attr, &v, base)),
+                    // Attribute nodes selected with @href also come through here
+                    None => Some(normalise_whitespace(&node.get_content())),
+                })
+                .filter_map(|v| self.post_process(field, &v))
+                .collect();
+            record.insert(field.name.clone(), to_value(field, values));
+        }
+        Ok(Value::Object(record))
+    }
+
+    fn post_process(&self, field: &FieldRule, value: &str) -> Option<String> {
+        let value = value.trim();
+        let value = match self.regexes.get(&field.name) {
+            Some(regex) => {
+                let captures = regex.captures(value)?;
+                captures.get(1).or_else(|| captures.get(0))?.as_str().trim().to_string()
+            }
+            None => value.to_string(),
+        };
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+fn parse_xml(html: &str) -> Result<(libxml::tree::Document, XPathContext)> {
+    let document = Parser::default_html()
+        .parse_string(html)
+        .map_err(|e| anyhow!("Failed to parse HTML: {:?}", e))?;
+    let context = XPathContext::new(&document).map_err(|_| anyhow!("Failed to create XPath context"))?;
+    Ok((document, context))
+}
+
+fn to_value(field: &FieldRule, mut values: Vec<String>) -> Value {
+    if field.multiple {
+        Value::Array(values.into_iter().map(Value::String).collect())
+    } else if values.is_empty() {
+        Value::Null
+    } else {
+        Value::String(values.swap_remove(0))
+    }
+}
+
+fn has_values(record: &Value) -> bool {
+    record
+        .as_object()
+        .map(|obj| obj.values().any(|v| match v {
+            Value::Null => false,
+            Value::Array(a) => !a.is_empty(),
+            _ => true,
+        }))
+        .unwrap_or(false)
+}
+
+fn normalise_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Make link-like attributes absolute against the page URL
+fn absolutise(attr: &str, value: &str, base: Option<&Url>) -> String {
+    if matches!(attr, "href" | "src" | "action" | "data-href" | "data-src") {
+        resolve_link(value, base).unwrap_or_else(|| value.to_string())
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LIST: &str = r#"
+        <html><body>
+          <div class="person">
+            <h2> Ada   Lovelace </h2>
+            <a class="mail" href="mailto:ada@example.com">Email</a>
+            <span class="tag">maths</span><span class="tag">engines</span>
+            <a class="more" href="/people/ada">More</a>
+          </div>
+          <div class="person">
+            <h2>Charles Babbage</h2>
+            <a class="more" href="/people/charles">More</a>
+          </div>
+          <div class="person"></div>
+        </body></html>
+    "#;
+
+    fn extractor(recipe: &str) -> SelectorExtractor {
+        SelectorExtractor::new(toml::from_str(recipe).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn extracts_one_record_per_css_list_item() {
+        let extractor = extractor(r#"
+            name = "people"
+            [list]
+            item = "div.person"
+            detail_link = "a.more"
+            [[fields]]
+            name = "name"
+            css = "h2"
+            [[fields]]
+            name = "email"
+            css = "a.mail"
+            attr = "href"
+            regex = "mailto:(.+)"
+            [[fields]]
+            name = "tags"
+            css = "span.tag"
+            multiple = true
+            [[fields]]
+            name = "phone"
+            css = "span.phone"
+            from_detail = true
+        "#);
+
+        let page = extractor.extract_list(LIST, "https://example.com/people?page=1").unwrap();
+        // The empty item has neither values nor a detail link
+        assert_eq!(page.records.len(), 2);
+        let (ada, ada_link) = &page.records[0];
+        assert_eq!(ada, &json!({"name": "Ada Lovelace", "email": "ada@example.com", "tags": ["maths", "engines"]}));
+        assert_eq!(ada_link.as_deref(), Some("https://example.com/people/ada"));
+        let (charles, _) = &page.records[1];
+        assert_eq!(charles["email"], Value::Null);
+        assert_eq!(charles["tags"], json!([]));
+
+        let detail = extractor
+            .extract_detail(r#"<html><body><span class="phone">+44 20 7946 0958</span></body></html>"#, "https://example.com/people/ada")
+            .unwrap();
+        assert_eq!(detail, json!({"phone": "+44 20 7946 0958"}));
+    }
+
+    #[test]
+    fn extracts_list_items_with_xpath() {
+        let extractor = extractor(r#"
+            name = "people"
+            [list]
+            item_xpath = "//div[@class='person']"
+            detail_link_xpath = ".//a[@class='more']"
+            [[fields]]
+            name = "name"
+            xpath = ".//h2"
+            [[fields]]
+            name = "email"
+            xpath = ".//a[@class='mail']/@href"
+        "#);
+
+        let page = extractor.extract_list(LIST, "https://example.com/people").unwrap();
+        assert_eq!(page.records.len(), 2);
+        assert_eq!(page.records[0].0, json!({"name": "Ada Lovelace", "email": "mailto:ada@example.com"}));
+        assert_eq!(page.records[1].1.as_deref(), Some("https://example.com/people/charles"));
+    }
+
+    #[test]
+    fn a_page_without_a_list_rule_is_one_record() {
+        let extractor = extractor(r#"
+            name = "page"
+            [[fields]]
+            name = "title"
+            css = "h2"
+            [[fields]]
+            name = "profile"
+            css = "a.more"
+            attr = "href"
+        "#);
+
+        let page = extractor.extract_list(LIST, "https://example.com/people").unwrap();
+        assert_eq!(page.records.len(), 1);
+        assert_eq!(page.records[0], (json!({"title": "Ada Lovelace", "profile": "https://example.com/people/ada"}), None));
+    }
+
+    #[test]
+    fn rejects_invalid_recipes() {
+        let recipe = toml::from_str(r#"
+            name = "broken"
+            [[fields]]
+            name = "name"
+            css = "h2["
+        "#)
+        .unwrap();
+        assert!(SelectorExtractor::new(recipe).is_err());
+    }
+}
@@ -7,10 +7,14 @@ use tracing_subscriber;
 mod engine;
 mod networking;
 mod data;
+mod extraction;
 mod ai_interface;
 mod browser_interface;
+mod crawl;
+mod pipeline;
 
-use engine::{TaskManager, Config};
+use ai_interface::Role;
+use engine::{TaskManager, Config, Conversation, CrawlJob, DialogueAction};
 
 #[derive(Parser)]
 #[command(name = "flash")]
@@ -36,12 +40,107 @@ enum Commands {
         /// Initial message to Flash
         #[arg(short, long)]
         message: Option<String>,
+        
+        /// Resume a saved chat session by ID
+        #[arg(short, long)]
+        resume: Option<String>,
     },
     
     /// Execute a natural language scraping command
     Execute {
         /// The scraping task in natural language
-        task: String,
+        #[arg(required_unless_present = "recipe")]
+        task: Option<String>,
+        
+        /// Run a selector recipe (TOML or YAML) instead of planning from the task
+        #[arg(short, long)]
+        recipe: Option<String>,
+        
+        /// Only export records from pages that changed since the last run
+        #[arg(short, long, requires = "recipe")]
+        incremental: bool,
+        
+        /// Serve pages only from the response cache, without touching the network
+        #[arg(long, requires = "recipe")]
+        offline: bool,
+        
+        /// Drop records with a quality score below this (0 to 1)
+        #[arg(long, value_parser = parse_score)]
+        min_quality: Option<f32>,
+        
+        /// Archive every request, response and rendered page to WARC files
+        #[arg(long)]
+        warc: bool,
+        
+        /// Output file path
+        #[arg(short, long)]
+        output: Option<String>,
+        
+        /// Enable stealth mode
+        #[arg(short, long)]
+        stealth: bool,
+    },
+    
+    /// Crawl from seed URLs, following links and extracting from every page
+    Crawl {
+        /// Seed URLs (defaults to the recipe's start_urls)
+        seeds: Vec<String>,
+        
+        /// What to extract, in natural language
+        #[arg(short, long)]
+        task: Option<String>,
+        
+        /// Extract with a selector recipe instead
+        #[arg(short, long)]
+        recipe: Option<String>,
+        
+        /// Links to follow away from a seed page
+        #[arg(long)]
+        max_depth: Option<u32>,
+        
+        /// Pages to fetch in total
+        #[arg(long)]
+        max_pages: Option<usize>,
+        
+        /// Follow links to any host (subject to --allow-domain)
+        #[arg(long)]
+        any_domain: bool,
+        
+        /// Also crawl this domain (repeatable)
+        #[arg(long = "allow-domain")]
+        allow_domains: Vec<String>,
+        
+        /// Only follow URLs matching this regex (repeatable)
+        #[arg(long)]
+        include: Vec<String>,
+        
+        /// Never follow URLs matching this regex (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        
+        /// Continue an earlier crawl by its task ID
+        #[arg(long)]
+        resume: Option<String>,
+        
+        /// Also seed from the sites' sitemaps (found through robots.txt)
+        #[arg(long)]
+        sitemap: bool,
+        
+        /// Only take sitemap URLs modified on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        
+        /// Only export records from pages that changed since the last crawl
+        #[arg(short, long)]
+        incremental: bool,
+        
+        /// Drop records with a quality score below this (0 to 1)
+        #[arg(long, value_parser = parse_score)]
+        min_quality: Option<f32>,
+        
+        /// Archive every request, response and rendered page to WARC files
+        #[arg(long)]
+        warc: bool,
         
         /// Output file path
         #[arg(short, long)]
@@ -52,6 +151,21 @@ enum Commands {
         stealth: bool,
     },
     
+    /// Merge the records of earlier tasks into deduplicated entities
+    Merge {
+        /// IDs of the tasks to merge
+        #[arg(required = true)]
+        task_ids: Vec<String>,
+        
+        /// Drop records with a quality score below this (0 to 1)
+        #[arg(long, value_parser = parse_score)]
+        min_quality: Option<f32>,
+        
+        /// Output file path
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    
     /// Start the web dashboard
     Dashboard {
         /// Port for the web interface
@@ -62,6 +176,15 @@ enum Commands {
     /// Manage proxy settings
     Proxy(ProxyArgs),
     
+    /// Manage saved cookies
+    Cookies(CookieArgs),
+    
+    /// Manage login profiles in the credential vault
+    Auth(AuthArgs),
+    
+    /// Work with WARC archives of fetched pages
+    Warc(WarcArgs),
+    
     /// Show system status
     Status,
 }
@@ -93,6 +216,119 @@ enum ProxyAction {
     },
 }
 
+#[derive(Args)]
+struct CookieArgs {
+    #[command(subcommand)]
+    action: CookieAction,
+}
+
+#[derive(Subcommand)]
+enum CookieAction {
+    /// Import cookies from a Netscape cookies.txt file
+    Import {
+        /// Path to cookies.txt
+        file: String,
+        /// Import into a task's jar instead of the global one
+        #[arg(short, long)]
+        task: Option<String>,
+    },
+    /// List saved cookies
+    List {
+        /// Only cookies for this domain (and its subdomains)
+        #[arg(short, long)]
+        domain: Option<String>,
+        /// Show a task's jar instead of the global one
+        #[arg(short, long)]
+        task: Option<String>,
+    },
+    /// Delete saved cookies
+    Clear {
+        /// Only cookies for this domain (and its subdomains)
+        #[arg(short, long)]
+        domain: Option<String>,
+        /// Only this task's cookies
+        #[arg(short, long)]
+        task: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct AuthArgs {
+    #[command(subcommand)]
+    action: AuthAction,
+}
+
+// Secrets are prompted for on stdin so they never end up in shell history
+#[derive(Subcommand)]
+enum AuthAction {
+    /// HTTP basic auth (prompts for the password)
+    Basic {
+        domain: String,
+        username: String,
+    },
+    /// Bearer token (prompts for the token)
+    Bearer {
+        domain: String,
+    },
+    /// Log in through a form in the browser (prompts for the password)
+    Form {
+        domain: String,
+        /// Page with the login form
+        #[arg(long)]
+        login_url: String,
+        #[arg(long)]
+        username: String,
+        #[arg(long, default_value = "input[type=email], input[name=username]")]
+        username_selector: String,
+        #[arg(long, default_value = "input[type=password]")]
+        password_selector: String,
+        #[arg(long, default_value = "button[type=submit], input[type=submit]")]
+        submit_selector: String,
+        /// Element that only appears once logged in
+        #[arg(long)]
+        success_selector: Option<String>,
+    },
+    /// TLS client certificate from a PEM file with the certificate and private key
+    Cert {
+        domain: String,
+        pem_file: String,
+    },
+    /// List domains with a saved profile
+    List,
+    /// Remove a domain's profile
+    Remove {
+        domain: String,
+    },
+}
+
+#[derive(Args)]
+struct WarcArgs {
+    #[command(subcommand)]
+    action: WarcAction,
+}
+
+#[derive(Subcommand)]
+enum WarcAction {
+    /// Re-run extraction over archived pages without fetching anything
+    Replay {
+        /// .warc or .warc.gz files
+        #[arg(required = true)]
+        archives: Vec<String>,
+        /// Extract with a selector recipe
+        #[arg(short, long, required_unless_present = "task")]
+        recipe: Option<String>,
+        /// What to extract, in natural language
+        #[arg(short, long)]
+        task: Option<String>,
+        /// Drop records with a quality score below this (0 to 1)
+        #[arg(long, value_parser = parse_score)]
+        min_quality: Option<f32>,
+        /// Output file path
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -106,20 +342,76 @@ async fn main() -> Result<()> {
     info!("🤖 Flash AI starting up...");
     
     // Load configuration
-    let config = Config::load(&cli.config)?;
+    let mut config = Config::load(&cli.config)?;
+    if let Some(Commands::Execute { offline: true, .. }) = &cli.command {
+        info!("Offline: pages come from the response cache only");
+        config.http_cache.offline = true;
+    }
+    if matches!(&cli.command, Some(Commands::Execute { warc: true, .. }) | Some(Commands::Crawl { warc: true, .. })) {
+        config.warc.enabled = true;
+    }
+    if let Some(
+        Commands::Execute { min_quality: Some(min_quality), .. }
+        | Commands::Crawl { min_quality: Some(min_quality), .. }
+        | Commands::Merge { min_quality: Some(min_quality), .. }
+        | Commands::Warc(WarcArgs { action: WarcAction::Replay { min_quality: Some(min_quality), .. } }),
+    ) = &cli.command
+    {
+        config.quality.min_score = *min_quality;
+    }
     
     // Initialize core systems
     let task_manager = TaskManager::new(config).await?;
     
     match cli.command {
-        Some(Commands::Chat { message }) => {
+        Some(Commands::Chat { message, resume }) => {
             info!("Starting interactive chat mode");
-            run_chat_mode(&task_manager, message).await?;
+            run_chat_mode(&task_manager, message, resume).await?;
         },
         
-        Some(Commands::Execute { task, output, stealth }) => {
-            info!("Executing task: {}", task);
-            execute_task(&task_manager, &task, output, stealth).await?;
+        Some(Commands::Execute { task, recipe, incremental, offline: _, min_quality: _, warc: _, output, stealth }) => {
+            match recipe {
+                Some(recipe) => {
+                    info!("Executing recipe: {}", recipe);
+                    let report = task_manager.run_recipe(&recipe, task.as_deref(), output, stealth, incremental).await?;
+                    println!("✅ {}", report);
+                },
+                None => {
+                    let task = task.unwrap_or_default();
+                    info!("Executing task: {}", task);
+                    execute_task(&task_manager, &task, output, stealth).await?;
+                },
+            }
+        },
+        
+        Some(Commands::Crawl { seeds, task, recipe, max_depth, max_pages, any_domain, allow_domains, include, exclude, resume, sitemap, since, incremental, min_quality: _, warc: _, output, stealth }) => {
+            let mut settings = task_manager.crawl_defaults();
+            if let Some(max_depth) = max_depth {
+                settings.max_depth = max_depth;
+            }
+            if let Some(max_pages) = max_pages {
+                settings.max_pages = max_pages;
+            }
+            if any_domain {
+                settings.same_domain = false;
+            }
+            settings.allowed_domains.extend(allow_domains);
+            settings.include.extend(include);
+            settings.exclude.extend(exclude);
+            if sitemap || since.is_some() {
+                settings.sitemaps = true;
+            }
+            let modified_since = since.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc());
+
+            info!("Crawling from {} seed(s)", seeds.len());
+            let report = task_manager.crawl(CrawlJob { seeds, settings, recipe, task, output, stealth, resume, modified_since, incremental }).await?;
+            println!("✅ {}", report);
+        },
+        
+        Some(Commands::Merge { task_ids, min_quality: _, output }) => {
+            info!("Merging {} task(s)", task_ids.len());
+            let report = task_manager.merge_tasks(&task_ids, output).await?;
+            println!("✅ {}", report);
         },
         
         Some(Commands::Dashboard { port }) => {
@@ -131,6 +423,18 @@ async fn main() -> Result<()> {
             handle_proxy_command(&task_manager, proxy_args).await?;
         },
         
+        Some(Commands::Cookies(cookie_args)) => {
+            handle_cookie_command(&task_manager, cookie_args).await?;
+        },
+        
+        Some(Commands::Auth(auth_args)) => {
+            handle_auth_command(&task_manager, auth_args).await?;
+        },
+        
+        Some(Commands::Warc(warc_args)) => {
+            handle_warc_command(&task_manager, warc_args).await?;
+        },
+        
         Some(Commands::Status) => {
             show_status(&task_manager).await?;
         },
@@ -138,14 +442,14 @@ async fn main() -> Result<()> {
         None => {
             // Default to chat mode
             info!("No command specified, starting chat mode");
-            run_chat_mode(&task_manager, None).await?;
+            run_chat_mode(&task_manager, None, None).await?;
         }
     }
     
     Ok(())
 }
 
-async fn run_chat_mode(task_manager: &TaskManager, initial_message: Option<String>) -> Result<()> {
+async fn run_chat_mode(task_manager: &TaskManager, initial_message: Option<String>, resume: Option<String>) -> Result<()> {
     println!("🤖 Flash AI Chat Mode");
     println!("=====================");
     println!("Type your scraping requests in natural language!");
@@ -153,12 +457,24 @@ async fn run_chat_mode(task_manager: &TaskManager, initial_message: Option<Strin
     println!("  - Find 100 universities in Japan with contact info");
     println!("  - Get all restaurants in Paris with Michelin stars");
     println!("  - Scrape job postings for AI engineers in Silicon Valley");
+    println!("Type /help for chat commands.");
     println!();
     
+    let mut conversation = task_manager.start_chat(resume.as_deref()).await?;
+    let stealth = task_manager.stealth_default();
+    if resume.is_some() {
+        println!("📂 Resumed session {} ({} messages)", conversation.session_id, conversation.history.len());
+        if let Some(plan) = &conversation.plan {
+            println!("📋 Pending plan:\n{}", plan.summary());
+        }
+    } else {
+        println!("🆔 Session: {} (resume with: flash chat --resume {})", conversation.session_id, conversation.session_id);
+    }
+    println!();
+
     if let Some(msg) = initial_message {
         println!("👤 You: {}", msg);
-        let response = task_manager.process_natural_language(&msg).await?;
-        println!("🤖 Flash: {}", response);
+        handle_chat_turn(task_manager, &mut conversation, &msg, stealth).await;
     }
     
     loop {
@@ -175,18 +491,95 @@ async fn run_chat_mode(task_manager: &TaskManager, initial_message: Option<Strin
             break;
         }
         
-        match task_manager.process_natural_language(input).await {
-            Ok(response) => println!("🤖 Flash: {}", response),
-            Err(e) => {
-                error!("Error processing request: {}", e);
-                println!("🤖 Flash: Sorry, I encountered an error: {}", e);
+        if let Some(command) = input.strip_prefix('/') {
+            if let Err(e) = handle_slash_command(task_manager, &mut conversation, command, stealth).await {
+                println!("🤖 Flash: {}", e);
             }
+            continue;
         }
+        
+        handle_chat_turn(task_manager, &mut conversation, input, stealth).await;
     }
     
     Ok(())
 }
 
+async fn handle_chat_turn(task_manager: &TaskManager, conversation: &mut Conversation, input: &str, stealth: bool) {
+    match task_manager.chat(conversation, input).await {
+        Ok(DialogueAction::Reply(response)) => println!("🤖 Flash: {}", response),
+        Ok(DialogueAction::Execute(plan, plan_id)) => {
+            println!("🤖 Flash: 🚀 On it!");
+            match task_manager.run_chat_plan(conversation, &plan, plan_id, stealth).await {
+                Ok(result) => println!("✅ {}", result),
+                Err(e) => {
+                    error!("Task failed: {}", e);
+                    println!("🤖 Flash: Sorry, the task failed: {}", e);
+                }
+            }
+        }
+        Err(e) => {
+            error!("Error processing request: {}", e);
+            println!("🤖 Flash: Sorry, I encountered an error: {}", e);
+        }
+    }
+}
+
+async fn handle_slash_command(task_manager: &TaskManager, conversation: &mut Conversation, command: &str, stealth: bool) -> Result<()> {
+    let (name, arg) = match command.split_once(' ') {
+        Some((name, arg)) => (name, Some(arg.trim()).filter(|a| !a.is_empty())),
+        None => (command, None),
+    };
+    
+    match name {
+        "help" => {
+            println!("📖 Chat commands:");
+            println!("  /history         Show this session's messages");
+            println!("  /plan            Show the current plan");
+            println!("  /run             Run the current plan now");
+            println!("  /save [title]    Save the session (optionally naming it)");
+            println!("  /export [path]   Export the session to JSON");
+            println!("  /sessions        List saved sessions");
+        },
+        "history" => {
+            if conversation.history.is_empty() {
+                println!("📜 No messages yet");
+            }
+            for message in &conversation.history {
+                let speaker = match message.role {
+                    Role::User => "👤 You",
+                    Role::Assistant => "🤖 Flash",
+                    Role::System => "⚙️ System",
+                };
+                println!("{}: {}", speaker, message.content);
+            }
+        },
+        "plan" => match &conversation.plan {
+            Some(plan) => println!("📋 Current plan:\n{}", plan.summary()),
+            None => println!("📋 No plan yet. Tell me what to scrape!"),
+        },
+        "run" => {
+            let report = task_manager.run_current_plan(conversation, stealth).await?;
+            println!("✅ {}", report);
+        },
+        "save" => {
+            task_manager.save_chat(conversation, arg).await?;
+            println!("💾 Session saved: {}", conversation.session_id);
+        },
+        "export" => {
+            let path = task_manager.export_chat(conversation, arg.map(|a| a.to_string())).await?;
+            println!("📁 Session exported to {}", path);
+        },
+        "sessions" => {
+            println!("📂 Saved sessions:");
+            for (id, title, updated_at) in task_manager.list_chat_sessions().await? {
+                println!("  {} - {} ({})", id, title.unwrap_or_else(|| "untitled".to_string()), updated_at);
+            }
+        },
+        other => println!("🤖 Flash: Unknown command /{}. Type /help for the list.", other),
+    }
+    Ok(())
+}
+
 async fn execute_task(
     task_manager: &TaskManager, 
     task: &str, 
@@ -230,6 +623,107 @@ async fn handle_proxy_command(task_manager: &TaskManager, args: ProxyArgs) -> Re
     Ok(())
 }
 
+async fn handle_cookie_command(task_manager: &TaskManager, args: CookieArgs) -> Result<()> {
+    match args.action {
+        CookieAction::Import { file, task } => {
+            let count = task_manager.import_cookies(&file, task.as_deref()).await?;
+            println!("🍪 Imported {} cookie(s) from {}", count, file);
+        },
+        CookieAction::List { domain, task } => {
+            let cookies = task_manager.list_cookies(task.as_deref(), domain.as_deref()).await?;
+            if cookies.is_empty() {
+                println!("🍪 No saved cookies");
+            }
+            for cookie in cookies {
+                let expires = cookie.expires
+                    .and_then(|e| chrono::DateTime::from_timestamp(e, 0))
+                    .map(|e| e.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| "session".to_string());
+                println!("  {}{} {} (expires {})", cookie.domain, cookie.path, cookie.name, expires);
+            }
+        },
+        CookieAction::Clear { domain, task } => {
+            let removed = task_manager.clear_cookies(task.as_deref(), domain.as_deref()).await?;
+            println!("✅ Removed {} cookie(s)", removed);
+        },
+    }
+    Ok(())
+}
+
+/// Prompt for a secret without echoing it to the terminal
+fn read_secret(prompt: &str) -> Result<data::Secret> {
+    let value = rpassword::prompt_password(format!("{}: ", prompt))?;
+    if value.is_empty() {
+        anyhow::bail!("{} must not be empty", prompt);
+    }
+    Ok(data::Secret::new(value))
+}
+
+fn parse_score(value: &str) -> std::result::Result<f32, String> {
+    let score: f32 = value.parse().map_err(|_| format!("'{}' is not a number", value))?;
+    if !(0.0..=1.0).contains(&score) {
+        return Err(format!("{} is not between 0 and 1", score));
+    }
+    Ok(score)
+}
+
+async fn handle_warc_command(task_manager: &TaskManager, args: WarcArgs) -> Result<()> {
+    match args.action {
+        WarcAction::Replay { archives, recipe, task, min_quality: _, output } => {
+            info!("Replaying {} WARC file(s)", archives.len());
+            let report = task_manager.replay_warc(&archives, recipe.as_deref(), task.as_deref(), output).await?;
+            println!("✅ {}", report);
+        },
+    }
+    Ok(())
+}
+
+async fn handle_auth_command(task_manager: &TaskManager, args: AuthArgs) -> Result<()> {
+    use data::{AuthMethod, AuthProfile};
+
+    let (domain, method) = match args.action {
+        AuthAction::Basic { domain, username } => {
+            let password = read_secret("🔑 Password")?;
+            (domain, AuthMethod::Basic { username, password })
+        },
+        AuthAction::Bearer { domain } => {
+            let token = read_secret("🔑 Token")?;
+            (domain, AuthMethod::Bearer { token })
+        },
+        AuthAction::Form { domain, login_url, username, username_selector, password_selector, submit_selector, success_selector } => {
+            let password = read_secret("🔑 Password")?;
+            (domain, AuthMethod::Form { login_url, username, password, username_selector, password_selector, submit_selector, success_selector })
+        },
+        AuthAction::Cert { domain, pem_file } => {
+            let pem = std::fs::read_to_string(&pem_file)?;
+            (domain, AuthMethod::ClientCert { identity_pem: data::Secret::new(pem) })
+        },
+        AuthAction::List => {
+            let profiles = task_manager.list_auth_profiles()?;
+            if profiles.is_empty() {
+                println!("🔐 No saved login profiles");
+            }
+            for (domain, kind) in profiles {
+                println!("  {} - {}", domain, kind);
+            }
+            return Ok(());
+        },
+        AuthAction::Remove { domain } => {
+            if task_manager.remove_auth_profile(&domain)? {
+                println!("✅ Removed login profile for {}", domain);
+            } else {
+                println!("❓ No login profile for {}", domain);
+            }
+            return Ok(());
+        },
+    };
+
+    let kind = method.kind();
+    task_manager.save_auth_profile(AuthProfile { domain: domain.clone(), method })?;
+    println!("🔐 Saved {} login for {}", kind, domain);
+    Ok(())
+}
+
 async fn show_status(task_manager: &TaskManager) -> Result<()> {
     let status = task_manager.get_status().await?;
     println!("🤖 Flash AI System Status");
@@ -0,0 +1,235 @@
+// On-disk HTTP response cache
+// Saves responses so extraction rules can be iterated on without refetching
+// the same pages, and so a run can be replayed with no network at all
+// (offline mode). Entries are keyed by method and URL, plus the auth profile
+// a request was sent with, so a page fetched logged in is never served to a
+// request made without those credentials. Responses with a Vary header are
+// stored per (hashed) value of the request headers it names. Set-Cookie
+// headers are dropped before saving, and responses that vary on credentials
+// or cookies are per-user, so they are never stored at all.
+
+use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::header::{HeaderMap, CACHE_CONTROL, EXPIRES, VARY};
+use reqwest::{Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use crate::Result;
+use crate::engine::config::{CacheMode, HttpCacheConfig};
+
+/// Response headers that hand out session state and must not be replayed
+const SESSION_HEADERS: &[&str] = &["set-cookie", "set-cookie2"];
+
+/// Request headers a response can't vary on and still be shared between users
+const PER_USER_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    /// Hashed values of the request headers named by the response's Vary
+    vary: BTreeMap<String, String>,
+    /// Where the response came from, after redirects
+    #[serde(default)]
+    url: Option<String>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    stored_at: DateTime<Utc>,
+    /// None when the response may be reused indefinitely
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl CachedResponse {
+    fn is_fresh(&self) -> bool {
+        self.expires_at.map(|e| e > Utc::now()).unwrap_or(true)
+    }
+
+    /// Rebuild the response, as served from `url` unless it was redirected
+    fn into_response(self, url: &str) -> Result<Response> {
+        let url = url::Url::parse(self.url.as_deref().unwrap_or(url))?;
+        let mut builder = http::Response::builder().status(self.status).url(url);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = BASE64.decode(&self.body)?;
+        Ok(Response::from(builder.body(body)?))
+    }
+}
+
+pub struct ResponseCache {
+    directory: PathBuf,
+    mode: CacheMode,
+    ttl: Duration,
+    offline: bool,
+}
+
+impl ResponseCache {
+    /// Cache under `base_dir` (the output directory) unless the configured directory is absolute
+    pub fn new(config: &HttpCacheConfig, base_dir: &str) -> Self {
+        Self {
+            directory: Path::new(base_dir).join(&config.directory),
+            mode: config.mode,
+            ttl: Duration::seconds(config.ttl_seconds as i64),
+            offline: config.offline,
+        }
+    }
+
+    /// Serve everything from the cache and never touch the network
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    fn path_for(&self, method: &str, url: &str, profile: Option<&str>) -> PathBuf {
+        let request = match profile {
+            Some(profile) => format!("{} {} as {}", method, url, profile),
+            None => format!("{} {}", method, url),
+        };
+        let key = format!("{:x}", Sha256::digest(request.as_bytes()));
+        self.directory.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    async fn entries(&self, path: &Path) -> Vec<CachedResponse> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// A cached response for the request, if there's a usable one. `profile`
+    /// names the auth profile the request is sent with, if any, and `header`
+    /// gives the value it sends for a header name. Offline, stale entries are
+    /// served too.
+    pub async fn lookup(&self, method: &str, url: &str, profile: Option<&str>, header: impl Fn(&str) -> Option<String>) -> Result<Option<Response>> {
+        let entries = self.entries(&self.path_for(method, url, profile)).await;
+        let entry = entries
+            .into_iter()
+            .find(|e| e.vary.iter().all(|(name, value)| hash_value(header(name).as_deref()) == *value));
+
+        match entry {
+            Some(entry) if self.offline || entry.is_fresh() => {
+                debug!("Serving {} {} from the response cache", method, url);
+                Ok(Some(entry.into_response(url)?))
+            }
+            Some(_) => Ok(None),
+            None if self.offline => Err(anyhow!("{} is not in the response cache (offline mode)", url)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save a response if the cache policy allows it. The body has to be read
+    /// to do that, so the response is handed back rebuilt from the saved copy.
+    pub async fn store(&self, method: &str, url: &str, profile: Option<&str>, response: Response, header: impl Fn(&str) -> Option<String>) -> Result<Response> {
+        let status = response.status();
+        let expires_at = match self.expiry(response.headers()) {
+            Some(expires_at) if status.is_success() => expires_at,
+            _ => return Ok(response),
+        };
+        // "Vary: *" means no request can reuse the response
+        if self.mode == CacheMode::Respect && vary_names(response.headers()).iter().any(|n| n == "*") {
+            return Ok(response);
+        }
+
+        if vary_names(response.headers()).iter().any(|n| PER_USER_HEADERS.contains(&n.as_str())) {
+            return Ok(response);
+        }
+
+        let vary = vary_names(response.headers())
+            .into_iter()
+            .filter(|name| name != "*")
+            .map(|name| {
+                let value = hash_value(header(&name).as_deref());
+                (name, value)
+            })
+            .collect();
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| !SESSION_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let entry = CachedResponse {
+            vary,
+            url: Some(response.url().to_string()),
+            status: status.as_u16(),
+            headers,
+            body: BASE64.encode(response.bytes().await?),
+            stored_at: Utc::now(),
+            expires_at,
+        };
+
+        let path = self.path_for(method, url, profile);
+        let mut entries = self.entries(&path).await;
+        entries.retain(|e| e.vary != entry.vary);
+        entries.push(entry.clone());
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_string(&entries)?).await?;
+
+        entry.into_response(url)
+    }
+
+    /// When a response stops being fresh: None to not store it at all,
+    /// Some(None) for no expiry
+    fn expiry(&self, headers: &HeaderMap) -> Option<Option<DateTime<Utc>>> {
+        let now = Utc::now();
+        if self.mode == CacheMode::Override {
+            return Some(if self.ttl.num_seconds() > 0 { Some(now + self.ttl) } else { None });
+        }
+
+        let directives: Vec<String> = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|d| d.trim().to_ascii_lowercase())
+            .collect();
+        if directives.iter().any(|d| d == "no-store") {
+            return None;
+        }
+        // Stored, but only good for offline replays
+        if directives.iter().any(|d| d == "no-cache") {
+            return Some(Some(now));
+        }
+        if let Some(max_age) = directives
+            .iter()
+            .find_map(|d| d.strip_prefix("max-age="))
+            .and_then(|v| v.parse::<i64>().ok())
+        {
+            return Some(Some(now + Duration::seconds(max_age)));
+        }
+        if let Some(expires) = headers
+            .get(EXPIRES)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        {
+            return Some(Some(expires.with_timezone(&Utc)));
+        }
+        Some(Some(now + self.ttl))
+    }
+}
+
+fn vary_names(headers: &HeaderMap) -> Vec<String> {
+    let mut names: Vec<String> = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn hash_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("{:x}", Sha256::digest(value.as_bytes())),
+        None => String::new(),
+    }
+}
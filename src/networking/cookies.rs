@@ -0,0 +1,332 @@
+// Cookie jar shared by HttpClient and the browser
+// Cookies are kept per domain in memory while a task runs and persisted to
+// Storage under a scope: "global" for cookies every run should see (consent,
+// imported logins) or a task ID when tasks are isolated from each other.
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use reqwest::cookie::CookieStore;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::RwLock;
+use url::Url;
+use crate::Result;
+
+pub const GLOBAL_SCOPE: &str = "global";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    /// Host the cookie belongs to, without a leading dot
+    pub domain: String,
+    /// Also sent to subdomains of `domain`
+    pub include_subdomains: bool,
+    pub path: String,
+    pub name: String,
+    pub value: String,
+    /// Unix timestamp; session cookies have none
+    pub expires: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    pub fn is_expired(&self) -> bool {
+        self.expires.map(|e| e <= Utc::now().timestamp()).unwrap_or(false)
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return false,
+        };
+        let domain_ok = host == self.domain
+            || (self.include_subdomains && host.ends_with(&format!(".{}", self.domain)));
+        let path_ok = path_matches(url.path(), &self.path);
+        let scheme_ok = !self.secure || url.scheme() == "https";
+        domain_ok && path_ok && scheme_ok && !self.is_expired()
+    }
+
+    /// Parse a Set-Cookie header received from `url`
+    pub fn parse_set_cookie(header: &str, url: &Url) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.trim().split_once('=')?;
+        if name.trim().is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            domain: url.host_str()?.to_string(),
+            include_subdomains: false,
+            path: default_path(url),
+            name: name.trim().to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+
+        for attribute in parts {
+            let (key, val) = match attribute.trim().split_once('=') {
+                Some((k, v)) => (k.trim().to_ascii_lowercase(), v.trim()),
+                None => (attribute.trim().to_ascii_lowercase(), ""),
+            };
+            match key.as_str() {
+                "domain" if !val.is_empty() => {
+                    let domain = val.trim_start_matches('.').to_ascii_lowercase();
+                    let host = url.host_str()?.to_ascii_lowercase();
+                    // A page may only set cookies for its own domain or a parent of it
+                    if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                        return None;
+                    }
+                    if is_public_suffix(&domain) {
+                        // RFC 6265 5.3: a public suffix is only accepted as the host itself, host-only
+                        if host != domain {
+                            return None;
+                        }
+                        continue;
+                    }
+                    cookie.domain = domain;
+                    cookie.include_subdomains = true;
+                }
+                "path" if val.starts_with('/') => cookie.path = val.to_string(),
+                // Max-Age wins over Expires when both are present
+                "max-age" => {
+                    if let Ok(seconds) = val.parse::<i64>() {
+                        cookie.expires = Some(Utc::now().timestamp() + seconds);
+                    }
+                }
+                "expires" if cookie.expires.is_none() => {
+                    cookie.expires = DateTime::parse_from_rfc2822(val).ok().map(|d| d.timestamp());
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+}
+
+// Registries that hand out names under themselves to unrelated owners
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "github.io", "gitlab.io", "herokuapp.com", "appspot.com", "blogspot.com", "azurewebsites.net",
+    "cloudfront.net", "netlify.app", "vercel.app", "pages.dev", "workers.dev", "web.app", "firebaseapp.com",
+    "s3.amazonaws.com", "fly.dev", "onrender.com", "wordpress.com", "tumblr.com",
+];
+
+// Second-level labels that country registries sell names under ("co.uk", "com.au")
+const REGISTRY_LABELS: &[&str] = &["co", "com", "net", "org", "ac", "edu", "gov", "gob", "go", "or", "ne", "ltd", "plc", "mil", "nic", "sch"];
+
+/// Whether cookies for `domain` would be shared by unrelated sites. Covers
+/// top-level domains, the common ccTLD second levels and shared hosting
+/// domains rather than the whole public suffix list.
+fn is_public_suffix(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    match labels.as_slice() {
+        [_] => true,
+        [second, tld] => (tld.len() == 2 && REGISTRY_LABELS.contains(second)) || PUBLIC_SUFFIXES.contains(&domain),
+        _ => PUBLIC_SUFFIXES.contains(&domain),
+    }
+}
+
+// Default cookie path per RFC 6265: the request path up to its last '/'
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => url.path()[..i].to_string(),
+    }
+}
+
+// Path-match per RFC 6265 §5.1.4: the cookie path is the request path or a
+// prefix of it that ends at a '/', so "/foo" covers "/foo/bar" but not "/foobar"
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[derive(Debug)]
+pub struct CookieJar {
+    scope: String,
+    cookies: RwLock<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    pub fn new(scope: &str) -> Self {
+        Self::with_cookies(scope, Vec::new())
+    }
+
+    pub fn with_cookies(scope: &str, cookies: Vec<Cookie>) -> Self {
+        Self {
+            scope: scope.to_string(),
+            cookies: RwLock::new(cookies),
+        }
+    }
+
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    /// Add or replace a cookie (same domain, path and name)
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.write().unwrap();
+        cookies.retain(|c| !(c.domain == cookie.domain && c.path == cookie.path && c.name == cookie.name));
+        if !cookie.is_expired() {
+            cookies.push(cookie);
+        }
+    }
+
+    pub fn extend(&self, cookies: impl IntoIterator<Item = Cookie>) {
+        for cookie in cookies {
+            self.insert(cookie);
+        }
+    }
+
+    /// Live cookies, optionally only those for `domain` and its subdomains
+    pub fn list(&self, domain: Option<&str>) -> Vec<Cookie> {
+        self.cookies
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|c| !c.is_expired())
+            .filter(|c| match domain {
+                Some(d) => c.domain == d || c.domain.ends_with(&format!(".{}", d)),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn cookies_for(&self, url: &Url) -> Vec<Cookie> {
+        self.cookies.read().unwrap().iter().filter(|c| c.matches(url)).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            if let Some(cookie) = header.to_str().ok().and_then(|h| Cookie::parse_set_cookie(h, url)) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .cookies_for(url)
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            None
+        } else {
+            HeaderValue::from_str(&header).ok()
+        }
+    }
+}
+
+/// Read cookies exported in Netscape cookies.txt format (curl, wget, browser extensions)
+pub fn parse_netscape<P: AsRef<Path>>(path: P) -> Result<Vec<Cookie>> {
+    let content = std::fs::read_to_string(path.as_ref())
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.as_ref().display(), e))?;
+
+    let mut cookies = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        // curl marks HttpOnly cookies with a prefix on an otherwise commented-looking line
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            return Err(anyhow!("Line {}: expected 7 tab-separated fields, found {}", number + 1, fields.len()));
+        }
+        let expires = fields[4]
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| anyhow!("Line {}: invalid expiry '{}'", number + 1, fields[4]))?;
+
+        cookies.push(Cookie {
+            domain: fields[0].trim().trim_start_matches('.').to_ascii_lowercase(),
+            include_subdomains: fields[1].eq_ignore_ascii_case("TRUE"),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            // 0 means a session cookie
+            expires: if expires == 0 { None } else { Some(expires) },
+            name: fields[5].to_string(),
+            value: fields[6..].join("\t"),
+            http_only,
+        });
+    }
+    Ok(cookies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header: &str, url: &str) -> Option<Cookie> {
+        Cookie::parse_set_cookie(header, &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn domain_cookies_cover_the_site_and_its_subdomains() {
+        let cookie = parse("sid=1; Domain=.example.com; Path=/", "https://www.example.com/login").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.include_subdomains);
+        assert!(cookie.matches(&Url::parse("https://shop.example.com/").unwrap()));
+    }
+
+    #[test]
+    fn cookies_for_other_sites_are_dropped() {
+        assert!(parse("sid=1; Domain=bank.com", "https://evil.com/").is_none());
+        assert!(parse("sid=1; Domain=www.example.com", "https://example.com/").is_none());
+        assert!(parse("sid=1; Domain=notexample.com", "https://example.com/").is_none());
+    }
+
+    #[test]
+    fn public_suffixes_are_rejected() {
+        assert!(parse("sid=1; Domain=com", "https://example.com/").is_none());
+        assert!(parse("sid=1; Domain=co.uk", "https://shop.example.co.uk/").is_none());
+        assert!(parse("sid=1; Domain=github.io", "https://someone.github.io/").is_none());
+        // The suffix itself may still set a cookie for just its own host
+        let cookie = parse("sid=1; Domain=github.io", "https://github.io/").unwrap();
+        assert!(!cookie.include_subdomains);
+    }
+
+    #[test]
+    fn cookies_without_a_domain_are_host_only() {
+        let cookie = parse("sid=1", "https://www.example.com/a/b").unwrap();
+        assert_eq!(cookie.domain, "www.example.com");
+        assert!(!cookie.include_subdomains);
+        assert_eq!(cookie.path, "/a");
+    }
+
+    #[test]
+    fn paths_match_on_segment_boundaries() {
+        let cookie = parse("sid=1; Path=/foo", "https://example.com/").unwrap();
+        let matches = |url: &str| cookie.matches(&Url::parse(url).unwrap());
+        assert!(matches("https://example.com/foo"));
+        assert!(matches("https://example.com/foo/bar"));
+        assert!(!matches("https://example.com/foobar"));
+        assert!(!matches("https://example.com/"));
+
+        let root = parse("sid=1; Path=/", "https://example.com/").unwrap();
+        assert!(root.matches(&Url::parse("https://example.com/anything").unwrap()));
+    }
+}
@@ -1,11 +1,31 @@
-use reqwest::{Client, Response, RequestBuilder};
+use anyhow::anyhow;
+use reqwest::cookie::CookieStore;
+use reqwest::header::{AUTHORIZATION, IF_MODIFIED_SINCE, IF_NONE_MATCH, LOCATION};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Identity, Method, Request, Response, RequestBuilder, StatusCode};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use crate::Result;
+use crate::data::vault::{profile_for, AuthMethod, AuthProfile};
+use crate::data::{PageSnapshot, SnapshotStore};
+use super::cache::ResponseCache;
+use super::cookies::CookieJar;
+use super::warc::{ArchivedRequest, WarcWriter};
+
+// Same limit reqwest applies when it follows redirects itself
+const MAX_REDIRECTS: usize = 10;
 
 pub struct HttpClient {
     client: Client,
     headers: HashMap<String, String>,
+    snapshots: Option<Arc<SnapshotStore>>,
+    cookies: Option<Arc<CookieJar>>,
+    auth: Vec<AuthProfile>,
+    // Client certificates are per connection, so those domains get their own client
+    cert_clients: HashMap<String, Client>,
+    cache: Option<Arc<ResponseCache>>,
+    archive: Option<Arc<WarcWriter>>,
 }
 
 impl HttpClient {
@@ -19,18 +39,222 @@ impl HttpClient {
         headers.insert("User-Agent".to_string(), 
             "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36".to_string());
 
-        Self { client, headers }
+        Self {
+            client,
+            headers,
+            snapshots: None,
+            cookies: None,
+            auth: Vec::new(),
+            cert_clients: HashMap::new(),
+            cache: None,
+            archive: None,
+        }
     }
 
-    pub async fn get(&self, url: &str) -> Result<Response> {
-        let mut request = self.client.get(url);
+    /// Send and remember cookies through `jar` instead of starting each request without any
+    pub fn with_cookies(mut self, jar: Arc<CookieJar>) -> Self {
+        self.cookies = Some(jar);
+        self.client = self.build_client(None).expect("Failed to create HTTP client");
+        self.cert_clients = self.build_cert_clients().expect("Client certificates were already validated");
+        self
+    }
+
+    /// Authenticate requests to domains that have a profile. Form logins happen
+    /// in the browser and reach us through the shared cookie jar.
+    pub fn with_auth(mut self, profiles: Vec<AuthProfile>) -> Result<Self> {
+        self.auth = profiles;
+        self.cert_clients = self.build_cert_clients()?;
+        Ok(self)
+    }
+
+    fn build_client(&self, identity: Option<Identity>) -> Result<Client> {
+        let mut builder = Client::builder().timeout(Duration::from_secs(30)).redirect(self.redirect_policy());
+        if let Some(jar) = &self.cookies {
+            builder = builder.cookie_provider(jar.clone());
+        }
+        if let Some(identity) = identity {
+            builder = builder.identity(identity);
+        }
+        Ok(builder.build()?)
+    }
+
+    fn build_cert_clients(&self) -> Result<HashMap<String, Client>> {
+        let mut clients = HashMap::new();
+        for profile in &self.auth {
+            if let AuthMethod::ClientCert { identity_pem } = &profile.method {
+                // The parse error could quote the PEM, so it's replaced rather than wrapped
+                let identity = Identity::from_pem(identity_pem.expose().as_bytes())
+                    .map_err(|_| anyhow!("Client certificate for {} is not a valid PEM identity", profile.domain))?;
+                clients.insert(profile.domain.clone(), self.build_client(Some(identity))?);
+            }
+        }
+        Ok(clients)
+    }
+
+    fn profile_for(&self, url: &str) -> Option<&AuthProfile> {
+        let host = url::Url::parse(url).ok()?.host_str()?.to_string();
+        profile_for(&self.auth, &host)
+    }
+
+    // Credentials differ per profile; the profile's kind and domain are enough to tell them apart
+    fn profile_name(&self, url: &str) -> Option<String> {
+        self.profile_for(url).map(|p| format!("{}:{}", p.method.kind(), p.domain))
+    }
+
+    fn client_for(&self, url: &str) -> &Client {
+        self.profile_for(url)
+            .and_then(|p| self.cert_clients.get(&p.domain))
+            .unwrap_or(&self.client)
+    }
+
+    fn authorize(&self, request: RequestBuilder, url: &str) -> RequestBuilder {
+        // Credentials are never sent in the clear, even if a profile matches
+        let secure = url::Url::parse(url).map(|u| u.scheme() == "https").unwrap_or(false);
+        if !secure {
+            return request;
+        }
+        match self.profile_for(url).map(|p| &p.method) {
+            Some(AuthMethod::Basic { username, password }) => request.basic_auth(username, Some(password.expose())),
+            Some(AuthMethod::Bearer { token }) => request.bearer_auth(token.expose()),
+            _ => request,
+        }
+    }
+
+    /// Save the raw HTML of pages fetched with `get_html`
+    pub fn with_snapshots(mut self, store: Arc<SnapshotStore>) -> Self {
+        self.snapshots = Some(store);
+        self
+    }
+
+    pub fn snapshots(&self) -> Option<&Arc<SnapshotStore>> {
+        self.snapshots.as_ref()
+    }
+
+    /// Serve and save GET responses through an on-disk cache
+    pub fn with_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Write every request and response sent over the network to WARC files,
+    /// redirects included
+    pub fn with_archive(mut self, archive: Arc<WarcWriter>) -> Self {
+        self.archive = Some(archive);
+        self.client = self.build_client(None).expect("Failed to create HTTP client");
+        self.cert_clients = self.build_cert_clients().expect("Client certificates were already validated");
+        self
+    }
+
+    // Archiving clients follow redirects themselves, so every hop gets recorded
+    fn redirect_policy(&self) -> Policy {
+        match self.archive {
+            Some(_) => Policy::none(),
+            None => Policy::default(),
+        }
+    }
+
+    /// True when responses may only come from the cache
+    pub fn offline(&self) -> bool {
+        self.cache.as_ref().map(|c| c.offline()).unwrap_or(false)
+    }
+
+    // Value this client sends for a request header, for matching Vary
+    fn header_value(&self, url: &str, name: &str) -> Option<String> {
+        match name {
+            "cookie" => {
+                let url = url::Url::parse(url).ok()?;
+                let header = self.cookies.as_ref()?.cookies(&url)?;
+                header.to_str().ok().map(str::to_string)
+            }
+            "authorization" => self.profile_name(url),
+            _ => self
+                .headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    async fn send(&self, url: &str, request: RequestBuilder) -> Result<Response> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.execute(request).await,
+        };
+        // Responses to authenticated requests are cached apart from everyone else's
+        let profile = self.profile_name(url);
+        let header = |name: &str| self.header_value(url, name);
+        if let Some(response) = cache.lookup("GET", url, profile.as_deref(), header).await? {
+            return Ok(response);
+        }
+        let response = self.execute(request).await?;
+        cache.store("GET", url, profile.as_deref(), response, header).await
+    }
+
+    // Cache hits never reach this, so the archive only holds real exchanges
+    async fn execute(&self, request: RequestBuilder) -> Result<Response> {
+        let archive = match &self.archive {
+            Some(archive) => archive,
+            None => return Ok(request.send().await?),
+        };
+        let (client, request) = request.build_split();
+        let mut request = request?;
+        for _ in 0..=MAX_REDIRECTS {
+            let archived = ArchivedRequest::capture(&request);
+            let next = request.try_clone();
+            let response = client.execute(request).await?;
+            let redirect = match (next, redirect_target(&response)) {
+                (Some(next), Some(target)) => Some(follow(next, response.status(), target)),
+                _ => None,
+            };
+            let response = archive.record_exchange(archived, response).await?;
+            match redirect {
+                Some(next) => request = next,
+                None => return Ok(response),
+            }
+        }
+        Err(anyhow!("Too many redirects"))
+    }
+
+    /// Fetch a page's body, keeping a snapshot of it when snapshots are enabled
+    pub async fn get_html(&self, url: &str) -> Result<(String, Option<PageSnapshot>)> {
+        let html = self.get(url).await?.error_for_status()?.text().await?;
+        let snapshot = self.snapshot_html(url, &html).await?;
+        Ok((html, snapshot))
+    }
+
+    /// Snapshot HTML that was fetched some other way
+    pub async fn snapshot_html(&self, url: &str, html: &str) -> Result<Option<PageSnapshot>> {
+        match &self.snapshots {
+            Some(store) => Ok(Some(store.capture(url, Some(html), None).await?)),
+            None => Ok(None),
+        }
+    }
+
+    fn request(&self, url: &str) -> RequestBuilder {
+        let mut request = self.authorize(self.client_for(url).get(url), url);
         
         for (key, value) in &self.headers {
             request = request.header(key, value);
         }
+        request
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Response> {
+        self.send(url, self.request(url)).await
+    }
+
+    /// GET with the validators from an earlier fetch; the server answers
+    /// 304 Not Modified when the page hasn't changed since
+    pub async fn get_if_modified(&self, url: &str, etag: Option<&str>, last_modified: Option<&str>) -> Result<Response> {
+        let mut request = self.request(url);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
 
-        let response = request.send().await?;
-        Ok(response)
+        self.send(url, request).await
     }
 
     pub async fn get_with_proxy(&self, url: &str, proxy: &str) -> Result<Response> {
@@ -38,18 +262,44 @@ impl HttpClient {
         let client = Client::builder()
             .proxy(proxy)
             .timeout(Duration::from_secs(30))
+            .redirect(self.redirect_policy())
             .build()?;
 
-        let mut request = client.get(url);
+        let mut request = self.authorize(client.get(url), url);
         for (key, value) in &self.headers {
             request = request.header(key, value);
         }
 
-        let response = request.send().await?;
-        Ok(response)
+        self.execute(request).await
     }
 
     pub fn set_header(&mut self, key: String, value: String) {
         self.headers.insert(key, value);
     }
 }
+
+/// Where a redirect response points, resolved against the URL it came from
+fn redirect_target(response: &Response) -> Option<url::Url> {
+    if !matches!(response.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    response.url().join(location).ok()
+}
+
+/// The request a redirect leads to, following the rules reqwest uses: 303
+/// (and 301/302 after a POST) turns into a body-less GET, and credentials
+/// are not passed on to another host or over plain http
+fn follow(mut request: Request, status: StatusCode, target: url::Url) -> Request {
+    let becomes_get = status == StatusCode::SEE_OTHER
+        || (matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND) && request.method() == Method::POST);
+    if becomes_get {
+        *request.method_mut() = Method::GET;
+        *request.body_mut() = None;
+    }
+    if request.url().host_str() != target.host_str() || target.scheme() != "https" {
+        request.headers_mut().remove(AUTHORIZATION);
+    }
+    *request.url_mut() = target;
+    request
+}
@@ -1,10 +1,16 @@
 // Networking module for Flash AI
 // Handles HTTP requests, proxy management, and stealth features
 
+pub mod cache;
+pub mod cookies;
 pub mod http_client;
 pub mod proxy_manager;
 pub mod stealth;
+pub mod warc;
 
+pub use cache::ResponseCache;
+pub use cookies::{Cookie, CookieJar};
 pub use http_client::HttpClient;
 pub use proxy_manager::ProxyManager;
 pub use stealth::StealthMode;
+pub use warc::WarcWriter;
@@ -0,0 +1,354 @@
+// WARC archives of everything fetched
+// Every HTTP exchange goes into WARC/1.1 files as a request/response record
+// pair, and every DOM the browser renders goes in as a resource record. A new
+// file is started once the current one reaches the size limit; with
+// compression on each record is its own gzip member, as .warc.gz readers
+// expect. Bodies are stored as reqwest hands them to us, i.e. with any
+// Content-Encoding already undone. Credentials in request headers are redacted.
+
+use anyhow::anyhow;
+use chrono::Utc;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, PROXY_AUTHORIZATION, TRANSFER_ENCODING};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::info;
+use crate::Result;
+use crate::engine::config::WarcConfig;
+
+const REDACTED: &str = "[redacted]";
+
+struct OpenFile {
+    file: tokio::fs::File,
+    written: u64,
+}
+
+struct WriterState {
+    current: Option<OpenFile>,
+    // Files started by this writer, so names stay unique within a second
+    sequence: u32,
+}
+
+pub struct WarcWriter {
+    directory: PathBuf,
+    max_file_bytes: u64,
+    compress: bool,
+    state: Mutex<WriterState>,
+}
+
+/// What goes into a request record, taken before the request is sent
+pub struct ArchivedRequest {
+    target: String,
+    block: Vec<u8>,
+}
+
+impl ArchivedRequest {
+    pub fn capture(request: &Request) -> Self {
+        let url = request.url();
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        let mut block = format!("{} {} HTTP/1.1\r\n", request.method(), path).into_bytes();
+        if let Some(host) = url.host_str() {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            };
+            block.extend_from_slice(format!("Host: {}\r\n", host).as_bytes());
+        }
+        for (name, value) in request.headers() {
+            block.extend_from_slice(name.as_str().as_bytes());
+            block.extend_from_slice(b": ");
+            if name == AUTHORIZATION || name == PROXY_AUTHORIZATION || name == COOKIE {
+                block.extend_from_slice(REDACTED.as_bytes());
+            } else {
+                block.extend_from_slice(value.as_bytes());
+            }
+            block.extend_from_slice(b"\r\n");
+        }
+        block.extend_from_slice(b"\r\n");
+        if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+            block.extend_from_slice(body);
+        }
+
+        Self { target: url.to_string(), block }
+    }
+}
+
+impl WarcWriter {
+    /// Archive under `base_dir` (the output directory) unless the configured directory is absolute
+    pub fn new(config: &WarcConfig, base_dir: &str) -> Self {
+        Self {
+            directory: Path::new(base_dir).join(&config.directory),
+            max_file_bytes: config.max_file_mb.max(1) * 1024 * 1024,
+            compress: config.compress,
+            state: Mutex::new(WriterState { current: None, sequence: 0 }),
+        }
+    }
+
+    /// Write the request and its response. The body has to be read to do
+    /// that, so the response is handed back rebuilt from the archived copy.
+    pub async fn record_exchange(&self, request: ArchivedRequest, response: Response) -> Result<Response> {
+        let status = response.status();
+        let version = response.version();
+        let url = response.url().clone();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+
+        let mut block = format!("{:?} {} {}\r\n", version, status.as_u16(), status.canonical_reason().unwrap_or("")).into_bytes();
+        for (name, value) in &headers {
+            // The body we have is no longer chunked
+            if name == TRANSFER_ENCODING {
+                continue;
+            }
+            block.extend_from_slice(name.as_str().as_bytes());
+            block.extend_from_slice(b": ");
+            block.extend_from_slice(value.as_bytes());
+            block.extend_from_slice(b"\r\n");
+        }
+        block.extend_from_slice(b"\r\n");
+        block.extend_from_slice(&body);
+
+        let response_id = record_id();
+        let records = [
+            record("response", &response_id, Some(&request.target), "application/http;msgtype=response", &block, &[]),
+            record("request", &record_id(), Some(&request.target), "application/http;msgtype=request", &request.block, &[("WARC-Concurrent-To", response_id.as_str())]),
+        ];
+        self.write(&records).await?;
+
+        let mut builder = http::Response::builder().status(status).version(version).url(url);
+        if let Some(map) = builder.headers_mut() {
+            *map = headers;
+        }
+        Ok(Response::from(builder.body(body)?))
+    }
+
+    /// Write a DOM taken from the browser
+    pub async fn record_rendered(&self, url: &str, html: &str) -> Result<()> {
+        let rendered = record("resource", &record_id(), Some(url), "text/html; charset=utf-8", html.as_bytes(), &[]);
+        self.write(&[rendered]).await
+    }
+
+    // Records passed together always land in the same file
+    async fn write(&self, records: &[Vec<u8>]) -> Result<()> {
+        let records = records
+            .iter()
+            .map(|r| self.encode(r))
+            .collect::<Result<Vec<_>>>()?;
+        let size: u64 = records.iter().map(|r| r.len() as u64).sum();
+
+        let mut state = self.state.lock().await;
+        let full = state
+            .current
+            .as_ref()
+            .map(|c| c.written > 0 && c.written + size > self.max_file_bytes)
+            .unwrap_or(true);
+        if full {
+            state.sequence += 1;
+            let opened = self.open(state.sequence).await?;
+            state.current = Some(opened);
+        }
+        let current = state.current.as_mut().expect("opened above");
+        for record in &records {
+            current.file.write_all(record).await?;
+        }
+        current.file.flush().await?;
+        current.written += size;
+        Ok(())
+    }
+
+    async fn open(&self, sequence: u32) -> Result<OpenFile> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let name = format!("flash-{}-{:05}.{}",
+                           Utc::now().format("%Y%m%d%H%M%S"),
+                           sequence,
+                           if self.compress { "warc.gz" } else { "warc" });
+        let path = self.directory.join(&name);
+        info!("Writing WARC records to {}", path.display());
+
+        let mut file = tokio::fs::File::create(&path).await?;
+        let fields = format!("software: flash-ai/{}\r\nformat: WARC File Format 1.1\r\n", env!("CARGO_PKG_VERSION"));
+        let warcinfo = self.encode(&record("warcinfo", &record_id(), None, "application/warc-fields", fields.as_bytes(), &[("WARC-Filename", name.as_str())]))?;
+        file.write_all(&warcinfo).await?;
+        Ok(OpenFile { file, written: warcinfo.len() as u64 })
+    }
+
+    fn encode(&self, record: &[u8]) -> Result<Vec<u8>> {
+        if !self.compress {
+            return Ok(record.to_vec());
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(record)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", uuid::Uuid::new_v4())
+}
+
+fn record(kind: &str, id: &str, target: Option<&str>, content_type: &str, block: &[u8], extra: &[(&str, &str)]) -> Vec<u8> {
+    let mut head = format!("WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n",
+                           kind,
+                           id,
+                           Utc::now().format("%Y-%m-%dT%H:%M:%SZ"));
+    if let Some(target) = target {
+        head.push_str(&format!("WARC-Target-URI: {}\r\n", target));
+    }
+    for (name, value) in extra {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Type: {}\r\nWARC-Block-Digest: sha256:{:x}\r\nContent-Length: {}\r\n\r\n",
+                           content_type,
+                           Sha256::digest(block),
+                           block.len()));
+
+    let mut bytes = head.into_bytes();
+    bytes.extend_from_slice(block);
+    bytes.extend_from_slice(b"\r\n\r\n");
+    bytes
+}
+
+#[derive(Debug, Clone)]
+pub struct WarcRecord {
+    pub kind: String,
+    pub headers: Vec<(String, String)>,
+    pub block: Vec<u8>,
+}
+
+/// An HTML page captured in an archive
+#[derive(Debug, Clone)]
+pub struct ArchivedPage {
+    pub url: String,
+    pub html: String,
+    /// Taken from the browser rather than an HTTP response
+    pub rendered: bool,
+}
+
+impl WarcRecord {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The page in this record: a successful HTML response or a rendered DOM
+    pub fn page(&self) -> Option<ArchivedPage> {
+        let url = self.header("WARC-Target-URI")?.to_string();
+        match self.kind.as_str() {
+            "resource" if self.header("Content-Type")?.contains("html") => Some(ArchivedPage {
+                url,
+                html: String::from_utf8_lossy(&self.block).into_owned(),
+                rendered: true,
+            }),
+            "response" => {
+                let end = find(&self.block, b"\r\n\r\n")?;
+                let head = String::from_utf8_lossy(&self.block[..end]);
+                let mut lines = head.split("\r\n");
+                let status: u16 = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+                let html = lines
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+                    .map(|(_, value)| value.contains("html"))
+                    .unwrap_or(true);
+                if !(200..300).contains(&status) || !html {
+                    return None;
+                }
+                Some(ArchivedPage {
+                    url,
+                    html: String::from_utf8_lossy(&self.block[end + 4..]).into_owned(),
+                    rendered: false,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Every record in a .warc or .warc.gz file
+pub async fn read_warc(path: &Path) -> Result<Vec<WarcRecord>> {
+    let bytes = tokio::fs::read(path).await?;
+    let data = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut data = Vec::new();
+        MultiGzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut data)
+            .map_err(|e| anyhow!("Failed to unpack {}: {}", path.display(), e))?;
+        data
+    } else {
+        bytes
+    };
+    parse_records(&data).map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+fn parse_records(mut data: &[u8]) -> Result<Vec<WarcRecord>> {
+    let mut records = Vec::new();
+    loop {
+        while data.starts_with(b"\r\n") {
+            data = &data[2..];
+        }
+        if data.is_empty() {
+            return Ok(records);
+        }
+
+        let end = find(data, b"\r\n\r\n").ok_or_else(|| anyhow!("Truncated record header"))?;
+        let head = std::str::from_utf8(&data[..end])?;
+        let mut lines = head.split("\r\n");
+        if !lines.next().map(|l| l.starts_with("WARC/")).unwrap_or(false) {
+            return Err(anyhow!("Not a WARC record"));
+        }
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let length: usize = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, value)| value.parse().ok())
+            .ok_or_else(|| anyhow!("Record without a Content-Length"))?;
+
+        let start = end + 4;
+        let block = data
+            .get(start..start + length)
+            .ok_or_else(|| anyhow!("Truncated record block"))?;
+        let kind = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("WARC-Type"))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        records.push(WarcRecord { kind, headers, block: block.to_vec() });
+        data = &data[start + length..];
+    }
+}
+
+/// The pages in `records`, one per URL. A rendered DOM wins over the HTTP
+/// response for the same URL, since that's what extraction ran on; otherwise
+/// the latest capture wins.
+pub fn archived_pages(records: &[WarcRecord]) -> Vec<ArchivedPage> {
+    let mut pages: Vec<ArchivedPage> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for page in records.iter().filter_map(WarcRecord::page) {
+        match index.get(&page.url) {
+            Some(&i) if pages[i].rendered && !page.rendered => {}
+            Some(&i) => pages[i] = page,
+            None => {
+                index.insert(page.url.clone(), pages.len());
+                pages.push(page);
+            }
+        }
+    }
+    pages
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
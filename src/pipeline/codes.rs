@@ -0,0 +1,341 @@
+// Country and region code tables
+// Every ISO 3166-1 country with its alpha-2/alpha-3 codes, E.164 calling code
+// and common English names, and ISO 3166-2 subdivisions for the countries
+// whose regions we can check. Unknown names are flagged rather than guessed.
+
+/// (alpha-2, alpha-3, calling code, names)
+const COUNTRIES: &[(&str, &str, &str, &[&str])] = &[
+    ("AD", "AND", "376", &["andorra"]),
+    ("AE", "ARE", "971", &["united arab emirates", "uae"]),
+    ("AF", "AFG", "93", &["afghanistan"]),
+    ("AG", "ATG", "1", &["antigua and barbuda"]),
+    ("AI", "AIA", "1", &["anguilla"]),
+    ("AL", "ALB", "355", &["albania"]),
+    ("AM", "ARM", "374", &["armenia"]),
+    ("AO", "AGO", "244", &["angola"]),
+    ("AQ", "ATA", "672", &["antarctica"]),
+    ("AR", "ARG", "54", &["argentina"]),
+    ("AS", "ASM", "1", &["american samoa"]),
+    ("AT", "AUT", "43", &["austria"]),
+    ("AU", "AUS", "61", &["australia"]),
+    ("AW", "ABW", "297", &["aruba"]),
+    ("AX", "ALA", "358", &["åland islands", "aland islands"]),
+    ("AZ", "AZE", "994", &["azerbaijan"]),
+    ("BA", "BIH", "387", &["bosnia and herzegovina"]),
+    ("BB", "BRB", "1", &["barbados"]),
+    ("BD", "BGD", "880", &["bangladesh"]),
+    ("BE", "BEL", "32", &["belgium"]),
+    ("BF", "BFA", "226", &["burkina faso"]),
+    ("BG", "BGR", "359", &["bulgaria"]),
+    ("BH", "BHR", "973", &["bahrain"]),
+    ("BI", "BDI", "257", &["burundi"]),
+    ("BJ", "BEN", "229", &["benin"]),
+    ("BL", "BLM", "590", &["saint barthélemy", "saint barthelemy"]),
+    ("BM", "BMU", "1", &["bermuda"]),
+    ("BN", "BRN", "673", &["brunei", "brunei darussalam"]),
+    ("BO", "BOL", "591", &["bolivia"]),
+    ("BQ", "BES", "599", &["caribbean netherlands", "bonaire, sint eustatius and saba"]),
+    ("BR", "BRA", "55", &["brazil", "brasil"]),
+    ("BS", "BHS", "1", &["bahamas", "the bahamas"]),
+    ("BT", "BTN", "975", &["bhutan"]),
+    ("BV", "BVT", "47", &["bouvet island"]),
+    ("BW", "BWA", "267", &["botswana"]),
+    ("BY", "BLR", "375", &["belarus"]),
+    ("BZ", "BLZ", "501", &["belize"]),
+    ("CA", "CAN", "1", &["canada"]),
+    ("CC", "CCK", "61", &["cocos (keeling) islands", "cocos islands"]),
+    ("CD", "COD", "243", &["democratic republic of the congo", "dr congo", "congo-kinshasa"]),
+    ("CF", "CAF", "236", &["central african republic"]),
+    ("CG", "COG", "242", &["republic of the congo", "congo", "congo-brazzaville"]),
+    ("CH", "CHE", "41", &["switzerland"]),
+    ("CI", "CIV", "225", &["côte d'ivoire", "cote d'ivoire", "ivory coast"]),
+    ("CK", "COK", "682", &["cook islands"]),
+    ("CL", "CHL", "56", &["chile"]),
+    ("CM", "CMR", "237", &["cameroon"]),
+    ("CN", "CHN", "86", &["china", "people's republic of china", "prc"]),
+    ("CO", "COL", "57", &["colombia"]),
+    ("CR", "CRI", "506", &["costa rica"]),
+    ("CU", "CUB", "53", &["cuba"]),
+    ("CV", "CPV", "238", &["cabo verde", "cape verde"]),
+    ("CW", "CUW", "599", &["curaçao", "curacao"]),
+    ("CX", "CXR", "61", &["christmas island"]),
+    ("CY", "CYP", "357", &["cyprus"]),
+    ("CZ", "CZE", "420", &["czech republic", "czechia"]),
+    ("DE", "DEU", "49", &["germany", "deutschland"]),
+    ("DJ", "DJI", "253", &["djibouti"]),
+    ("DK", "DNK", "45", &["denmark"]),
+    ("DM", "DMA", "1", &["dominica"]),
+    ("DO", "DOM", "1", &["dominican republic"]),
+    ("DZ", "DZA", "213", &["algeria"]),
+    ("EC", "ECU", "593", &["ecuador"]),
+    ("EE", "EST", "372", &["estonia"]),
+    ("EG", "EGY", "20", &["egypt"]),
+    ("EH", "ESH", "212", &["western sahara"]),
+    ("ER", "ERI", "291", &["eritrea"]),
+    ("ES", "ESP", "34", &["spain", "españa"]),
+    ("ET", "ETH", "251", &["ethiopia"]),
+    ("FI", "FIN", "358", &["finland"]),
+    ("FJ", "FJI", "679", &["fiji"]),
+    ("FK", "FLK", "500", &["falkland islands"]),
+    ("FM", "FSM", "691", &["micronesia", "federated states of micronesia"]),
+    ("FO", "FRO", "298", &["faroe islands"]),
+    ("FR", "FRA", "33", &["france"]),
+    ("GA", "GAB", "241", &["gabon"]),
+    ("GB", "GBR", "44", &["united kingdom", "uk", "great britain", "england", "scotland", "wales", "northern ireland"]),
+    ("GD", "GRD", "1", &["grenada"]),
+    ("GE", "GEO", "995", &["georgia"]),
+    ("GF", "GUF", "594", &["french guiana"]),
+    ("GG", "GGY", "44", &["guernsey"]),
+    ("GH", "GHA", "233", &["ghana"]),
+    ("GI", "GIB", "350", &["gibraltar"]),
+    ("GL", "GRL", "299", &["greenland"]),
+    ("GM", "GMB", "220", &["gambia", "the gambia"]),
+    ("GN", "GIN", "224", &["guinea"]),
+    ("GP", "GLP", "590", &["guadeloupe"]),
+    ("GQ", "GNQ", "240", &["equatorial guinea"]),
+    ("GR", "GRC", "30", &["greece"]),
+    ("GS", "SGS", "500", &["south georgia and the south sandwich islands"]),
+    ("GT", "GTM", "502", &["guatemala"]),
+    ("GU", "GUM", "1", &["guam"]),
+    ("GW", "GNB", "245", &["guinea-bissau"]),
+    ("GY", "GUY", "592", &["guyana"]),
+    ("HK", "HKG", "852", &["hong kong"]),
+    ("HM", "HMD", "672", &["heard island and mcdonald islands"]),
+    ("HN", "HND", "504", &["honduras"]),
+    ("HR", "HRV", "385", &["croatia"]),
+    ("HT", "HTI", "509", &["haiti"]),
+    ("HU", "HUN", "36", &["hungary"]),
+    ("ID", "IDN", "62", &["indonesia"]),
+    ("IE", "IRL", "353", &["ireland", "republic of ireland"]),
+    ("IL", "ISR", "972", &["israel"]),
+    ("IM", "IMN", "44", &["isle of man"]),
+    ("IN", "IND", "91", &["india"]),
+    ("IO", "IOT", "246", &["british indian ocean territory"]),
+    ("IQ", "IRQ", "964", &["iraq"]),
+    ("IR", "IRN", "98", &["iran"]),
+    ("IS", "ISL", "354", &["iceland"]),
+    ("IT", "ITA", "39", &["italy", "italia"]),
+    ("JE", "JEY", "44", &["jersey"]),
+    ("JM", "JAM", "1", &["jamaica"]),
+    ("JO", "JOR", "962", &["jordan"]),
+    ("JP", "JPN", "81", &["japan"]),
+    ("KE", "KEN", "254", &["kenya"]),
+    ("KG", "KGZ", "996", &["kyrgyzstan"]),
+    ("KH", "KHM", "855", &["cambodia"]),
+    ("KI", "KIR", "686", &["kiribati"]),
+    ("KM", "COM", "269", &["comoros"]),
+    ("KN", "KNA", "1", &["saint kitts and nevis"]),
+    ("KP", "PRK", "850", &["north korea", "democratic people's republic of korea"]),
+    ("KR", "KOR", "82", &["south korea", "korea", "republic of korea"]),
+    ("KW", "KWT", "965", &["kuwait"]),
+    ("KY", "CYM", "1", &["cayman islands"]),
+    ("KZ", "KAZ", "7", &["kazakhstan"]),
+    ("LA", "LAO", "856", &["laos", "lao people's democratic republic"]),
+    ("LB", "LBN", "961", &["lebanon"]),
+    ("LC", "LCA", "1", &["saint lucia"]),
+    ("LI", "LIE", "423", &["liechtenstein"]),
+    ("LK", "LKA", "94", &["sri lanka"]),
+    ("LR", "LBR", "231", &["liberia"]),
+    ("LS", "LSO", "266", &["lesotho"]),
+    ("LT", "LTU", "370", &["lithuania"]),
+    ("LU", "LUX", "352", &["luxembourg"]),
+    ("LV", "LVA", "371", &["latvia"]),
+    ("LY", "LBY", "218", &["libya"]),
+    ("MA", "MAR", "212", &["morocco"]),
+    ("MC", "MCO", "377", &["monaco"]),
+    ("MD", "MDA", "373", &["moldova"]),
+    ("ME", "MNE", "382", &["montenegro"]),
+    ("MF", "MAF", "590", &["saint martin"]),
+    ("MG", "MDG", "261", &["madagascar"]),
+    ("MH", "MHL", "692", &["marshall islands"]),
+    ("MK", "MKD", "389", &["north macedonia", "macedonia"]),
+    ("ML", "MLI", "223", &["mali"]),
+    ("MM", "MMR", "95", &["myanmar", "burma"]),
+    ("MN", "MNG", "976", &["mongolia"]),
+    ("MO", "MAC", "853", &["macau", "macao"]),
+    ("MP", "MNP", "1", &["northern mariana islands"]),
+    ("MQ", "MTQ", "596", &["martinique"]),
+    ("MR", "MRT", "222", &["mauritania"]),
+    ("MS", "MSR", "1", &["montserrat"]),
+    ("MT", "MLT", "356", &["malta"]),
+    ("MU", "MUS", "230", &["mauritius"]),
+    ("MV", "MDV", "960", &["maldives"]),
+    ("MW", "MWI", "265", &["malawi"]),
+    ("MX", "MEX", "52", &["mexico", "méxico"]),
+    ("MY", "MYS", "60", &["malaysia"]),
+    ("MZ", "MOZ", "258", &["mozambique"]),
+    ("NA", "NAM", "264", &["namibia"]),
+    ("NC", "NCL", "687", &["new caledonia"]),
+    ("NE", "NER", "227", &["niger"]),
+    ("NF", "NFK", "672", &["norfolk island"]),
+    ("NG", "NGA", "234", &["nigeria"]),
+    ("NI", "NIC", "505", &["nicaragua"]),
+    ("NL", "NLD", "31", &["netherlands", "the netherlands", "holland"]),
+    ("NO", "NOR", "47", &["norway"]),
+    ("NP", "NPL", "977", &["nepal"]),
+    ("NR", "NRU", "674", &["nauru"]),
+    ("NU", "NIU", "683", &["niue"]),
+    ("NZ", "NZL", "64", &["new zealand"]),
+    ("OM", "OMN", "968", &["oman"]),
+    ("PA", "PAN", "507", &["panama"]),
+    ("PE", "PER", "51", &["peru"]),
+    ("PF", "PYF", "689", &["french polynesia"]),
+    ("PG", "PNG", "675", &["papua new guinea"]),
+    ("PH", "PHL", "63", &["philippines"]),
+    ("PK", "PAK", "92", &["pakistan"]),
+    ("PL", "POL", "48", &["poland"]),
+    ("PM", "SPM", "508", &["saint pierre and miquelon"]),
+    ("PN", "PCN", "64", &["pitcairn islands", "pitcairn"]),
+    ("PR", "PRI", "1", &["puerto rico"]),
+    ("PS", "PSE", "970", &["palestine", "state of palestine"]),
+    ("PT", "PRT", "351", &["portugal"]),
+    ("PW", "PLW", "680", &["palau"]),
+    ("PY", "PRY", "595", &["paraguay"]),
+    ("QA", "QAT", "974", &["qatar"]),
+    ("RE", "REU", "262", &["réunion", "reunion"]),
+    ("RO", "ROU", "40", &["romania"]),
+    ("RS", "SRB", "381", &["serbia"]),
+    ("RU", "RUS", "7", &["russia", "russian federation"]),
+    ("RW", "RWA", "250", &["rwanda"]),
+    ("SA", "SAU", "966", &["saudi arabia"]),
+    ("SB", "SLB", "677", &["solomon islands"]),
+    ("SC", "SYC", "248", &["seychelles"]),
+    ("SD", "SDN", "249", &["sudan"]),
+    ("SE", "SWE", "46", &["sweden"]),
+    ("SG", "SGP", "65", &["singapore"]),
+    ("SH", "SHN", "290", &["saint helena", "saint helena, ascension and tristan da cunha"]),
+    ("SI", "SVN", "386", &["slovenia"]),
+    ("SJ", "SJM", "47", &["svalbard and jan mayen"]),
+    ("SK", "SVK", "421", &["slovakia"]),
+    ("SL", "SLE", "232", &["sierra leone"]),
+    ("SM", "SMR", "378", &["san marino"]),
+    ("SN", "SEN", "221", &["senegal"]),
+    ("SO", "SOM", "252", &["somalia"]),
+    ("SR", "SUR", "597", &["suriname"]),
+    ("SS", "SSD", "211", &["south sudan"]),
+    ("ST", "STP", "239", &["são tomé and príncipe", "sao tome and principe"]),
+    ("SV", "SLV", "503", &["el salvador"]),
+    ("SX", "SXM", "1", &["sint maarten"]),
+    ("SY", "SYR", "963", &["syria"]),
+    ("SZ", "SWZ", "268", &["eswatini", "swaziland"]),
+    ("TC", "TCA", "1", &["turks and caicos islands"]),
+    ("TD", "TCD", "235", &["chad"]),
+    ("TF", "ATF", "262", &["french southern territories"]),
+    ("TG", "TGO", "228", &["togo"]),
+    ("TH", "THA", "66", &["thailand"]),
+    ("TJ", "TJK", "992", &["tajikistan"]),
+    ("TK", "TKL", "690", &["tokelau"]),
+    ("TL", "TLS", "670", &["timor-leste", "east timor"]),
+    ("TM", "TKM", "993", &["turkmenistan"]),
+    ("TN", "TUN", "216", &["tunisia"]),
+    ("TO", "TON", "676", &["tonga"]),
+    ("TR", "TUR", "90", &["turkey", "türkiye"]),
+    ("TT", "TTO", "1", &["trinidad and tobago"]),
+    ("TV", "TUV", "688", &["tuvalu"]),
+    ("TW", "TWN", "886", &["taiwan"]),
+    ("TZ", "TZA", "255", &["tanzania"]),
+    ("UA", "UKR", "380", &["ukraine"]),
+    ("UG", "UGA", "256", &["uganda"]),
+    ("UM", "UMI", "1", &["united states minor outlying islands"]),
+    ("US", "USA", "1", &["united states", "united states of america", "usa", "us", "america"]),
+    ("UY", "URY", "598", &["uruguay"]),
+    ("UZ", "UZB", "998", &["uzbekistan"]),
+    ("VA", "VAT", "39", &["vatican city", "holy see"]),
+    ("VC", "VCT", "1", &["saint vincent and the grenadines"]),
+    ("VE", "VEN", "58", &["venezuela"]),
+    ("VG", "VGB", "1", &["british virgin islands"]),
+    ("VI", "VIR", "1", &["us virgin islands", "u.s. virgin islands"]),
+    ("VN", "VNM", "84", &["vietnam", "viet nam"]),
+    ("VU", "VUT", "678", &["vanuatu"]),
+    ("WF", "WLF", "681", &["wallis and futuna"]),
+    ("WS", "WSM", "685", &["samoa"]),
+    ("YE", "YEM", "967", &["yemen"]),
+    ("YT", "MYT", "262", &["mayotte"]),
+    ("ZA", "ZAF", "27", &["south africa"]),
+    ("ZM", "ZMB", "260", &["zambia"]),
+    ("ZW", "ZWE", "263", &["zimbabwe"]),
+];
+
+/// Trunk prefixes other than the usual 0: national numbers start with these
+/// and lose them when dialled from abroad. An empty prefix means the leading
+/// 0 is part of the number itself and stays.
+const TRUNK_PREFIXES: &[(&str, &str)] = &[
+    ("BY", "8"),
+    ("HU", "06"),
+    ("IT", ""),
+    ("KZ", "8"),
+    ("RU", "8"),
+    ("SM", ""),
+    ("VA", ""),
+];
+
+/// (country, subdivision code, name)
+const REGIONS: &[(&str, &str, &str)] = &[
+    ("US", "AL", "alabama"), ("US", "AK", "alaska"), ("US", "AZ", "arizona"), ("US", "AR", "arkansas"),
+    ("US", "CA", "california"), ("US", "CO", "colorado"), ("US", "CT", "connecticut"), ("US", "DE", "delaware"),
+    ("US", "DC", "district of columbia"), ("US", "FL", "florida"), ("US", "GA", "georgia"), ("US", "HI", "hawaii"),
+    ("US", "ID", "idaho"), ("US", "IL", "illinois"), ("US", "IN", "indiana"), ("US", "IA", "iowa"),
+    ("US", "KS", "kansas"), ("US", "KY", "kentucky"), ("US", "LA", "louisiana"), ("US", "ME", "maine"),
+    ("US", "MD", "maryland"), ("US", "MA", "massachusetts"), ("US", "MI", "michigan"), ("US", "MN", "minnesota"),
+    ("US", "MS", "mississippi"), ("US", "MO", "missouri"), ("US", "MT", "montana"), ("US", "NE", "nebraska"),
+    ("US", "NV", "nevada"), ("US", "NH", "new hampshire"), ("US", "NJ", "new jersey"), ("US", "NM", "new mexico"),
+    ("US", "NY", "new york"), ("US", "NC", "north carolina"), ("US", "ND", "north dakota"), ("US", "OH", "ohio"),
+    ("US", "OK", "oklahoma"), ("US", "OR", "oregon"), ("US", "PA", "pennsylvania"), ("US", "RI", "rhode island"),
+    ("US", "SC", "south carolina"), ("US", "SD", "south dakota"), ("US", "TN", "tennessee"), ("US", "TX", "texas"),
+    ("US", "UT", "utah"), ("US", "VT", "vermont"), ("US", "VA", "virginia"), ("US", "WA", "washington"),
+    ("US", "WV", "west virginia"), ("US", "WI", "wisconsin"), ("US", "WY", "wyoming"), ("US", "PR", "puerto rico"),
+    ("CA", "AB", "alberta"), ("CA", "BC", "british columbia"), ("CA", "MB", "manitoba"), ("CA", "NB", "new brunswick"),
+    ("CA", "NL", "newfoundland and labrador"), ("CA", "NS", "nova scotia"), ("CA", "NT", "northwest territories"),
+    ("CA", "NU", "nunavut"), ("CA", "ON", "ontario"), ("CA", "PE", "prince edward island"), ("CA", "QC", "quebec"),
+    ("CA", "SK", "saskatchewan"), ("CA", "YT", "yukon"),
+    ("AU", "ACT", "australian capital territory"), ("AU", "NSW", "new south wales"), ("AU", "NT", "northern territory"),
+    ("AU", "QLD", "queensland"), ("AU", "SA", "south australia"), ("AU", "TAS", "tasmania"), ("AU", "VIC", "victoria"),
+    ("AU", "WA", "western australia"),
+];
+
+/// ISO 3166-1 alpha-2 code for a country name or code
+pub fn country_code(text: &str) -> Option<&'static str> {
+    let text = text.trim().trim_end_matches('.').to_lowercase();
+    COUNTRIES
+        .iter()
+        .find(|(alpha2, alpha3, _, names)| {
+            text.eq_ignore_ascii_case(alpha2) || text.eq_ignore_ascii_case(alpha3) || names.contains(&text.as_str())
+        })
+        .map(|(alpha2, ..)| *alpha2)
+}
+
+/// E.164 calling code (without the +) of an alpha-2 country code
+pub fn calling_code(country: &str) -> Option<&'static str> {
+    COUNTRIES
+        .iter()
+        .find(|(alpha2, ..)| alpha2.eq_ignore_ascii_case(country))
+        .map(|(_, _, code, _)| *code)
+}
+
+/// Trunk prefix of national numbers in an alpha-2 country (see `TRUNK_PREFIXES`)
+pub fn trunk_prefix(country: &str) -> &'static str {
+    TRUNK_PREFIXES
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(country))
+        .map(|(_, prefix)| *prefix)
+        .unwrap_or("0")
+}
+
+/// Whether we know the subdivisions of `country` well enough to check a region
+pub fn has_regions(country: &str) -> bool {
+    REGIONS.iter().any(|(c, ..)| c.eq_ignore_ascii_case(country))
+}
+
+/// ISO 3166-2 code ("US-CA") for a region name or code within `country`
+pub fn region_code(country: &str, text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches('.').to_lowercase();
+    // Already in ISO 3166-2 form
+    let bare = text
+        .strip_prefix(&format!("{}-", country.to_lowercase()))
+        .unwrap_or(&text);
+    REGIONS
+        .iter()
+        .filter(|(c, ..)| c.eq_ignore_ascii_case(country))
+        .find(|(_, code, name)| bare.eq_ignore_ascii_case(code) || bare == *name)
+        .map(|(c, code, _)| format!("{}-{}", c, code))
+}
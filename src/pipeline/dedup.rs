@@ -0,0 +1,449 @@
+// Record deduplication and entity merging
+// The same entity often turns up on several sites (a university listed by
+// three directories). Records are matched on configurable keys — the
+// normalised website URL, its domain, or a fuzzy name match in the same
+// location — and each group is merged into one entity. Every field of the
+// entity remembers which source it came from, and conflicting values are
+// settled by source priority or by quality score.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{is_empty, on_domain, source_host};
+use crate::data::schema::FieldIssue;
+use crate::data::PageSnapshot;
+use crate::engine::config::{ConflictPolicy, DedupConfig, DedupKey};
+use crate::engine::ScrapingResult;
+
+const NAME_STOPWORDS: &[&str] = &["the", "of", "and", "at"];
+/// Words of each name used to find candidate matches, rarest first
+const BLOCKING_WORDS: usize = 2;
+/// Names each name is compared with in a block, in name order
+const BLOCK_WINDOW: usize = 50;
+
+/// Where a merged field's value came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldProvenance {
+    pub source_url: String,
+    pub quality_score: f32,
+    /// Different values other sources had for the field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<SourcedValue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcedValue {
+    pub source_url: String,
+    pub value: Value,
+}
+
+/// One real-world entity merged from one or more records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entity {
+    pub data: Value,
+    /// Source URLs of the merged records, preferred source first
+    pub sources: Vec<String>,
+    pub provenance: BTreeMap<String, FieldProvenance>,
+    /// Best quality score among the merged records, until the entity is scored itself
+    pub quality_score: f32,
+    /// Extraction confidence of the preferred record
+    pub confidence: f32,
+    /// Flags raised on the values the entity kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<FieldIssue>,
+    /// When the preferred record was extracted
+    pub extracted_at: DateTime<Utc>,
+    /// Copy of the page the preferred record came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<PageSnapshot>,
+}
+
+impl Entity {
+    pub fn primary_source(&self) -> &str {
+        self.sources.first().map(String::as_str).unwrap_or_default()
+    }
+}
+
+pub struct Deduplicator {
+    config: DedupConfig,
+}
+
+impl Deduplicator {
+    pub fn new(config: &DedupConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    /// Group matching records and merge each group into an entity. Entities
+    /// come out in the order their first record came in.
+    pub fn merge(&self, results: Vec<ScrapingResult>) -> Vec<Entity> {
+        let mut groups = Groups::new(results.len());
+        for key in &self.config.keys {
+            match key {
+                DedupKey::Url => self.join_exact(&results, &mut groups, |r| self.first_url(r).map(|(host, path)| format!("{}{}", host, path))),
+                DedupKey::Domain => self.join_exact(&results, &mut groups, |r| self.first_url(r).map(|(host, _)| host)),
+                DedupKey::NameLocation => self.join_names(&results, &mut groups),
+            }
+        }
+
+        let mut members: Vec<Vec<&ScrapingResult>> = Vec::new();
+        let mut group_index: HashMap<usize, usize> = HashMap::new();
+        for (i, result) in results.iter().enumerate() {
+            let root = groups.find(i);
+            let index = *group_index.entry(root).or_insert_with(|| {
+                members.push(Vec::new());
+                members.len() - 1
+            });
+            members[index].push(result);
+        }
+        members.into_iter().map(|group| self.merge_group(group)).collect()
+    }
+
+    fn join_exact(&self, results: &[ScrapingResult], groups: &mut Groups, key: impl Fn(&ScrapingResult) -> Option<String>) {
+        let mut first: HashMap<String, usize> = HashMap::new();
+        for (i, result) in results.iter().enumerate() {
+            if let Some(key) = key(result) {
+                match first.get(&key) {
+                    Some(&j) => groups.join(i, j),
+                    None => {
+                        first.insert(key, i);
+                    }
+                }
+            }
+        }
+    }
+
+    // Names are only compared when they share one of their rarest words, so
+    // reordered names still meet without every "University" being compared
+    // with every other. A block that's big anyway (a common surname) is sorted
+    // by name and each name only compared with the ones just after it, which
+    // keeps this linear in the result set.
+    fn join_names(&self, results: &[ScrapingResult], groups: &mut Groups) {
+        let names: Vec<Option<(String, Option<String>)>> = results
+            .iter()
+            .map(|r| {
+                let name = normalize_name(&first_text(&r.data, &self.config.name_fields)?);
+                let location = first_text(&r.data, &self.config.location_fields).map(|l| normalize_name(&l));
+                (!name.is_empty()).then_some((name, location))
+            })
+            .collect();
+
+        let words: Vec<Vec<&str>> = names
+            .iter()
+            .map(|entry| {
+                let mut words: Vec<&str> = entry.iter().flat_map(|(name, _)| name.split(' ')).collect();
+                words.sort_unstable();
+                words.dedup();
+                words
+            })
+            .collect();
+        let mut frequency: HashMap<&str, usize> = HashMap::new();
+        for word in words.iter().flatten() {
+            *frequency.entry(*word).or_default() += 1;
+        }
+
+        let mut blocks: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, mut words) in words.into_iter().enumerate() {
+            words.sort_by_key(|w| frequency[w]);
+            for word in words.into_iter().take(BLOCKING_WORDS) {
+                blocks.entry(word).or_default().push(i);
+            }
+        }
+        let name = |i: usize| names[i].as_ref().map(|(name, _)| name.as_str()).unwrap_or_default();
+        for block in blocks.values_mut() {
+            block.sort_by(|&i, &j| name(i).cmp(name(j)));
+            for (a, &i) in block.iter().enumerate() {
+                for &j in block[a + 1..].iter().take(BLOCK_WINDOW) {
+                    // Two names sharing both their rarest words meet twice
+                    if groups.find(i) == groups.find(j) {
+                        continue;
+                    }
+                    let (Some(left), Some(right)) = (&names[i], &names[j]) else {
+                        continue;
+                    };
+                    if self.same_entity(left, right) {
+                        groups.join(i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    fn same_entity(&self, left: &(String, Option<String>), right: &(String, Option<String>)) -> bool {
+        let threshold = self.config.name_similarity as f64;
+        match (&left.1, &right.1) {
+            (Some(a), Some(b)) => {
+                let same_place = a.contains(b.as_str()) || b.contains(a.as_str()) || similarity(a, b) >= threshold;
+                same_place && similarity(&left.0, &right.0) >= threshold
+            }
+            // A shared name alone isn't enough (two people called John Smith)
+            _ => false,
+        }
+    }
+
+    /// Host (without www.) and path of the first URL field in the record
+    fn first_url(&self, result: &ScrapingResult) -> Option<(String, String)> {
+        let text = first_text(&result.data, &self.config.url_fields)?;
+        let text = if text.contains("://") { text } else { format!("http://{}", text) };
+        let url = url::Url::parse(&text).ok()?;
+        let host = url.host_str()?.trim_start_matches("www.").to_ascii_lowercase();
+        Some((host, url.path().trim_end_matches('/').to_string()))
+    }
+
+    fn merge_group(&self, mut group: Vec<&ScrapingResult>) -> Entity {
+        let rank = |r: &ScrapingResult| self.source_rank(&r.source_url);
+        group.sort_by(|a, b| {
+            let by_quality = b.quality_score.total_cmp(&a.quality_score);
+            let by_priority = rank(a).cmp(&rank(b));
+            match self.config.conflict {
+                ConflictPolicy::SourcePriority => by_priority.then(by_quality),
+                ConflictPolicy::QualityScore => by_quality.then(by_priority),
+            }
+        });
+
+        let mut fields: Vec<String> = Vec::new();
+        for result in &group {
+            for key in result.data.as_object().into_iter().flat_map(|obj| obj.keys()) {
+                if !fields.contains(key) {
+                    fields.push(key.clone());
+                }
+            }
+        }
+
+        let mut data = Map::new();
+        let mut provenance = BTreeMap::new();
+        let mut flags = Vec::new();
+        for field in fields {
+            let mut values: Vec<(&ScrapingResult, &Value)> = group
+                .iter()
+                .filter_map(|r| r.data.get(&field).filter(|v| !is_empty(v)).map(|v| (*r, v)))
+                .collect();
+            // A value that passed validation beats a preferred source's flagged one
+            values.sort_by_key(|(r, _)| r.flags.iter().any(|f| f.field == field));
+            let Some(&(chosen, value)) = values.first() else {
+                flags.extend(group[0].flags.iter().filter(|f| f.field == field).cloned());
+                data.insert(field, Value::Null);
+                continue;
+            };
+            let mut alternatives: Vec<SourcedValue> = Vec::new();
+            for (result, other) in &values[1..] {
+                if !same_value(value, other) && !alternatives.iter().any(|a| same_value(&a.value, other)) {
+                    alternatives.push(SourcedValue { source_url: result.source_url.clone(), value: (*other).clone() });
+                }
+            }
+            flags.extend(chosen.flags.iter().filter(|f| f.field == field).cloned());
+            data.insert(field.clone(), value.clone());
+            provenance.insert(field, FieldProvenance {
+                source_url: chosen.source_url.clone(),
+                quality_score: chosen.quality_score,
+                alternatives,
+            });
+        }
+
+        let mut sources: Vec<String> = Vec::new();
+        for result in &group {
+            if !sources.contains(&result.source_url) {
+                sources.push(result.source_url.clone());
+            }
+        }
+        Entity {
+            data: Value::Object(data),
+            sources,
+            provenance,
+            quality_score: group.iter().map(|r| r.quality_score).fold(0.0, f32::max),
+            confidence: group[0].confidence,
+            flags,
+            extracted_at: group[0].extracted_at,
+            snapshot: group[0].snapshot.clone(),
+        }
+    }
+
+    // Position of the source's domain in the priority list; unlisted sources come last
+    fn source_rank(&self, source_url: &str) -> usize {
+        let host = source_host(source_url);
+        self.config
+            .source_priority
+            .iter()
+            .position(|d| on_domain(&host, d))
+            .unwrap_or(self.config.source_priority.len())
+    }
+}
+
+/// Disjoint sets of record indexes
+struct Groups {
+    parent: Vec<usize>,
+}
+
+impl Groups {
+    fn new(size: usize) -> Self {
+        Self { parent: (0..size).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = i;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    // The lower index stays the root so groups keep their first record's position
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+fn first_text(record: &Value, fields: &[String]) -> Option<String> {
+    fields.iter().find_map(|field| match record.get(field)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        _ => None,
+    })
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => normalize_name(a) == normalize_name(b),
+        _ => a == b,
+    }
+}
+
+/// Lowercase words with punctuation and filler words dropped
+fn normalize_name(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !NAME_STOPWORDS.contains(w))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 1.0 for the same words in any order, otherwise one minus the edit distance
+/// relative to the longer name
+fn similarity(a: &str, b: &str) -> f64 {
+    let mut left: Vec<&str> = a.split(' ').collect();
+    let mut right: Vec<&str> = b.split(' ').collect();
+    left.sort_unstable();
+    right.sort_unstable();
+    if left == right {
+        return 1.0;
+    }
+
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut diagonal = row[0];
+        row[0] = i;
+        for j in 1..=b.len() {
+            let substitution = diagonal + usize::from(a[i - 1] != b[j - 1]);
+            diagonal = row[j];
+            row[j] = substitution.min(row[j] + 1).min(row[j - 1] + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(source_url: &str, quality_score: f32, data: Value) -> ScrapingResult {
+        ScrapingResult {
+            task_id: "task".to_string(),
+            data,
+            source_url: source_url.to_string(),
+            extracted_at: Utc::now(),
+            quality_score,
+            confidence: 0.9,
+            snapshot: None,
+            flags: Vec::new(),
+        }
+    }
+
+    fn dedup() -> Deduplicator {
+        Deduplicator::new(&DedupConfig { enabled: true, ..DedupConfig::default() })
+    }
+
+    #[test]
+    fn merges_records_with_the_same_website() {
+        let entities = dedup().merge(vec![
+            result("https://a.example/list", 0.6, json!({"name": "Oxford", "website": "https://www.ox.ac.uk/", "phone": ""})),
+            result("https://b.example/list", 0.8, json!({"name": "University of Oxford", "website": "http://ox.ac.uk", "phone": "+441865270000"})),
+            result("https://b.example/list", 0.8, json!({"name": "University of Cambridge", "website": "https://www.cam.ac.uk"})),
+        ]);
+
+        assert_eq!(entities.len(), 2);
+        let oxford = &entities[0];
+        assert_eq!(oxford.sources, vec!["https://b.example/list", "https://a.example/list"]);
+        assert_eq!(oxford.data["name"], "University of Oxford");
+        // The better record's empty fields are filled, not the other way round
+        assert_eq!(oxford.data["phone"], "+441865270000");
+        assert_eq!(oxford.provenance["name"].alternatives.len(), 1);
+        assert_eq!(oxford.provenance["name"].alternatives[0].source_url, "https://a.example/list");
+        assert_eq!(oxford.quality_score, 0.8);
+    }
+
+    #[test]
+    fn matches_reordered_names_only_in_the_same_place() {
+        let entities = dedup().merge(vec![
+            result("https://a.example/", 0.7, json!({"name": "Oxford University", "city": "Oxford, England"})),
+            result("https://b.example/", 0.7, json!({"name": "The University of Oxford", "city": "Oxford, England"})),
+            result("https://c.example/", 0.7, json!({"name": "Oxford University", "city": "Oxford, Mississippi"})),
+            // No location to tell two people of the same name apart
+            result("https://d.example/", 0.7, json!({"name": "John Smith"})),
+            result("https://e.example/", 0.7, json!({"name": "John Smith"})),
+        ]);
+
+        let sizes: Vec<usize> = entities.iter().map(|e| e.sources.len()).collect();
+        assert_eq!(sizes, vec![2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn common_words_do_not_hide_matches_in_large_sets() {
+        let mut results: Vec<ScrapingResult> = (0..2000)
+            .map(|i| result("https://a.example/", 0.7, json!({"name": format!("University Campus {}", i), "city": "Springfield"})))
+            .collect();
+        results.push(result("https://b.example/", 0.7, json!({"name": "Campus 1234 University", "city": "Springfield"})));
+
+        // Only the same words count as the same name, so neighbouring numbers don't match
+        let config = DedupConfig { enabled: true, name_similarity: 1.0, ..DedupConfig::default() };
+        let entities = Deduplicator::new(&config).merge(results);
+        assert_eq!(entities.len(), 2000);
+        let merged: Vec<&Entity> = entities.iter().filter(|e| e.sources.len() > 1).collect();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].data["name"], "University Campus 1234");
+    }
+
+    #[test]
+    fn source_priority_settles_conflicts() {
+        let config = DedupConfig {
+            enabled: true,
+            conflict: ConflictPolicy::SourcePriority,
+            source_priority: vec!["trusted.example".to_string()],
+            ..DedupConfig::default()
+        };
+        let entities = Deduplicator::new(&config).merge(vec![
+            result("https://other.example/", 0.9, json!({"name": "Acme", "website": "acme.test", "phone": "111"})),
+            result("https://www.trusted.example/", 0.5, json!({"name": "Acme Ltd", "website": "acme.test", "phone": "222"})),
+        ]);
+
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].primary_source(), "https://www.trusted.example/");
+        assert_eq!(entities[0].data["phone"], "222");
+        assert_eq!(entities[0].provenance["phone"].alternatives[0].value, "111");
+    }
+}
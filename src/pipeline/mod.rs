@@ -0,0 +1,36 @@
+// Post-extraction pipeline for Flash AI
+// Stages records go through between extraction and storage/export
+
+pub mod codes;
+pub mod dedup;
+pub mod normalize;
+pub mod quality;
+
+pub use dedup::{Deduplicator, Entity, FieldProvenance};
+pub use normalize::{NormalizedRecord, Normalizer};
+pub use quality::{QualityModel, QualityScore};
+
+use serde_json::Value;
+
+/// True for null, blank strings and empty lists: values that don't fill a field
+pub(crate) fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        Value::Array(a) => a.is_empty(),
+        _ => false,
+    }
+}
+
+/// Lowercase host of a source URL without "www.", or "" when it has none
+pub(crate) fn source_host(source_url: &str) -> String {
+    url::Url::parse(source_url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_ascii_lowercase()))
+        .unwrap_or_default()
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+pub(crate) fn on_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
@@ -0,0 +1,342 @@
+// Field normalisation and validation
+// Extracted values arrive as raw strings. Every string gets HTML entities
+// decoded and whitespace collapsed; typed schema fields are then brought into
+// one canonical form: absolute canonical URLs, lowercase emails, E.164 phone
+// numbers, ISO 3166 country and region codes, ISO-8601 dates and plain numbers.
+// A value that can't be normalised is kept as it was and flagged.
+
+use chrono::{DateTime, NaiveDate};
+use scraper::Html;
+use serde_json::{Map, Number, Value};
+use url::Url;
+
+use super::codes::{calling_code, country_code, has_regions, region_code, trunk_prefix};
+use crate::crawl::canonicalize;
+use crate::data::schema::{FieldIssue, FieldType, OutputSchema};
+use crate::engine::config::NormalizeConfig;
+
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%B %d, %Y", "%b %d, %Y", "%d %B %Y", "%d %b %Y", "%B %d %Y", "%d.%m.%Y"];
+
+const DAY_FIRST_FORMATS: &[&str] = &["%d/%m/%Y", "%d-%m-%Y", "%m/%d/%Y"];
+
+const MONTH_FIRST_FORMATS: &[&str] = &["%m/%d/%Y", "%m-%d-%Y", "%d/%m/%Y"];
+
+/// A record after normalisation, with the values that didn't pass
+#[derive(Debug, Clone)]
+pub struct NormalizedRecord {
+    pub data: Value,
+    pub flags: Vec<FieldIssue>,
+}
+
+pub struct Normalizer {
+    config: NormalizeConfig,
+    ignore_params: Vec<String>,
+}
+
+impl Normalizer {
+    /// `ignore_params` are the tracking parameters dropped from URLs
+    pub fn new(config: &NormalizeConfig, ignore_params: &[String]) -> Self {
+        Self {
+            config: config.clone(),
+            ignore_params: ignore_params.to_vec(),
+        }
+    }
+
+    /// Normalise every field of `record`; relative URLs resolve against `source_url`
+    pub fn normalize(&self, record: &Value, schema: &OutputSchema, source_url: &str) -> NormalizedRecord {
+        let obj = match record.as_object() {
+            Some(obj) if self.config.enabled => obj,
+            _ => return NormalizedRecord { data: record.clone(), flags: Vec::new() },
+        };
+        let base = Url::parse(source_url).ok();
+
+        // Phone numbers and regions are read in the record's own country when it
+        // has one. If it names a country we can't place, the default would only
+        // be a guess, so national numbers and regions go unread.
+        let named: Vec<&str> = schema
+            .fields
+            .iter()
+            .filter(|f| f.field_type == FieldType::Country)
+            .filter_map(|f| obj.get(&f.name)?.as_str())
+            .filter(|v| !v.trim().is_empty())
+            .collect();
+        let country = if named.is_empty() {
+            Some(self.config.default_country.as_str())
+        } else {
+            named.iter().find_map(|v| country_code(v))
+        };
+
+        let mut data = Map::new();
+        let mut flags = Vec::new();
+        for (name, value) in obj {
+            let field_type = schema.field(name).map(|f| f.field_type).unwrap_or(FieldType::String);
+            let value = self.normalize_value(value, field_type, base.as_ref(), country, &mut |problem| {
+                flags.push(FieldIssue { field: name.clone(), problem });
+            });
+            data.insert(name.clone(), value);
+        }
+        NormalizedRecord { data: Value::Object(data), flags }
+    }
+
+    fn normalize_value(&self, value: &Value, field_type: FieldType, base: Option<&Url>, country: Option<&str>, flag: &mut dyn FnMut(String)) -> Value {
+        match value {
+            Value::String(text) => {
+                let text = clean(text);
+                if text.is_empty() {
+                    return Value::String(text);
+                }
+                match self.typed(&text, field_type, base, country) {
+                    Ok(value) => value,
+                    Err(problem) => {
+                        flag(problem);
+                        Value::String(text)
+                    }
+                }
+            }
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.normalize_value(item, field_type, base, country, flag))
+                    .collect(),
+            ),
+            Value::Number(n) if field_type == FieldType::Phone => self
+                .typed(&n.to_string(), field_type, base, country)
+                .unwrap_or_else(|problem| {
+                    flag(problem);
+                    value.clone()
+                }),
+            other => other.clone(),
+        }
+    }
+
+    fn typed(&self, text: &str, field_type: FieldType, base: Option<&Url>, country: Option<&str>) -> Result<Value, String> {
+        let normalized = match field_type {
+            FieldType::String => return Ok(Value::String(text.to_string())),
+            FieldType::Url => self.url(text, base).ok_or_else(|| format!("not a web address: '{}'", text))?,
+            FieldType::Email => email(text).ok_or_else(|| format!("not an email address: '{}'", text))?,
+            FieldType::Phone => e164(text, country).ok_or_else(|| match country {
+                Some(country) => format!("not a phone number in {}: '{}'", country, text),
+                None => format!("not an international phone number: '{}'", text),
+            })?,
+            FieldType::Number => return number(text).ok_or_else(|| format!("not a number: '{}'", text)),
+            FieldType::Date => self.date(text).ok_or_else(|| format!("unrecognised date: '{}'", text))?,
+            FieldType::Country => country_code(text)
+                .map(str::to_string)
+                .ok_or_else(|| format!("unknown country: '{}'", text))?,
+            FieldType::Region => match country {
+                Some(country) => match region_code(country, text) {
+                    Some(code) => code,
+                    None if has_regions(country) => return Err(format!("unknown region of {}: '{}'", country, text)),
+                    // We can't check regions of this country, so leave it alone
+                    None => text.to_string(),
+                },
+                None => text.to_string(),
+            },
+        };
+        Ok(Value::String(normalized))
+    }
+
+    fn url(&self, text: &str, base: Option<&Url>) -> Option<String> {
+        let text = text.trim();
+        let url = match Url::parse(text) {
+            Ok(url) => url,
+            // "www.example.com" with no scheme is a website, not a relative path
+            Err(_) if text.starts_with("www.") => Url::parse(&format!("http://{}", text)).ok()?,
+            Err(_) => base?.join(text).ok()?,
+        };
+        canonicalize(&url, &self.ignore_params).map(|u| u.to_string())
+    }
+
+    fn date(&self, text: &str) -> Option<String> {
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+            return Some(datetime.to_rfc3339());
+        }
+        let numeric = if self.config.day_first { DAY_FIRST_FORMATS } else { MONTH_FIRST_FORMATS };
+        // Drop ordinal suffixes ("March 3rd, 2024") and weekday prefixes
+        let cleaned = strip_ordinals(text.split_once(", ").filter(|(day, _)| is_weekday(day)).map(|(_, rest)| rest).unwrap_or(text));
+        if let Some(date) = DATE_FORMATS
+            .iter()
+            .chain(numeric)
+            .find_map(|format| NaiveDate::parse_from_str(&cleaned, format).ok())
+        {
+            return Some(date.format("%Y-%m-%d").to_string());
+        }
+        // Reduced precision: "March 2024", "2024"
+        if let Ok(date) = NaiveDate::parse_from_str(&format!("1 {}", cleaned), "%d %B %Y") {
+            return Some(date.format("%Y-%m").to_string());
+        }
+        if cleaned.len() == 4 && cleaned.parse::<u16>().is_ok() {
+            return Some(cleaned);
+        }
+        None
+    }
+}
+
+/// Decode HTML entities, drop stray tags and collapse whitespace
+pub fn clean(text: &str) -> String {
+    let text = if text.contains('&') || text.contains('<') {
+        Html::parse_fragment(text).root_element().text().collect::<String>()
+    } else {
+        text.to_string()
+    };
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn email(text: &str) -> Option<String> {
+    let text = text.trim();
+    let text = text.strip_prefix("mailto:").unwrap_or(text);
+    let email = text.split('?').next().unwrap_or_default().to_lowercase();
+    FieldType::Email.accepts(&Value::String(email.clone())).then_some(email)
+}
+
+/// E.164 form of a phone number, reading national numbers as `country`'s
+/// (only international ones are read when the country is unknown).
+/// Extensions are dropped since E.164 has no place for them.
+fn e164(text: &str, country: Option<&str>) -> Option<String> {
+    let lower = text.to_lowercase();
+    // Labels such as "Tel:", "Phone" or "Fax:" come off before looking for an
+    // extension, so the "x" in "Fax" isn't taken for one
+    let number = lower.trim_start_matches(|c: char| !(c.is_ascii_digit() || c == '+' || c == '('));
+    let number = match extension_at(number) {
+        Some(at) => &number[..at],
+        None => number,
+    }
+    .trim();
+    if !number.chars().all(|c| c.is_ascii_digit() || " +-().\u{a0}/".contains(c)) {
+        return None;
+    }
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    let international = if number.starts_with('+') {
+        digits
+    } else if let Some(rest) = digits.strip_prefix("00") {
+        rest.to_string()
+    } else {
+        let country = country?;
+        let code = calling_code(country)?;
+        if code == "1" {
+            // North American numbers: 10 digits, or 11 with the leading 1
+            match digits.len() {
+                10 => format!("1{}", digits),
+                11 if digits.starts_with('1') => digits,
+                _ => return None,
+            }
+        } else {
+            // Elsewhere the trunk prefix goes when dialling from abroad
+            let national = digits.strip_prefix(trunk_prefix(country)).unwrap_or(digits.as_str());
+            format!("{}{}", code, national)
+        }
+    };
+    (8..=15).contains(&international.len()).then(|| format!("+{}", international))
+}
+
+/// Where an extension ("x12", "ext. 12", "#12") starts in a phone number; the
+/// marker only counts when digits follow it
+fn extension_at(number: &str) -> Option<usize> {
+    number.char_indices().find_map(|(at, _)| {
+        let rest = &number[at..];
+        let after = ["extension", "ext", "x", "#"].iter().find_map(|marker| rest.strip_prefix(marker))?;
+        after
+            .trim_start_matches(|c: char| c == '.' || c == ':' || c.is_whitespace())
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then_some(at)
+    })
+}
+
+fn number(text: &str) -> Option<Value> {
+    let cleaned: String = text
+        .trim()
+        .trim_start_matches(|c: char| "$€£¥".contains(c))
+        .trim_end_matches(|c: char| "$€£¥%".contains(c) || c.is_whitespace())
+        .chars()
+        .filter(|c| *c != ',' && !c.is_whitespace())
+        .collect();
+    if let Ok(integer) = cleaned.parse::<i64>() {
+        return Some(Value::Number(integer.into()));
+    }
+    Number::from_f64(cleaned.parse::<f64>().ok()?).map(Value::Number)
+}
+
+fn is_weekday(text: &str) -> bool {
+    let text = text.to_lowercase();
+    ["mon", "tue", "wed", "thu", "fri", "sat", "sun"].iter().any(|d| text.starts_with(d)) && text.len() <= 9
+}
+
+fn strip_ordinals(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let bare = word.trim_end_matches(',');
+            let digits = bare.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+            let suffix = &bare[digits.len()..];
+            if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) && matches!(suffix, "st" | "nd" | "rd" | "th") {
+                word.replacen(suffix, "", 1)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn normalizer(day_first: bool) -> Normalizer {
+        Normalizer::new(&NormalizeConfig { day_first, ..NormalizeConfig::default() }, &[])
+    }
+
+    #[test]
+    fn phone_numbers_lose_one_trunk_prefix() {
+        assert_eq!(e164("020 7946 0958", Some("GB")).as_deref(), Some("+442079460958"));
+        assert_eq!(e164("Tel: 030 1234567", Some("DE")).as_deref(), Some("+49301234567"));
+        // Italian numbers keep their leading 0
+        assert_eq!(e164("06 6982 0000", Some("IT")).as_deref(), Some("+390669820000"));
+        assert_eq!(e164("0549 882 111", Some("SM")).as_deref(), Some("+3780549882111"));
+        assert_eq!(e164("8 (495) 123-45-67", Some("RU")).as_deref(), Some("+74951234567"));
+        assert_eq!(e164("06 1 234 5678", Some("HU")).as_deref(), Some("+3612345678"));
+    }
+
+    #[test]
+    fn phone_numbers_in_international_form() {
+        assert_eq!(e164("(415) 555-0132 ext. 7", Some("US")).as_deref(), Some("+14155550132"));
+        assert_eq!(e164("1-415-555-0132", Some("CA")).as_deref(), Some("+14155550132"));
+        assert_eq!(e164("Fax: +33 1 23 45 67 89", None).as_deref(), Some("+33123456789"));
+        assert_eq!(e164("0044 20 7946 0958", Some("FR")).as_deref(), Some("+442079460958"));
+        // A national number can't be read without its country
+        assert_eq!(e164("020 7946 0958", None), None);
+        assert_eq!(e164("call us", Some("GB")), None);
+        assert_eq!(e164("555-0132", Some("US")), None);
+    }
+
+    #[test]
+    fn dates_in_iso_form() {
+        let normalizer = normalizer(false);
+        assert_eq!(normalizer.date("March 3rd, 2024").as_deref(), Some("2024-03-03"));
+        assert_eq!(normalizer.date("Tue, 5 March 2024").as_deref(), Some("2024-03-05"));
+        assert_eq!(normalizer.date("2024/03/05").as_deref(), Some("2024-03-05"));
+        assert_eq!(normalizer.date("03/04/2024").as_deref(), Some("2024-03-04"));
+        assert_eq!(normalizer.date("2024-03-05T10:00:00+01:00").as_deref(), Some("2024-03-05T10:00:00+01:00"));
+        assert_eq!(normalizer.date("March 2024").as_deref(), Some("2024-03"));
+        assert_eq!(normalizer.date("2024").as_deref(), Some("2024"));
+        assert_eq!(normalizer.date("soon"), None);
+    }
+
+    #[test]
+    fn numeric_dates_follow_the_day_order_setting() {
+        assert_eq!(normalizer(true).date("03/04/2024").as_deref(), Some("2024-04-03"));
+        assert_eq!(normalizer(true).date("25/12/2024").as_deref(), Some("2024-12-25"));
+        // Falls back to the other order when the first can't be right
+        assert_eq!(normalizer(false).date("25/12/2024").as_deref(), Some("2024-12-25"));
+    }
+
+    #[test]
+    fn numbers_without_currency_or_separators() {
+        assert_eq!(number("$1,250"), Some(json!(1250)));
+        assert_eq!(number("€ 3 000"), Some(json!(3000)));
+        assert_eq!(number("12.5%"), Some(json!(12.5)));
+        assert_eq!(number("-7"), Some(json!(-7)));
+        assert_eq!(number("n/a"), None);
+    }
+}
@@ -0,0 +1,95 @@
+// Record quality scoring
+// A record's quality score is a weighted mix of four signals, each from 0 to 1:
+// how much of the task schema it fills (required fields count double), how
+// many of its values passed normalisation and validation, how confident the
+// extractor was, and how reliable its source site is. Records below a task's
+// minimum score are dropped before they are stored or exported.
+
+use serde_json::Value;
+
+use super::{is_empty, on_domain, source_host};
+use crate::data::schema::{FieldIssue, OutputSchema};
+use crate::engine::config::QualityConfig;
+
+/// A quality score with the signals it was made from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityScore {
+    pub score: f32,
+    pub completeness: f32,
+    pub validation: f32,
+    pub confidence: f32,
+    pub reliability: f32,
+}
+
+pub struct QualityModel {
+    config: QualityConfig,
+}
+
+impl QualityModel {
+    pub fn new(config: &QualityConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    /// Score a normalised record against the schema, given the values flagged on
+    /// it, the extractor's confidence and the reliability of its source
+    pub fn score(&self, schema: &OutputSchema, data: &Value, flags: &[FieldIssue], confidence: f32, reliability: f32) -> QualityScore {
+        let filled: Vec<&str> = schema
+            .fields
+            .iter()
+            .filter(|f| data.get(&f.name).map(|v| !is_empty(v)).unwrap_or(false))
+            .map(|f| f.name.as_str())
+            .collect();
+
+        let weight = |required: bool| if required { 2.0 } else { 1.0 };
+        let possible: f32 = schema.fields.iter().map(|f| weight(f.required)).sum();
+        let present: f32 = schema
+            .fields
+            .iter()
+            .filter(|f| filled.contains(&f.name.as_str()))
+            .map(|f| weight(f.required))
+            .sum();
+        let completeness = if possible > 0.0 { present / possible } else { 0.0 };
+
+        // Missing values already cost completeness; only the values we have are checked here
+        let validation = if filled.is_empty() {
+            0.0
+        } else {
+            let failed = filled.iter().filter(|name| flags.iter().any(|f| f.field == **name)).count();
+            1.0 - failed as f32 / filled.len() as f32
+        };
+
+        let confidence = confidence.clamp(0.0, 1.0);
+        let reliability = reliability.clamp(0.0, 1.0);
+        let weights = &self.config.weights;
+        let total = weights.completeness + weights.validation + weights.confidence + weights.reliability;
+        let score = if total > 0.0 {
+            (completeness * weights.completeness
+                + validation * weights.validation
+                + confidence * weights.confidence
+                + reliability * weights.reliability)
+                / total
+        } else {
+            0.0
+        };
+
+        QualityScore { score, completeness, validation, confidence, reliability }
+    }
+
+    /// Configured reliability of a source URL's site; subdomains share their domain's
+    pub fn reliability(&self, source_url: &str) -> f32 {
+        let host = source_host(source_url);
+        self.config
+            .source_reliability
+            .iter()
+            .filter(|(domain, _)| on_domain(&host, domain))
+            // The most specific domain wins
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, reliability)| *reliability)
+            .unwrap_or(self.config.default_reliability)
+    }
+
+    /// Whether a score is high enough for the record to be kept
+    pub fn keeps(&self, score: f32) -> bool {
+        score >= self.config.min_score
+    }
+}